@@ -68,8 +68,9 @@ impl<V: ArrowWriteableValue> VecBuilderStorage<V> {
         None
     }
 
-    fn get(&self, _: &CompositeKey) -> Option<V::PreparedValue> {
-        unimplemented!()
+    fn get(&self, key: &CompositeKey) -> Option<V::PreparedValue> {
+        let index = self.storage.binary_search_by(|(k, _)| k.cmp(key)).ok()?;
+        Some(V::prepare(self.storage[index].1.clone()))
     }
 
     fn min_key(&self) -> Option<&CompositeKey> {
@@ -4,7 +4,7 @@ use crate::arrow::block::value::data_record_value::DataRecordStorageEntry;
 use crate::arrow::types::ArrowWriteableValue;
 use crate::{
     arrow::types::ArrowWriteableKey,
-    key::{CompositeKey, KeyWrapper},
+    key::{CompositeKey, CompositeKeyRange, KeyWrapper},
 };
 use arrow::{array::RecordBatch, util::bit_util};
 use chroma_types::DataRecord;
@@ -112,6 +112,22 @@ impl DataRecordStorage {
         }
     }
 
+    /// Deletes every key in the range and returns the number of keys deleted.
+    pub fn delete_range(&self, range: &CompositeKeyRange) -> usize {
+        let keys = self
+            .inner
+            .read()
+            .storage
+            .keys()
+            .filter(|key| range.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.delete(&key.prefix, key.key.clone());
+        }
+        keys.len()
+    }
+
     pub fn get_min_key(&self) -> Option<CompositeKey> {
         let inner = self.inner.read();
         inner.storage.keys().next().cloned()
@@ -263,7 +279,7 @@ impl DataRecordStorage {
 
         for (key, value) in storage.into_iter() {
             key_builder.add_key(key);
-            <&DataRecord as ArrowWriteableValue>::append(value, &mut value_builder);
+            <&DataRecord as ArrowWriteableValue>::append(value, &mut value_builder)?;
         }
 
         // Build arrow key with fields.
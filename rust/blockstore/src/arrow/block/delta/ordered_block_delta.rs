@@ -4,11 +4,16 @@ use super::{storage::BlockStorage, types::Delta};
 use crate::{
     arrow::{
         block::Block,
-        types::{ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue},
+        types::{
+            ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue,
+            ValueDimension,
+        },
     },
-    key::{CompositeKey, KeyWrapper},
+    key::{CompositeKey, CompositeKeyRange, KeyWrapper},
+    BlockfileWriterMutationOrdering,
 };
 use arrow::array::{RecordBatch, StringArray};
+use chroma_error::ChromaError;
 use uuid::Uuid;
 
 /// This delta type performs mutations more efficiently than the `UnorderedBlockDelta` type if the mutations are already in sorted order.
@@ -80,18 +85,81 @@ impl OrderedBlockDelta {
             }
         }
 
-        self.copy_up_to::<K::ReadableKey<'_>, V::ReadableValue<'_>>(prefix, &wrapped_key);
+        self.copy_up_to::<K::ReadableKey<'_>, V::ReadableValue<'_>>(prefix, &wrapped_key, true);
 
         // TODO: errors?
         V::add(prefix, wrapped_key, value, &self.builder);
     }
 
+    /// Checks that a value can be added to the block delta of a blockfile with the given value
+    /// dimension, including the rows of the old block that are not copied yet, see
+    /// `ValueDimension::check`.
+    pub(crate) fn check<V: ArrowWriteableValue>(
+        &self,
+        value: &V,
+        dimension: &ValueDimension,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        dimension.check(value, &self.builder, self.old_block.as_ref())
+    }
+
     pub fn skip<K, V>(&mut self, prefix: &str, key: K)
     where
         K: ArrowWriteableKey,
         V: ArrowWriteableValue,
     {
-        self.copy_up_to::<K::ReadableKey<'_>, V::ReadableValue<'_>>(prefix, &key.into());
+        self.copy_up_to::<K::ReadableKey<'_>, V::ReadableValue<'_>>(prefix, &key.into(), true);
+    }
+
+    /// Copies the rows of the old block before the range into the delta and skips over the rows
+    /// in the range. Like `.skip()`, keys passed afterwards must be past the end of the range.
+    pub fn skip_range<K, V>(&mut self, range: &CompositeKeyRange)
+    where
+        K: ArrowWriteableKey,
+        V: ArrowWriteableValue,
+    {
+        self.skip_range_of_old_block::<K::ReadableKey<'_>, V::ReadableValue<'_>>(range);
+    }
+
+    /// Get the prepared value of a key, either added to this delta or still pending in the old block.
+    /// Like `.add()` and `.skip()`, this copies the old rows before the key into the delta, so keys
+    /// passed afterwards must not be less than this key.
+    pub fn get_owned<K, V>(&mut self, prefix: &str, key: K) -> Option<V::PreparedValue>
+    where
+        K: ArrowWriteableKey,
+        V: ArrowWriteableValue,
+    {
+        let wrapped_key: KeyWrapper = key.into();
+        self.copy_up_to::<K::ReadableKey<'_>, V::ReadableValue<'_>>(prefix, &wrapped_key, false);
+
+        if let Some(value) =
+            V::get_owned_value_from_delta(prefix, wrapped_key.clone(), &self.builder)
+        {
+            return Some(value);
+        }
+
+        // The key may be the next pending row of the old block. It is not copied into the delta
+        // here because a later `.add()` or `.skip()` of the same key would then duplicate it.
+        let old_block = self.old_block.as_ref()?;
+        let row = self.copied_up_to_row_of_old_block;
+        if row >= old_block.data.num_rows() {
+            return None;
+        }
+        let old_prefix = old_block
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(row);
+        let old_key = K::ReadableKey::get(old_block.data.column(1), row);
+        let old_wrapped_key: KeyWrapper = old_key.clone().into();
+        if old_prefix != prefix || old_wrapped_key != wrapped_key {
+            return None;
+        }
+        let old_value = V::ReadableValue::get(old_block.data.column(2), row);
+        let mut storage = V::get_delta_builder(BlockfileWriterMutationOrdering::Unordered);
+        K::ReadableKey::add_to_delta(old_prefix, old_key, old_value, &mut storage);
+        V::get_owned_value_from_delta(prefix, wrapped_key, &storage)
     }
 
     pub fn copy_to_end<K: ArrowWriteableKey, V: ArrowWriteableValue>(&mut self) {
@@ -131,10 +199,13 @@ impl OrderedBlockDelta {
         self.builder.len()
     }
 
+    /// Copy the rows of the old block before the excluded key into the delta. A row equal to the
+    /// excluded key is skipped over if `skip_excluded` is set, and left pending otherwise.
     fn copy_up_to<'me, K: ArrowReadableKey<'me>, V: ArrowReadableValue<'me>>(
         &'me mut self,
         excluded_prefix: &str,
         excluded_key: &KeyWrapper,
+        skip_excluded: bool,
     ) {
         if let Some(old_block) = self.old_block.as_ref() {
             let prefix_arr = old_block
@@ -168,7 +239,9 @@ impl OrderedBlockDelta {
                         match old_key.clone().into().partial_cmp(excluded_key) {
                             Some(std::cmp::Ordering::Less) => {}
                             Some(std::cmp::Ordering::Equal) => {
-                                self.copied_up_to_row_of_old_block += 1;
+                                if skip_excluded {
+                                    self.copied_up_to_row_of_old_block += 1;
+                                }
                                 break;
                             }
                             Some(std::cmp::Ordering::Greater) => break,
@@ -185,6 +258,38 @@ impl OrderedBlockDelta {
         }
     }
 
+    fn skip_range_of_old_block<'me, K: ArrowReadableKey<'me>, V: ArrowReadableValue<'me>>(
+        &'me mut self,
+        range: &CompositeKeyRange,
+    ) {
+        if let Some(old_block) = self.old_block.as_ref() {
+            let prefix_arr = old_block
+                .data
+                .column(0)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            let key_arr = old_block.data.column(1);
+
+            for i in self.copied_up_to_row_of_old_block..old_block.data.num_rows() {
+                let old_prefix = prefix_arr.value(i);
+                let old_key = K::get(key_arr, i);
+                let old_composite_key = CompositeKey {
+                    prefix: old_prefix.to_string(),
+                    key: old_key.clone().into(),
+                };
+
+                if !range.is_after_start(&old_composite_key) {
+                    let old_value = V::get(old_block.data.column(2), i);
+                    K::add_to_delta(old_prefix, old_key, old_value, &mut self.builder);
+                } else if !range.is_before_end(&old_composite_key) {
+                    break;
+                }
+                self.copied_up_to_row_of_old_block += 1;
+            }
+        }
+    }
+
     ///  Gets the size of the block delta as it would be in a block. This includes
     ///  the size of the prefix, key, and value data and the size of the offsets
     ///  where applicable. The size is rounded up to the nearest 64 bytes as per
@@ -285,4 +390,15 @@ impl OrderedBlockDelta {
     pub(crate) fn min_key(&self) -> Option<CompositeKey> {
         self.builder.get_min_key()
     }
+
+    /// Moves every key value pair of the other delta into this delta. Used to merge the deltas
+    /// of adjacent blocks, so the keys of the other delta must all be after the keys of this one.
+    /// Both deltas must be complete, see `.copy_to_end()`.
+    pub(crate) fn merge<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &mut self,
+        other: OrderedBlockDelta,
+    ) {
+        let other = Block::from_record_batch(other.id, other.finish::<K, V>(None));
+        other.copy_to_delta_storage::<K::ReadableKey<'_>, V::ReadableValue<'_>>(&mut self.builder);
+    }
 }
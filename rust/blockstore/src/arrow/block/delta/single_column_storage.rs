@@ -5,11 +5,11 @@ use super::{
 };
 use crate::{
     arrow::types::{ArrowWriteableKey, ArrowWriteableValue},
-    key::{CompositeKey, KeyWrapper},
+    key::{CompositeKey, CompositeKeyRange, KeyWrapper},
     BlockfileWriterMutationOrdering,
 };
 use arrow::util::bit_util;
-use arrow::{array::Array, datatypes::Schema};
+use arrow::{array::Array, datatypes::Schema, error::ArrowError};
 use parking_lot::RwLock;
 use std::sync::Arc;
 use std::{collections::HashMap, vec};
@@ -32,6 +32,16 @@ impl<V: ArrowWriteableValue> std::fmt::Debug for Inner<V> {
     }
 }
 
+impl SingleColumnStorage<Vec<f32>> {
+    /// The dimension of the vectors in the storage, `None` if it is empty
+    pub(in crate::arrow) fn dimension(&self) -> Option<usize> {
+        let inner = self.inner.read();
+        let num_items = inner.size_tracker.get_num_items();
+        (num_items > 0)
+            .then(|| inner.size_tracker.get_value_size() / std::mem::size_of::<f32>() / num_items)
+    }
+}
+
 impl<V: ArrowWriteableValue<SizeTracker = SingleColumnSizeTracker>> SingleColumnStorage<V> {
     pub(in crate::arrow) fn new(mutation_ordering_hint: BlockfileWriterMutationOrdering) -> Self {
         let storage = match mutation_ordering_hint {
@@ -143,6 +153,23 @@ impl<V: ArrowWriteableValue<SizeTracker = SingleColumnSizeTracker>> SingleColumn
         }
     }
 
+    /// Deletes every key in the range and returns the number of keys deleted.
+    pub fn delete_range(&self, range: &CompositeKeyRange) -> usize {
+        let keys = self
+            .inner
+            .read()
+            .storage
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| range.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.delete(&key.prefix, key.key.clone());
+        }
+        keys.len()
+    }
+
     pub(super) fn split<K: ArrowWriteableKey>(
         &self,
         split_size: usize,
@@ -253,7 +280,7 @@ impl<V: ArrowWriteableValue<SizeTracker = SingleColumnSizeTracker>> SingleColumn
         self,
         mut key_builder: BlockKeyArrowBuilder,
         metadata: Option<HashMap<String, String>>,
-    ) -> (Arc<Schema>, Vec<Arc<dyn Array>>) {
+    ) -> Result<(Arc<Schema>, Vec<Arc<dyn Array>>), ArrowError> {
         let inner = Arc::try_unwrap(self.inner)
             .expect(
                 "Invariant violation: SingleColumnStorage inner should have only one reference.",
@@ -265,7 +292,7 @@ impl<V: ArrowWriteableValue<SizeTracker = SingleColumnSizeTracker>> SingleColumn
         let storage = inner.storage;
         for (key, value) in storage.into_iter() {
             key_builder.add_key(key);
-            V::append(V::prepare(value), &mut value_builder);
+            V::append(V::prepare(value), &mut value_builder)?;
         }
 
         let (prefix_field, prefix_arr, key_field, key_arr) = key_builder.as_arrow();
@@ -274,10 +301,10 @@ impl<V: ArrowWriteableValue<SizeTracker = SingleColumnSizeTracker>> SingleColumn
 
         if let Some(metadata) = metadata {
             let schema = schema.with_metadata(metadata);
-            return (schema.into(), vec![prefix_arr, key_arr, value_arr]);
+            return Ok((schema.into(), vec![prefix_arr, key_arr, value_arr]));
         }
 
-        (schema.into(), vec![prefix_arr, key_arr, value_arr])
+        Ok((schema.into(), vec![prefix_arr, key_arr, value_arr]))
     }
 
     pub fn get_owned_value(&self, prefix: &str, key: KeyWrapper) -> Option<V::PreparedValue> {
@@ -9,7 +9,7 @@ use crate::{
         block::value::spann_posting_list_value::SpannPostingListDeltaEntry,
         types::{ArrowWriteableKey, ArrowWriteableValue},
     },
-    key::{CompositeKey, KeyWrapper},
+    key::{CompositeKey, CompositeKeyRange, KeyWrapper},
 };
 
 use super::{spann_posting_list_size_tracker::SpannPostingListSizeTracker, BlockKeyArrowBuilder};
@@ -114,6 +114,22 @@ impl SpannPostingListDelta {
         }
     }
 
+    /// Deletes every key in the range and returns the number of keys deleted.
+    pub fn delete_range(&self, range: &CompositeKeyRange) -> usize {
+        let keys = self
+            .inner
+            .read()
+            .storage
+            .keys()
+            .filter(|key| range.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            self.delete(&key.prefix, key.key.clone());
+        }
+        keys.len()
+    }
+
     pub(super) fn get_size<K: ArrowWriteableKey>(&self) -> usize {
         let read_guard = self.inner.read();
         let prefix_size =
@@ -245,7 +261,7 @@ impl SpannPostingListDelta {
 
         for (key, value) in storage.into_iter() {
             key_builder.add_key(key);
-            <&SpannPostingList as ArrowWriteableValue>::append(value, &mut value_builder);
+            <&SpannPostingList as ArrowWriteableValue>::append(value, &mut value_builder)?;
         }
 
         // Build arrow key with fields.
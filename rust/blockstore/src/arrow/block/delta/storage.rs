@@ -4,11 +4,12 @@ use super::{
 };
 use crate::{
     arrow::types::ArrowWriteableKey,
-    key::{CompositeKey, KeyWrapper},
+    key::{CompositeKey, CompositeKeyRange, KeyWrapper},
 };
 use arrow::{
     array::{
-        Array, ArrayRef, BooleanBuilder, Float32Builder, RecordBatch, StringBuilder, UInt32Builder,
+        Array, ArrayRef, BinaryBuilder, BooleanBuilder, Float32Builder, Int64Builder, RecordBatch,
+        StringBuilder, UInt32Builder, UInt64Builder,
     },
     datatypes::Field,
 };
@@ -22,6 +23,8 @@ use std::{
 pub enum BlockStorage {
     String(SingleColumnStorage<String>),
     VecUInt32(SingleColumnStorage<Vec<u32>>),
+    VecFloat32(SingleColumnStorage<Vec<f32>>),
+    Bytes(SingleColumnStorage<Vec<u8>>),
     UInt32(SingleColumnStorage<u32>),
     RoaringBitmap(SingleColumnStorage<RoaringBitmap>),
     DataRecord(DataRecordStorage),
@@ -33,6 +36,8 @@ impl Debug for BlockStorage {
         match self {
             BlockStorage::String(_) => f.debug_struct("String").finish(),
             BlockStorage::VecUInt32(_) => f.debug_struct("VecUInt32").finish(),
+            BlockStorage::VecFloat32(_) => f.debug_struct("VecFloat32").finish(),
+            BlockStorage::Bytes(_) => f.debug_struct("Bytes").finish(),
             BlockStorage::UInt32(_) => f.debug_struct("UInt32").finish(),
             BlockStorage::RoaringBitmap(_) => f.debug_struct("RoaringBitmap").finish(),
             BlockStorage::DataRecord(_) => f.debug_struct("DataRecord").finish(),
@@ -48,6 +53,9 @@ pub enum BlockKeyArrowBuilder {
     String((StringBuilder, StringBuilder)),
     Float32((StringBuilder, Float32Builder)),
     UInt32((StringBuilder, UInt32Builder)),
+    UInt64((StringBuilder, UInt64Builder)),
+    Int64((StringBuilder, Int64Builder)),
+    Binary((StringBuilder, BinaryBuilder)),
 }
 
 impl BlockKeyArrowBuilder {
@@ -93,6 +101,36 @@ impl BlockKeyArrowBuilder {
                 builder.0.append_value(key.prefix);
                 builder.1.append_value(value);
             }
+            KeyWrapper::Uint64(value) => {
+                let builder = match self {
+                    BlockKeyArrowBuilder::UInt64(builder) => builder,
+                    _ => {
+                        unreachable!("Invariant violation. BlockKeyArrowBuilder should be UInt64.")
+                    }
+                };
+                builder.0.append_value(key.prefix);
+                builder.1.append_value(value);
+            }
+            KeyWrapper::Int64(value) => {
+                let builder = match self {
+                    BlockKeyArrowBuilder::Int64(builder) => builder,
+                    _ => {
+                        unreachable!("Invariant violation. BlockKeyArrowBuilder should be Int64.")
+                    }
+                };
+                builder.0.append_value(key.prefix);
+                builder.1.append_value(value);
+            }
+            KeyWrapper::Bytes(value) => {
+                let builder = match self {
+                    BlockKeyArrowBuilder::Binary(builder) => builder,
+                    _ => {
+                        unreachable!("Invariant violation. BlockKeyArrowBuilder should be Binary.")
+                    }
+                };
+                builder.0.append_value(key.prefix);
+                builder.1.append_value(value);
+            }
         }
     }
 
@@ -146,6 +184,42 @@ impl BlockKeyArrowBuilder {
                     (&key_arr as &dyn Array).slice(0, key_arr.len()),
                 )
             }
+            BlockKeyArrowBuilder::UInt64((ref mut prefix_builder, ref mut key_builder)) => {
+                let prefix_field = Field::new("prefix", arrow::datatypes::DataType::Utf8, false);
+                let key_field = Field::new("key", arrow::datatypes::DataType::UInt64, false);
+                let prefix_arr = prefix_builder.finish();
+                let key_arr = key_builder.finish();
+                (
+                    prefix_field,
+                    (&prefix_arr as &dyn Array).slice(0, prefix_arr.len()),
+                    key_field,
+                    (&key_arr as &dyn Array).slice(0, key_arr.len()),
+                )
+            }
+            BlockKeyArrowBuilder::Int64((ref mut prefix_builder, ref mut key_builder)) => {
+                let prefix_field = Field::new("prefix", arrow::datatypes::DataType::Utf8, false);
+                let key_field = Field::new("key", arrow::datatypes::DataType::Int64, false);
+                let prefix_arr = prefix_builder.finish();
+                let key_arr = key_builder.finish();
+                (
+                    prefix_field,
+                    (&prefix_arr as &dyn Array).slice(0, prefix_arr.len()),
+                    key_field,
+                    (&key_arr as &dyn Array).slice(0, key_arr.len()),
+                )
+            }
+            BlockKeyArrowBuilder::Binary((ref mut prefix_builder, ref mut key_builder)) => {
+                let prefix_field = Field::new("prefix", arrow::datatypes::DataType::Utf8, false);
+                let key_field = Field::new("key", arrow::datatypes::DataType::Binary, false);
+                let prefix_arr = prefix_builder.finish();
+                let key_arr = key_builder.finish();
+                (
+                    prefix_field,
+                    (&prefix_arr as &dyn Array).slice(0, prefix_arr.len()),
+                    key_field,
+                    (&key_arr as &dyn Array).slice(0, key_arr.len()),
+                )
+            }
         }
     }
 }
@@ -157,6 +231,8 @@ impl BlockStorage {
             BlockStorage::UInt32(builder) => builder.get_prefix_size(),
             BlockStorage::DataRecord(builder) => builder.get_prefix_size(),
             BlockStorage::VecUInt32(builder) => builder.get_prefix_size(),
+            BlockStorage::VecFloat32(builder) => builder.get_prefix_size(),
+            BlockStorage::Bytes(builder) => builder.get_prefix_size(),
             BlockStorage::RoaringBitmap(builder) => builder.get_prefix_size(),
             BlockStorage::SpannPostingListDelta(builder) => builder.get_prefix_size(),
         }
@@ -168,6 +244,8 @@ impl BlockStorage {
             BlockStorage::UInt32(builder) => builder.get_key_size(),
             BlockStorage::DataRecord(builder) => builder.get_key_size(),
             BlockStorage::VecUInt32(builder) => builder.get_key_size(),
+            BlockStorage::VecFloat32(builder) => builder.get_key_size(),
+            BlockStorage::Bytes(builder) => builder.get_key_size(),
             BlockStorage::RoaringBitmap(builder) => builder.get_key_size(),
             BlockStorage::SpannPostingListDelta(builder) => builder.get_key_size(),
         }
@@ -179,11 +257,27 @@ impl BlockStorage {
             BlockStorage::UInt32(builder) => builder.get_min_key(),
             BlockStorage::DataRecord(builder) => builder.get_min_key(),
             BlockStorage::VecUInt32(builder) => builder.get_min_key(),
+            BlockStorage::VecFloat32(builder) => builder.get_min_key(),
+            BlockStorage::Bytes(builder) => builder.get_min_key(),
             BlockStorage::RoaringBitmap(builder) => builder.get_min_key(),
             BlockStorage::SpannPostingListDelta(builder) => builder.get_min_key(),
         }
     }
 
+    /// Deletes every key in the range and returns the number of keys deleted.
+    pub fn delete_range(&self, range: &CompositeKeyRange) -> usize {
+        match self {
+            BlockStorage::String(builder) => builder.delete_range(range),
+            BlockStorage::UInt32(builder) => builder.delete_range(range),
+            BlockStorage::DataRecord(builder) => builder.delete_range(range),
+            BlockStorage::VecUInt32(builder) => builder.delete_range(range),
+            BlockStorage::VecFloat32(builder) => builder.delete_range(range),
+            BlockStorage::Bytes(builder) => builder.delete_range(range),
+            BlockStorage::RoaringBitmap(builder) => builder.delete_range(range),
+            BlockStorage::SpannPostingListDelta(builder) => builder.delete_range(range),
+        }
+    }
+
     /// Returns the arrow-padded (rounded to 64 bytes) size for the delta.
     pub fn get_size<K: ArrowWriteableKey>(&self) -> usize {
         match self {
@@ -191,6 +285,8 @@ impl BlockStorage {
             BlockStorage::UInt32(builder) => builder.get_size::<K>(),
             BlockStorage::DataRecord(builder) => builder.get_size::<K>(),
             BlockStorage::VecUInt32(builder) => builder.get_size::<K>(),
+            BlockStorage::VecFloat32(builder) => builder.get_size::<K>(),
+            BlockStorage::Bytes(builder) => builder.get_size::<K>(),
             BlockStorage::RoaringBitmap(builder) => builder.get_size::<K>(),
             BlockStorage::SpannPostingListDelta(builder) => builder.get_size::<K>(),
         }
@@ -214,6 +310,14 @@ impl BlockStorage {
                 let (split_key, storage) = builder.split::<K>(split_size);
                 (split_key, BlockStorage::VecUInt32(storage))
             }
+            BlockStorage::VecFloat32(builder) => {
+                let (split_key, storage) = builder.split::<K>(split_size);
+                (split_key, BlockStorage::VecFloat32(storage))
+            }
+            BlockStorage::Bytes(builder) => {
+                let (split_key, storage) = builder.split::<K>(split_size);
+                (split_key, BlockStorage::Bytes(storage))
+            }
             BlockStorage::RoaringBitmap(builder) => {
                 let (split_key, storage) = builder.split::<K>(split_size);
                 (split_key, BlockStorage::RoaringBitmap(storage))
@@ -231,6 +335,8 @@ impl BlockStorage {
             BlockStorage::UInt32(builder) => builder.len(),
             BlockStorage::DataRecord(builder) => builder.len(),
             BlockStorage::VecUInt32(builder) => builder.len(),
+            BlockStorage::VecFloat32(builder) => builder.len(),
+            BlockStorage::Bytes(builder) => builder.len(),
             BlockStorage::RoaringBitmap(builder) => builder.len(),
             BlockStorage::SpannPostingListDelta(builder) => builder.len(),
         }
@@ -245,13 +351,17 @@ impl BlockStorage {
         match self {
             BlockStorage::String(builder) => {
                 // TODO: handle error
-                let (schema, columns) = builder.into_arrow(key_builder, metadata);
-                RecordBatch::try_new(schema, columns).unwrap()
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
             }
             BlockStorage::UInt32(builder) => {
                 // TODO: handle error
-                let (schema, columns) = builder.into_arrow(key_builder, metadata);
-                RecordBatch::try_new(schema, columns).unwrap()
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
             }
             BlockStorage::DataRecord(builder) => {
                 // TODO: handle error
@@ -259,13 +369,31 @@ impl BlockStorage {
             }
             BlockStorage::VecUInt32(builder) => {
                 // TODO: handle error
-                let (schema, columns) = builder.into_arrow(key_builder, metadata);
-                RecordBatch::try_new(schema, columns).unwrap()
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
+            }
+            BlockStorage::VecFloat32(builder) => {
+                // TODO: handle error
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
+            }
+            BlockStorage::Bytes(builder) => {
+                // TODO: handle error
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
             }
             BlockStorage::RoaringBitmap(builder) => {
                 // TODO: handle error
-                let (schema, columns) = builder.into_arrow(key_builder, metadata);
-                RecordBatch::try_new(schema, columns).unwrap()
+                builder
+                    .into_arrow(key_builder, metadata)
+                    .and_then(|(schema, columns)| RecordBatch::try_new(schema, columns))
+                    .unwrap()
             }
             BlockStorage::SpannPostingListDelta(builder) => {
                 // TODO: handle error
@@ -4,11 +4,12 @@ use super::{storage::BlockStorage, types::Delta};
 use crate::{
     arrow::{
         block::Block,
-        types::{ArrowWriteableKey, ArrowWriteableValue},
+        types::{ArrowWriteableKey, ArrowWriteableValue, ValueDimension},
     },
-    key::CompositeKey,
+    key::{CompositeKey, CompositeKeyRange},
 };
 use arrow::array::RecordBatch;
+use chroma_error::ChromaError;
 use uuid::Uuid;
 
 /// This is the delta type used by most applications.
@@ -65,11 +66,32 @@ impl UnorderedBlockDelta {
         V::add(prefix, key.into(), value, &self.builder);
     }
 
+    /// Checks that a value can be added to the block delta of a blockfile with the given value
+    /// dimension, see `ValueDimension::check`.
+    pub(crate) fn check<V: ArrowWriteableValue>(
+        &self,
+        value: &V,
+        dimension: &ValueDimension,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        dimension.check(value, &self.builder, None)
+    }
+
+    /// Records the dimension of the values of the block delta before they are deleted, see
+    /// `ValueDimension::observe`.
+    pub(crate) fn observe_dimension<V: ArrowWriteableValue>(&self, dimension: &ValueDimension) {
+        dimension.observe::<V>(&self.builder, None)
+    }
+
     /// Deletes a key from the block delta.
     pub fn delete<K: ArrowWriteableKey, V: ArrowWriteableValue>(&self, prefix: &str, key: K) {
         V::delete(prefix, key.into(), self)
     }
 
+    /// Deletes every key in the range from the block delta and returns the number of keys deleted.
+    pub fn delete_range(&self, range: &CompositeKeyRange) -> usize {
+        self.builder.delete_range(range)
+    }
+
     ///  Gets the size of the block delta as it would be in a block. This includes
     ///  the size of the prefix, key, and value data and the size of the offsets
     ///  where applicable. The size is rounded up to the nearest 64 bytes as per
@@ -128,6 +150,16 @@ impl UnorderedBlockDelta {
         output
     }
 
+    /// Moves every key value pair of the other delta into this delta. Used to merge the deltas
+    /// of adjacent blocks, so the keys of the other delta must all be after the keys of this one.
+    pub(crate) fn merge<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &mut self,
+        other: UnorderedBlockDelta,
+    ) {
+        let other = Block::from_record_batch(other.id, other.finish::<K, V>(None));
+        other.copy_to_delta_storage::<K::ReadableKey<'_>, V::ReadableValue<'_>>(&mut self.builder);
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.builder.len()
     }
@@ -162,6 +194,17 @@ mod test {
         let loaded = Block::load_with_validation(&save_path, block.id).unwrap();
         assert_eq!(loaded.id, block.id);
         assert_eq!(block.get_size(), loaded.get_size());
+
+        // The mmap load path must produce the same block with buffers that stay aligned
+        let mapped = Block::load(&save_path, block.id).unwrap();
+        assert_eq!(mapped.id, block.id);
+        assert_eq!(*mapped.data, *loaded.data);
+        assert_eq!(block.get_size(), mapped.get_size());
+        for column in mapped.data.columns() {
+            for buffer in column.to_data().buffers() {
+                assert_eq!(buffer.as_ptr().align_offset(64), 0);
+            }
+        }
         loaded
     }
 
@@ -196,9 +239,12 @@ mod test {
             values_before_flush.push(read.to_vec());
         }
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let block = block_manager
-            .get(prefix_path, &block.id, StorageRequestPriority::P0)
+            .get(prefix_path, &block.id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -240,10 +286,13 @@ mod test {
             values_before_flush.push(read.unwrap().to_string());
         }
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
 
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -267,14 +316,17 @@ mod test {
 
         // test fork
         let forked_block = block_manager
-            .fork::<&str, String, UnorderedBlockDelta>(&delta_id, prefix_path)
+            .fork::<&str, String, UnorderedBlockDelta>(&delta_id, prefix_path, None)
             .await
             .unwrap();
         let new_id = forked_block.id;
         let block = block_manager.commit::<&str, String>(forked_block).await;
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let forked_block = block_manager
-            .get(prefix_path, &new_id, StorageRequestPriority::P0)
+            .get(prefix_path, &new_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -312,9 +364,12 @@ mod test {
             values_before_flush.push(read);
         }
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -350,9 +405,12 @@ mod test {
         let delta_id = delta.id;
         let block = block_manager.commit::<&str, RoaringBitmap>(delta).await;
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -420,9 +478,12 @@ mod test {
         let delta_id = delta.id;
         let block = block_manager.commit::<&str, &DataRecord>(delta).await;
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -460,9 +521,12 @@ mod test {
         let delta_id = delta.id;
         let block = block_manager.commit::<u32, String>(delta).await;
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -500,10 +564,13 @@ mod test {
             values_before_flush.push(read.unwrap().to_string());
         }
         let prefix_path = "";
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
 
         let block = block_manager
-            .get(prefix_path, &delta_id, StorageRequestPriority::P0)
+            .get(prefix_path, &delta_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -527,14 +594,17 @@ mod test {
 
         // test fork
         let forked_block = block_manager
-            .fork::<u32, u32, UnorderedBlockDelta>(&delta_id, prefix_path)
+            .fork::<u32, u32, UnorderedBlockDelta>(&delta_id, prefix_path, None)
             .await
             .unwrap();
         let new_id = forked_block.id;
         let block = block_manager.commit::<u32, u32>(forked_block).await;
-        block_manager.flush(&block, prefix_path).await.unwrap();
+        block_manager
+            .flush(&block, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
         let forked_block = block_manager
-            .get(prefix_path, &new_id, StorageRequestPriority::P0)
+            .get(prefix_path, &new_id, None, StorageRequestPriority::P0)
             .await
             .unwrap()
             .unwrap();
@@ -0,0 +1,49 @@
+use crate::{
+    arrow::{
+        block::delta::{BlockKeyArrowBuilder, BlockStorage},
+        types::{ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey},
+    },
+    BytesKey,
+};
+use arrow::{
+    array::{Array, BinaryArray, BinaryBuilder, StringBuilder},
+    util::bit_util,
+};
+use std::sync::Arc;
+
+impl ArrowWriteableKey for BytesKey<'_> {
+    type ReadableKey<'referred_data> = BytesKey<'referred_data>;
+
+    fn offset_size(item_count: usize) -> usize {
+        bit_util::round_upto_multiple_of_64((item_count + 1) * 4)
+    }
+    fn get_arrow_builder(
+        item_count: usize,
+        prefix_capacity: usize,
+        capacity: usize,
+    ) -> BlockKeyArrowBuilder {
+        let prefix_builder = StringBuilder::with_capacity(item_count, prefix_capacity);
+        let key_builder = BinaryBuilder::with_capacity(item_count, capacity);
+        BlockKeyArrowBuilder::Binary((prefix_builder, key_builder))
+    }
+}
+
+impl<'referred_data> ArrowReadableKey<'referred_data> for BytesKey<'referred_data> {
+    fn get(array: &'referred_data Arc<dyn Array>, index: usize) -> BytesKey<'referred_data> {
+        BytesKey(
+            array
+                .as_any()
+                .downcast_ref::<BinaryArray>()
+                .unwrap()
+                .value(index),
+        )
+    }
+    fn add_to_delta<'external, V: ArrowReadableValue<'external>>(
+        prefix: &str,
+        key: Self,
+        value: V,
+        storage: &mut BlockStorage,
+    ) {
+        V::add_to_delta(prefix, key, value, storage);
+    }
+}
@@ -1,4 +1,7 @@
 pub(super) mod bool_key;
+pub(super) mod bytes_key;
 pub(super) mod f32_key;
+pub(super) mod i64_key;
 pub(super) mod str_key;
 pub(super) mod u32_key;
+pub(super) mod u64_key;
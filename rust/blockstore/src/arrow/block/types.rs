@@ -1,16 +1,34 @@
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::io::SeekFrom;
 use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::Arc;
 
+use crate::arrow::bloom_filter::BlockBloomFilter;
+use crate::arrow::config::BlockCompression;
+use crate::arrow::encryption::{is_encrypted, BlockEncryption, EncryptionError};
 use crate::arrow::types::{ArrowReadableKey, ArrowReadableValue};
+use crate::key::{CompositeKey, KeyWrapper};
 use arrow::array::ArrayData;
-use arrow::buffer::Buffer;
-use arrow::ipc::reader::read_footer_length;
-use arrow::ipc::{root_as_footer, root_as_message, MessageHeader, MetadataVersion};
+use arrow::buffer::{Buffer, MutableBuffer};
+use arrow::datatypes::Schema;
+use arrow::error::ArrowError;
+use arrow::ipc::convert::fb_to_schema;
+use arrow::ipc::reader::{read_footer_length, FileDecoder};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow::ipc::{
+    root_as_footer, root_as_message, CompressionType, MessageHeader, MetadataVersion,
+};
 use arrow::util::bit_util;
 use arrow::{
-    array::{Array, StringArray},
+    array::{
+        Array, BinaryArray, BooleanArray, Float32Array, Int64Array, StringArray, UInt32Array,
+        UInt64Array,
+    },
+    datatypes::DataType,
     record_batch::RecordBatch,
 };
 use chroma_error::{ChromaError, ErrorCodes};
@@ -20,9 +38,11 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
-use super::delta::UnorderedBlockDelta;
+use super::delta::{BlockStorage, UnorderedBlockDelta};
 
 const ARROW_ALIGNMENT: usize = 64;
+// The schema metadata key under which the compression codec of a block is stored
+const COMPRESSION_METADATA_KEY: &str = "compression";
 
 /// A RecordBatchWrapper looks like a record batch, but also implements serde's Serialize and
 /// Deserialize.
@@ -83,19 +103,151 @@ impl<'de> Deserialize<'de> for RecordBatchWrapper {
 /// A Block holds BlockData via its Inner. Conceptually, the BlockData being loaded into memory is an optimization. The Block interface
 /// could also support out of core operations where the BlockData is loaded from disk on demand. Currently we force operations to be in-core
 /// but could expand to out-of-core in the future.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug)]
 pub struct Block {
     // The data is stored in an Arrow record batch with the column schema (prefix, key, value).
     // These are stored in sorted order by prefix and key for efficient lookups.
     pub data: RecordBatchWrapper,
     pub id: Uuid,
+    // Set when the block was deserialized from the block cache and its bytes did not match
+    // the checksum they were written with. Such a block holds no data and must be evicted.
+    corrupted: bool,
+    // Set by the block manager when blocks are encrypted at rest. The block is written to
+    // storage and to the disk cache as these bytes, so it is only encrypted once.
+    encrypted: Option<Buffer>,
+    // Set when the block was deserialized from the block cache in encrypted form. Such a block
+    // holds no data until the block manager decrypts it with `.unseal()`.
+    sealed: bool,
+}
+
+/// The serialized form of a block in a persistent cache. The checksum covers the
+/// serialized record batch so that corrupted cache entries are detected on load.
+#[derive(Serialize)]
+struct SerializedBlockRef<'a> {
+    #[serde(serialize_with = "serialize_bytes")]
+    data: &'a [u8],
+    id: Uuid,
+    checksum: u32,
+}
+
+#[derive(Deserialize)]
+struct SerializedBlock {
+    data: Vec<u8>,
+    id: Uuid,
+    checksum: u32,
+}
+
+fn serialize_bytes<S>(data: &&[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_bytes(data)
+}
+
+impl Serialize for Block {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let data = match &self.encrypted {
+            Some(encrypted) => Cow::Borrowed(encrypted.as_slice()),
+            None => Cow::Owned(Block::record_batch_to_bytes(&self.data).map_err(S::Error::custom)?),
+        };
+        SerializedBlockRef {
+            data: &data,
+            id: self.id,
+            checksum: Block::checksum_of(&data),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let serialized = SerializedBlock::deserialize(deserializer)?;
+        if Block::checksum_of(&serialized.data) != serialized.checksum {
+            // We do not fail deserialization here because the cache would swallow the error,
+            // instead the block manager checks for corruption and evicts the entry.
+            return Ok(Block {
+                data: RecordBatch::new_empty(Arc::new(Schema::empty())).into(),
+                id: serialized.id,
+                corrupted: true,
+                encrypted: None,
+                sealed: false,
+            });
+        }
+        if is_encrypted(&serialized.data) {
+            // Decrypting needs the key provider of the block manager, which unseals the block
+            return Ok(Block {
+                data: RecordBatch::new_empty(Arc::new(Schema::empty())).into(),
+                id: serialized.id,
+                corrupted: false,
+                encrypted: Some(Buffer::from_vec(serialized.data)),
+                sealed: true,
+            });
+        }
+        // Decode over the deserialized bytes instead of copying them again into Arrow buffers
+        Block::from_buffer(Buffer::from_vec(serialized.data), serialized.id)
+            .map_err(D::Error::custom)
+    }
 }
 
 impl Block {
     /// Create a concrete block from an id and the underlying record batch of data
     pub fn from_record_batch(id: Uuid, data: RecordBatch) -> Self {
         let data = data.into();
-        Self { id, data }
+        Self {
+            id,
+            data,
+            corrupted: false,
+            encrypted: None,
+            sealed: false,
+        }
+    }
+
+    /// Computes the checksum of the serialized bytes of a block
+    pub fn checksum_of(bytes: &[u8]) -> u32 {
+        crc32fast::hash(bytes)
+    }
+
+    /// Returns true if the block failed its checksum when it was read from the block cache
+    pub(crate) fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+
+    /// Returns the block with the encrypted bytes it is written to storage and the disk cache as
+    pub(crate) fn with_encrypted_bytes(mut self, encrypted: Buffer) -> Self {
+        self.encrypted = Some(encrypted);
+        self
+    }
+
+    /// Returns the encrypted bytes of the block, if it is encrypted at rest
+    pub(crate) fn encrypted_bytes(&self) -> Option<&[u8]> {
+        self.encrypted
+            .as_ref()
+            .map(|encrypted| encrypted.as_slice())
+    }
+
+    /// Returns true if the block was read from the block cache in encrypted form
+    pub(crate) fn is_sealed(&self) -> bool {
+        self.sealed
+    }
+
+    /// Decrypts a block that was read from the block cache in encrypted form.
+    /// The block keeps its encrypted bytes, so caching it again does not encrypt it again.
+    pub(crate) async fn unseal(
+        &self,
+        encryption: &BlockEncryption,
+    ) -> Result<Block, BlockLoadError> {
+        let Some(encrypted) = self.encrypted.as_ref().filter(|_| self.sealed) else {
+            return Ok(self.clone());
+        };
+        let bytes = encryption.decrypt(encrypted.as_slice(), &self.id).await?;
+        Ok(Block::from_buffer(Buffer::from_vec(bytes), self.id)?
+            .with_encrypted_bytes(encrypted.clone()))
     }
 
     /// Converts the block to a block delta for writing to a new block
@@ -103,6 +255,19 @@ impl Block {
         &'me self,
         mut delta: UnorderedBlockDelta,
     ) -> UnorderedBlockDelta {
+        self.copy_to_delta_storage::<K, V>(&mut delta.builder);
+        delta
+    }
+
+    /// Adds every key value pair of the block to the storage of a delta
+    pub(crate) fn copy_to_delta_storage<
+        'me,
+        K: ArrowReadableKey<'me>,
+        V: ArrowReadableValue<'me>,
+    >(
+        &'me self,
+        storage: &mut BlockStorage,
+    ) {
         let prefix_arr = self
             .data
             .column(0)
@@ -114,9 +279,43 @@ impl Block {
             let key = K::get(self.data.column(1), i);
             let value = V::get(self.data.column(2), i);
 
-            K::add_to_delta(prefix, key, value, &mut delta.builder);
+            K::add_to_delta(prefix, key, value, storage);
         }
-        delta
+    }
+
+    /// Builds a bloom filter over the (prefix, key) pairs stored in this block
+    pub(crate) fn build_bloom_filter<'me, K: ArrowReadableKey<'me>>(
+        &'me self,
+        bits_per_key: usize,
+    ) -> BlockBloomFilter {
+        let prefix_arr = self
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let mut bloom_filter = BlockBloomFilter::new(self.len(), bits_per_key);
+        for i in 0..self.data.num_rows() {
+            let key = K::get(self.data.column(1), i);
+            bloom_filter.insert(&CompositeKey::new(prefix_arr.value(i).to_string(), key));
+        }
+        bloom_filter
+    }
+
+    /// Returns the largest (prefix, key) pair stored in this block, or None if the block is empty
+    pub(crate) fn last_key<'me, K: ArrowReadableKey<'me>>(&'me self) -> Option<CompositeKey> {
+        let last_index = self.len().checked_sub(1)?;
+        let prefix_arr = self
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let key = K::get(self.data.column(1), last_index);
+        Some(CompositeKey::new(
+            prefix_arr.value(last_index).to_string(),
+            key,
+        ))
     }
 
     /// Binary searches this slice with a comparator function.
@@ -327,22 +526,93 @@ impl Block {
         PrefixRange: RangeBounds<&'prefix str>,
         KeyRange: RangeBounds<K>,
     {
-        let mut index_ranges = Vec::new();
+        self.get_range_indices(prefix_range, key_range)
+            .into_iter()
+            .flatten()
+            .map(|index| self.get_prefix_key_value_at::<K, V>(index))
+    }
+
+    /// Get all the values for a given prefix & key range in the block, in descending order
+    ///
+    /// ### Example
+    /// If we have block: [(p0, k0, v0), (p0, k1, v1), (p1, k0, v2), (p1, k1, v3), (p2, k1, v4)]
+    /// Then block.get_range_rev(p0..p2, k1..) will return [(p1, k1, v3), (p0, k1, v1)]
+    pub fn get_range_rev<
+        'prefix,
+        'me,
+        K: ArrowReadableKey<'me>,
+        V: ArrowReadableValue<'me>,
+        PrefixRange,
+        KeyRange,
+    >(
+        &'me self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> impl Iterator<Item = (&'me str, K, V)> + 'me
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+        KeyRange: RangeBounds<K>,
+    {
+        self.get_range_indices(prefix_range, key_range)
+            .into_iter()
+            .rev()
+            .flat_map(|index_range| index_range.rev())
+            .map(|index| self.get_prefix_key_value_at::<K, V>(index))
+    }
+
+    /// Get the record at the given position in the block, or None if the block is shorter
+    pub(crate) fn get_at_index<'me, K: ArrowReadableKey<'me>, V: ArrowReadableValue<'me>>(
+        &'me self,
+        index: usize,
+    ) -> Option<(&'me str, K, V)> {
+        (index < self.len()).then(|| self.get_prefix_key_value_at::<K, V>(index))
+    }
+
+    /// Returns the positions of the records with the given prefix in the block
+    pub(crate) fn get_prefix_indices<'me, K: ArrowReadableKey<'me>>(
+        &'me self,
+        prefix: &str,
+    ) -> std::ops::Range<usize> {
+        self.find_smallest_index_of_prefix::<K>(prefix)
+            ..self.find_smallest_index_of_next_prefix::<K>(prefix)
+    }
 
+    fn get_prefix_key_value_at<'me, K: ArrowReadableKey<'me>, V: ArrowReadableValue<'me>>(
+        &'me self,
+        index: usize,
+    ) -> (&'me str, K, V) {
         let prefix_array = self
             .data
             .column(0)
             .as_any()
             .downcast_ref::<StringArray>()
             .unwrap();
+        (
+            prefix_array.value(index),
+            K::get(self.data.column(1), index),
+            V::get(self.data.column(2), index),
+        )
+    }
 
-        let prefix_key_val_getter = |index| {
-            (
-                prefix_array.value(index),
-                K::get(self.data.column(1), index),
-                V::get(self.data.column(2), index),
-            )
-        };
+    /// Returns the ascending, non-overlapping index ranges of the records that fall in
+    /// the given prefix & key range
+    fn get_range_indices<'prefix, 'me, K: ArrowReadableKey<'me>, PrefixRange, KeyRange>(
+        &'me self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> Vec<std::ops::Range<usize>>
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+        KeyRange: RangeBounds<K>,
+    {
+        let mut index_ranges = Vec::new();
+
+        let prefix_array = self
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
 
         let mut cursor_prefix_index = match prefix_range.start_bound() {
             Bound::Included(prefix) => self.find_smallest_index_of_prefix::<K>(prefix),
@@ -359,10 +629,7 @@ impl Block {
                 Bound::Unbounded => self.len(),
             };
             index_ranges.push(cursor_prefix_index..final_prefix_index);
-            return index_ranges
-                .into_iter()
-                .flatten()
-                .map(prefix_key_val_getter);
+            return index_ranges;
         }
 
         while cursor_prefix_index < self.len() {
@@ -405,9 +672,6 @@ impl Block {
         }
 
         index_ranges
-            .into_iter()
-            .flatten()
-            .map(prefix_key_val_getter)
     }
 
     /*
@@ -424,6 +688,74 @@ impl Block {
         total_size
     }
 
+    /// Returns each distinct prefix stored in this block with its number of keys, in prefix order
+    pub(crate) fn prefix_counts(&self) -> Vec<(&str, usize)> {
+        let prefix_arr = self
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let mut prefix_counts: Vec<(&str, usize)> = Vec::new();
+        for prefix in prefix_arr.iter().flatten() {
+            match prefix_counts.last_mut() {
+                Some((last_prefix, count)) if *last_prefix == prefix => *count += 1,
+                _ => prefix_counts.push((prefix, 1)),
+            }
+        }
+        prefix_counts
+    }
+
+    /// Returns the (prefix, key) of every item in the block in order. Unlike `.get_range()`,
+    /// this does not need the key type and the keys do not borrow from the block.
+    pub(crate) fn composite_keys(&self) -> impl Iterator<Item = CompositeKey> + '_ {
+        let prefix_arr = self
+            .data
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let key_arr = self.data.column(1);
+        (0..self.len()).map(move |index| CompositeKey {
+            prefix: prefix_arr.value(index).to_string(),
+            key: Self::key_at(key_arr, index),
+        })
+    }
+
+    fn key_at(key_arr: &Arc<dyn Array>, index: usize) -> KeyWrapper {
+        let any = key_arr.as_any();
+        match key_arr.data_type() {
+            DataType::Utf8 => KeyWrapper::String(
+                any.downcast_ref::<StringArray>()
+                    .unwrap()
+                    .value(index)
+                    .to_string(),
+            ),
+            DataType::Float32 => {
+                KeyWrapper::Float32(any.downcast_ref::<Float32Array>().unwrap().value(index))
+            }
+            DataType::Boolean => {
+                KeyWrapper::Bool(any.downcast_ref::<BooleanArray>().unwrap().value(index))
+            }
+            DataType::UInt32 => {
+                KeyWrapper::Uint32(any.downcast_ref::<UInt32Array>().unwrap().value(index))
+            }
+            DataType::UInt64 => {
+                KeyWrapper::Uint64(any.downcast_ref::<UInt64Array>().unwrap().value(index))
+            }
+            DataType::Int64 => {
+                KeyWrapper::Int64(any.downcast_ref::<Int64Array>().unwrap().value(index))
+            }
+            DataType::Binary => KeyWrapper::Bytes(
+                any.downcast_ref::<BinaryArray>()
+                    .unwrap()
+                    .value(index)
+                    .to_vec(),
+            ),
+            data_type => panic!("Unsupported key type {}", data_type),
+        }
+    }
+
     /// Returns the number of items in the block
     pub(crate) fn len(&self) -> usize {
         self.data.num_rows()
@@ -438,6 +770,44 @@ impl Block {
         schema.metadata()
     }
 
+    /// Returns the block with the given compression codec recorded in its metadata.
+    /// The codec is applied whenever the block is serialized, i.e when it is flushed to
+    /// storage or written to the disk cache.
+    pub(crate) fn with_compression(self, compression: BlockCompression) -> Self {
+        let schema = self.data.schema();
+        let mut metadata = schema.metadata().clone();
+        match compression {
+            BlockCompression::None => {
+                if metadata.remove(COMPRESSION_METADATA_KEY).is_none() {
+                    return self;
+                }
+            }
+            _ => {
+                metadata.insert(
+                    COMPRESSION_METADATA_KEY.to_string(),
+                    compression_to_metadata_value(compression).to_string(),
+                );
+            }
+        }
+        let schema = Arc::new(schema.as_ref().clone().with_metadata(metadata));
+        let data = self
+            .data
+            .0
+            .with_schema(schema)
+            .expect("Changing only the schema metadata should never fail");
+        Self::from_record_batch(self.id, data)
+    }
+
+    /// Returns the compression codec the block is serialized with
+    pub fn compression(&self) -> BlockCompression {
+        self.data
+            .schema_ref()
+            .metadata()
+            .get(COMPRESSION_METADATA_KEY)
+            .and_then(|value| compression_from_metadata_value(value))
+            .unwrap_or_default()
+    }
+
     /*
         ===== Block Serialization =====
     */
@@ -454,11 +824,7 @@ impl Block {
         // We force the block to be written with 64 byte alignment
         // this is the default, but we are just being defensive
         let mut writer = std::io::BufWriter::new(file);
-        let options = match arrow::ipc::writer::IpcWriteOptions::try_new(
-            ARROW_ALIGNMENT,
-            false,
-            MetadataVersion::V5,
-        ) {
+        let options = match ipc_write_options(&self.data) {
             Ok(options) => options,
             Err(e) => {
                 return Err(BlockSaveError::ArrowError(e));
@@ -495,8 +861,18 @@ impl Block {
         let mut bytes = Vec::new();
         // Scope the writer so that it is dropped before we return the bytes
         {
-            let mut writer = match arrow::ipc::writer::FileWriter::try_new(&mut bytes, &rb.schema())
-            {
+            let options = match ipc_write_options(rb) {
+                Ok(options) => options,
+                Err(e) => {
+                    return Err(BlockToBytesError::ArrowError(e));
+                }
+            };
+            let writer = arrow::ipc::writer::FileWriter::try_new_with_options(
+                &mut bytes,
+                &rb.schema(),
+                options,
+            );
+            let mut writer = match writer {
                 Ok(writer) => writer,
                 Err(e) => {
                     return Err(BlockToBytesError::ArrowError(e));
@@ -532,6 +908,9 @@ impl Block {
     }
 
     fn from_bytes_internal(bytes: &[u8], id: Uuid, validate: bool) -> Result<Self, BlockLoadError> {
+        if is_encrypted(bytes) {
+            return Err(BlockLoadError::Encrypted);
+        }
         let cursor = std::io::Cursor::new(bytes);
         Self::load_with_reader(cursor, id, validate)
     }
@@ -544,9 +923,34 @@ impl Block {
         Self::load_internal(path, id, true)
     }
 
-    /// Load a block from the given path with the given id
+    /// Load a block from the given path with the given id by memory mapping the file.
+    /// The record batch is built directly over the mapping, so the column buffers are
+    /// not copied and are paged in on demand.
+    /// ### Notes
+    /// - The file must not be modified or truncated while the block is alive, see `.map_file()`
+    /// - Compressed blocks are decompressed into owned buffers
     pub fn load(path: &str, id: Uuid) -> Result<Self, BlockLoadError> {
-        Self::load_internal(path, id, false)
+        Self::from_buffer(Self::map_file(Path::new(path))?, id)
+    }
+
+    /// Memory map a file into a buffer that owns the mapping.
+    /// ### Notes
+    /// - Reading a mapping past the end of a file that was truncated after it was mapped raises
+    ///   SIGBUS instead of returning an error, and writes to the file show through the mapping.
+    ///   Only files that are complete and never written again may be mapped. Every block file
+    ///   that is mapped is either written to a temporary file and renamed into place, or is
+    ///   written once under a fresh block id before any root refers to it.
+    /// - Deleting a mapped file only unlinks it, the mapping stays valid
+    pub(crate) fn map_file(path: &Path) -> Result<Buffer, BlockLoadError> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: mapped block files are never modified once they are complete, see above.
+        // Checks against the length of the mapping catch files that were truncated earlier.
+        let mmap = unsafe { memmap2::Mmap::map(&file) }?;
+        let len = mmap.len();
+        let ptr = NonNull::new(mmap.as_ptr() as *mut u8).expect("Mmap pointer is never null");
+        // SAFETY: the buffer owns the mapping, which stays valid for its whole lifetime.
+        // The mapping is page aligned, so it is also ARROW_ALIGNMENT aligned.
+        Ok(unsafe { Buffer::from_custom_allocation(ptr, len, Arc::new(mmap)) })
     }
 
     fn load_internal(path: &str, id: Uuid, validate: bool) -> Result<Self, BlockLoadError> {
@@ -561,6 +965,77 @@ impl Block {
         Self::load_with_reader(reader, id, validate)
     }
 
+    /// Load a block from a buffer in Arrow IPC format with the given id without copying
+    /// the column buffers out of it.
+    /// ### Notes
+    /// - If the buffer itself is not 64 byte aligned it is copied once into an aligned buffer
+    pub fn from_buffer(buffer: Buffer, id: Uuid) -> Result<Self, BlockLoadError> {
+        let batch = Self::decode_record_batch(buffer)?;
+        Ok(Self::from_record_batch(id, batch))
+    }
+
+    fn decode_record_batch(buffer: Buffer) -> Result<RecordBatch, BlockLoadError> {
+        if is_encrypted(buffer.as_slice()) {
+            return Err(BlockLoadError::Encrypted);
+        }
+        let buffer = if buffer.as_ptr().align_offset(ARROW_ALIGNMENT) != 0 {
+            let mut aligned = MutableBuffer::new(buffer.len());
+            aligned.extend_from_slice(buffer.as_slice());
+            aligned.into()
+        } else {
+            buffer
+        };
+
+        // Space for ARROW_MAGIC (6 bytes) and length (4 bytes)
+        if buffer.len() < 10 {
+            return Err(BlockLoadError::InvalidIpcFile);
+        }
+        let trailer_start = buffer.len() - 10;
+        let footer_len = read_footer_length(
+            buffer[trailer_start..]
+                .try_into()
+                .expect("Trailer is 10 bytes"),
+        )?;
+        if footer_len > trailer_start {
+            return Err(BlockLoadError::InvalidIpcFile);
+        }
+        let footer = root_as_footer(&buffer[trailer_start - footer_len..trailer_start])
+            .map_err(|_| BlockLoadError::InvalidIpcFile)?;
+        let schema = footer.schema().ok_or(BlockLoadError::InvalidIpcFile)?;
+        let mut decoder = FileDecoder::new(Arc::new(fb_to_schema(schema)), footer.version());
+
+        let slice_ipc_block = |block: &arrow::ipc::Block| {
+            let offset = block.offset() as usize;
+            let len = block.metaDataLength() as usize + block.bodyLength() as usize;
+            if offset + len > trailer_start {
+                return Err(BlockLoadError::InvalidIpcFile);
+            }
+            Ok(buffer.slice_with_length(offset, len))
+        };
+
+        for dictionary in footer.dictionaries().iter().flatten() {
+            decoder.read_dictionary(dictionary, &slice_ipc_block(dictionary)?)?;
+        }
+
+        let record_batch = match footer.recordBatches().and_then(|b| b.iter().next()) {
+            Some(record_batch) => record_batch,
+            None => {
+                return Err(BlockLoadError::NoRecordBatches);
+            }
+        };
+        // The body must start on an ARROW_ALIGNMENT boundary for the buffers to be used in place
+        let body_offset = record_batch.offset() as usize + record_batch.metaDataLength() as usize;
+        if body_offset % ARROW_ALIGNMENT != 0 {
+            return Err(BlockLoadError::ArrowLayoutVerificationError(
+                ArrowLayoutVerificationError::BufferLengthNotAligned,
+            ));
+        }
+        match decoder.read_record_batch(record_batch, &slice_ipc_block(record_batch)?)? {
+            Some(batch) => Ok(batch),
+            None => Err(BlockLoadError::NoRecordBatches),
+        }
+    }
+
     fn load_with_reader<R>(reader: R, id: Uuid, validate: bool) -> Result<Self, BlockLoadError>
     where
         R: std::io::Read + std::io::Seek,
@@ -601,6 +1076,49 @@ impl chroma_cache::Weighted for Block {
     }
 }
 
+fn compression_to_metadata_value(compression: BlockCompression) -> &'static str {
+    match compression {
+        BlockCompression::None => "none",
+        BlockCompression::Lz4 => "lz4",
+        BlockCompression::Zstd => "zstd",
+    }
+}
+
+fn compression_from_metadata_value(value: &str) -> Option<BlockCompression> {
+    match value {
+        "none" => Some(BlockCompression::None),
+        "lz4" => Some(BlockCompression::Lz4),
+        "zstd" => Some(BlockCompression::Zstd),
+        _ => None,
+    }
+}
+
+/// Builds the IPC write options for the given record batch. We force the block to be
+/// written with 64 byte alignment, and compress the buffers with the codec recorded in
+/// the schema metadata, if any. Compressed buffers are still padded to the alignment.
+/// The arrow reader detects compressed buffers on its own, so blocks written with or
+/// without compression can always be loaded.
+fn ipc_write_options(rb: &RecordBatch) -> Result<IpcWriteOptions, ArrowError> {
+    let options = IpcWriteOptions::try_new(ARROW_ALIGNMENT, false, MetadataVersion::V5)?;
+    let compression = match rb.schema_ref().metadata().get(COMPRESSION_METADATA_KEY) {
+        Some(value) => match compression_from_metadata_value(value) {
+            Some(compression) => compression,
+            None => {
+                return Err(ArrowError::InvalidArgumentError(format!(
+                    "Unknown block compression codec: {}",
+                    value
+                )));
+            }
+        },
+        None => BlockCompression::None,
+    };
+    match compression {
+        BlockCompression::None => Ok(options),
+        BlockCompression::Lz4 => options.try_with_compression(Some(CompressionType::LZ4_FRAME)),
+        BlockCompression::Zstd => options.try_with_compression(Some(CompressionType::ZSTD)),
+    }
+}
+
 fn get_size_of_array_data(array_data: &ArrayData) -> usize {
     let mut total_size = 0;
     for buffer in array_data.buffers() {
@@ -686,10 +1204,16 @@ pub enum BlockLoadError {
     ArrowLayoutVerificationError(#[from] ArrowLayoutVerificationError),
     #[error("No record batches in IPC file")]
     NoRecordBatches,
+    #[error("Invalid IPC file")]
+    InvalidIpcFile,
     #[error(transparent)]
     BlockToBytesError(#[from] crate::arrow::block::types::BlockToBytesError),
     #[error(transparent)]
     CacheError(#[from] chroma_cache::CacheError),
+    #[error("Block is encrypted and no key provider is configured")]
+    Encrypted,
+    #[error("Could not decrypt block: {0}")]
+    DecryptionError(#[from] EncryptionError),
 }
 
 impl ChromaError for BlockLoadError {
@@ -699,8 +1223,11 @@ impl ChromaError for BlockLoadError {
             BlockLoadError::ArrowError(_) => ErrorCodes::Internal,
             BlockLoadError::ArrowLayoutVerificationError(_) => ErrorCodes::Internal,
             BlockLoadError::NoRecordBatches => ErrorCodes::Internal,
+            BlockLoadError::InvalidIpcFile => ErrorCodes::Internal,
             BlockLoadError::BlockToBytesError(_) => ErrorCodes::Internal,
             BlockLoadError::CacheError(_) => ErrorCodes::Internal,
+            BlockLoadError::Encrypted => ErrorCodes::FailedPrecondition,
+            BlockLoadError::DecryptionError(e) => e.code(),
         }
     }
 }
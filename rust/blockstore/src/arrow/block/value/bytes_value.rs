@@ -0,0 +1,101 @@
+use crate::{
+    arrow::{
+        block::delta::{
+            single_column_size_tracker::SingleColumnSizeTracker,
+            single_column_storage::SingleColumnStorage, BlockStorage, UnorderedBlockDelta,
+        },
+        types::{ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue},
+    },
+    key::KeyWrapper,
+    BlockfileWriterMutationOrdering,
+};
+use arrow::{
+    array::{Array, BinaryArray, BinaryBuilder},
+    datatypes::Field,
+    error::ArrowError,
+    util::bit_util,
+};
+use std::sync::Arc;
+
+impl ArrowWriteableValue for Vec<u8> {
+    type ReadableValue<'referred_data> = &'referred_data [u8];
+    type ArrowBuilder = BinaryBuilder;
+    type SizeTracker = SingleColumnSizeTracker;
+    type PreparedValue = Vec<u8>;
+
+    fn offset_size(item_count: usize) -> usize {
+        bit_util::round_upto_multiple_of_64((item_count + 1) * 4)
+    }
+
+    fn validity_size(_item_count: usize) -> usize {
+        0 // We don't support None values for BinaryArray
+    }
+
+    fn add(prefix: &str, key: KeyWrapper, value: Self, delta: &BlockStorage) {
+        match &delta {
+            BlockStorage::Bytes(builder) => builder.add(prefix, key, value),
+            _ => panic!("Invalid builder type"),
+        }
+    }
+
+    fn delete(prefix: &str, key: KeyWrapper, delta: &UnorderedBlockDelta) {
+        match &delta.builder {
+            BlockStorage::Bytes(builder) => builder.delete(prefix, key),
+            _ => panic!("Invalid builder type"),
+        }
+    }
+
+    fn get_delta_builder(mutation_ordering_hint: BlockfileWriterMutationOrdering) -> BlockStorage {
+        BlockStorage::Bytes(SingleColumnStorage::new(mutation_ordering_hint))
+    }
+
+    fn get_arrow_builder(size_tracker: Self::SizeTracker) -> Self::ArrowBuilder {
+        BinaryBuilder::with_capacity(size_tracker.get_num_items(), size_tracker.get_value_size())
+    }
+
+    fn prepare(value: Self) -> Self::PreparedValue {
+        value
+    }
+
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
+        builder.append_value(value);
+        Ok(())
+    }
+
+    fn finish(mut builder: Self::ArrowBuilder, _: &Self::SizeTracker) -> (Field, Arc<dyn Array>) {
+        let value_field = Field::new("value", arrow::datatypes::DataType::Binary, false);
+        let value_arr = builder.finish();
+        let value_arr = (&value_arr as &dyn Array).slice(0, value_arr.len());
+        (value_field, value_arr)
+    }
+
+    fn get_owned_value_from_delta(
+        prefix: &str,
+        key: KeyWrapper,
+        delta: &BlockStorage,
+    ) -> Option<Self::PreparedValue> {
+        match delta {
+            BlockStorage::Bytes(builder) => builder.get_owned_value(prefix, key),
+            _ => panic!("Invalid builder type"),
+        }
+    }
+}
+
+impl<'referred_data> ArrowReadableValue<'referred_data> for &'referred_data [u8] {
+    fn get(array: &'referred_data Arc<dyn Array>, index: usize) -> Self {
+        let array = array.as_any().downcast_ref::<BinaryArray>().unwrap();
+        array.value(index)
+    }
+
+    fn add_to_delta<K: ArrowWriteableKey>(
+        prefix: &str,
+        key: K,
+        value: Self,
+        storage: &mut BlockStorage,
+    ) {
+        <Vec<u8>>::add(prefix, key.into(), value.to_vec(), storage);
+    }
+}
@@ -15,6 +15,7 @@ use arrow::{
         Float32Builder, StringArray, StringBuilder, StructArray,
     },
     datatypes::{Field, Fields},
+    error::ArrowError,
 };
 use arrow::{
     array::{ArrayRef, BinaryArray},
@@ -113,7 +114,10 @@ impl ArrowWriteableValue for &DataRecord<'_> {
         (id, embedding, metadata, document)
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         let (id, embedding, metadata, document) = value;
 
         builder.id_builder.append_value(id);
@@ -126,6 +130,7 @@ impl ArrowWriteableValue for &DataRecord<'_> {
 
         builder.metadata_builder.append_option(metadata);
         builder.document_builder.append_option(document);
+        Ok(())
     }
 
     fn finish(mut builder: Self::ArrowBuilder, _: &Self::SizeTracker) -> (Field, Arc<dyn Array>) {
@@ -184,9 +189,9 @@ impl ArrowWriteableValue for &DataRecord<'_> {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::DataRecord(builder) => builder.get_owned_value(prefix, key),
             _ => panic!("Invalid builder type"),
         }
@@ -0,0 +1,172 @@
+use crate::{
+    arrow::{
+        block::{
+            delta::{
+                single_column_size_tracker::SingleColumnSizeTracker,
+                single_column_storage::SingleColumnStorage, BlockStorage, UnorderedBlockDelta,
+            },
+            Block,
+        },
+        types::{ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue},
+    },
+    key::KeyWrapper,
+    BlockfileWriterMutationOrdering,
+};
+use arrow::{
+    array::{Array, FixedSizeListArray, FixedSizeListBuilder, Float32Array, Float32Builder},
+    datatypes::Field,
+    error::ArrowError,
+};
+use std::{mem::size_of, sync::Arc};
+
+// Dense vectors are stored as a FixedSizeListArray, so every vector in a block
+// must have the same dimension. The dimension is derived from the size tracker
+// when the block is built. Blocks are merged and split freely, so the writers
+// enforce a single dimension per blockfile, see `ValueDimension`.
+impl ArrowWriteableValue for Vec<f32> {
+    type ReadableValue<'referred_data> = &'referred_data [f32];
+    type ArrowBuilder = FixedSizeListBuilder<Float32Builder>;
+    type SizeTracker = SingleColumnSizeTracker;
+    type PreparedValue = Vec<f32>;
+
+    fn offset_size(_item_count: usize) -> usize {
+        0 // FixedSizeListArray has no offsets
+    }
+
+    fn validity_size(_item_count: usize) -> usize {
+        0 // We don't support None values for FixedSizeListArray
+    }
+
+    fn add(prefix: &str, key: KeyWrapper, value: Self, delta: &BlockStorage) {
+        match &delta {
+            BlockStorage::VecFloat32(builder) => {
+                builder.add(prefix, key, value);
+            }
+            _ => panic!("Invalid builder type"),
+        }
+    }
+
+    fn dimension(value: &Self) -> Option<usize> {
+        Some(value.len())
+    }
+
+    fn delta_dimension(delta: &BlockStorage, old_block: Option<&Block>) -> Option<usize> {
+        match delta {
+            BlockStorage::VecFloat32(builder) => builder.dimension(),
+            _ => panic!("Invalid builder type"),
+        }
+        .or_else(|| {
+            old_block
+                .filter(|block| block.len() > 0)
+                .and_then(|block| {
+                    block
+                        .data
+                        .column(2)
+                        .as_any()
+                        .downcast_ref::<FixedSizeListArray>()
+                })
+                .map(|array| array.value_length() as usize)
+        })
+    }
+
+    fn delete(prefix: &str, key: KeyWrapper, delta: &UnorderedBlockDelta) {
+        match &delta.builder {
+            BlockStorage::VecFloat32(builder) => {
+                builder.delete(prefix, key);
+            }
+            _ => panic!("Invalid builder type"),
+        }
+    }
+
+    fn get_delta_builder(mutation_ordering_hint: BlockfileWriterMutationOrdering) -> BlockStorage {
+        BlockStorage::VecFloat32(SingleColumnStorage::new(mutation_ordering_hint))
+    }
+
+    fn get_arrow_builder(size_tracker: Self::SizeTracker) -> Self::ArrowBuilder {
+        let item_count = size_tracker.get_num_items();
+        let total_value_count = size_tracker.get_value_size() / size_of::<f32>();
+        let dimension = if item_count == 0 {
+            0
+        } else {
+            total_value_count / item_count
+        };
+        FixedSizeListBuilder::with_capacity(
+            Float32Builder::with_capacity(total_value_count),
+            dimension as i32,
+            item_count,
+        )
+    }
+
+    fn prepare(value: Self) -> Self::PreparedValue {
+        value
+    }
+
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
+        if value.len() != builder.value_length() as usize {
+            return Err(ArrowError::InvalidArgumentError(format!(
+                "All vectors in a block must have the same dimension, expected {} got {}",
+                builder.value_length(),
+                value.len()
+            )));
+        }
+        builder.values().append_slice(&value);
+        builder.append(true);
+        Ok(())
+    }
+
+    fn finish(mut builder: Self::ArrowBuilder, _: &Self::SizeTracker) -> (Field, Arc<dyn Array>) {
+        let value_field = Field::new(
+            "value",
+            arrow::datatypes::DataType::FixedSizeList(
+                Arc::new(Field::new(
+                    "item",
+                    arrow::datatypes::DataType::Float32,
+                    true,
+                )),
+                builder.value_length(),
+            ),
+            true,
+        );
+        let value_arr = builder.finish();
+        let value_arr = (&value_arr as &dyn Array).slice(0, value_arr.len());
+
+        (value_field, value_arr)
+    }
+
+    fn get_owned_value_from_delta(
+        prefix: &str,
+        key: KeyWrapper,
+        delta: &BlockStorage,
+    ) -> Option<Self::PreparedValue> {
+        match delta {
+            BlockStorage::VecFloat32(builder) => builder.get_owned_value(prefix, key),
+            _ => panic!("Invalid builder type"),
+        }
+    }
+}
+
+impl<'referred_data> ArrowReadableValue<'referred_data> for &'referred_data [f32] {
+    fn get(array: &'referred_data Arc<dyn Array>, index: usize) -> Self {
+        let list_array = array.as_any().downcast_ref::<FixedSizeListArray>().unwrap();
+        let start = list_array.value_offset(index) as usize;
+        let end = start + list_array.value_length() as usize;
+        let f32array = list_array
+            .values()
+            .as_any()
+            .downcast_ref::<Float32Array>()
+            .unwrap();
+        &f32array.values()[start..end]
+    }
+
+    fn add_to_delta<K: ArrowWriteableKey>(
+        prefix: &str,
+        key: K,
+        value: Self,
+        storage: &mut BlockStorage,
+    ) {
+        <Vec<f32>>::add(prefix, key.into(), value.to_vec(), storage);
+    }
+}
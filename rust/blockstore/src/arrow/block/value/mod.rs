@@ -1,4 +1,6 @@
+pub(super) mod bytes_value;
 pub(super) mod data_record_value;
+pub(super) mod f32array_value;
 pub(super) mod roaring_bitmap_value;
 pub(super) mod spann_posting_list_value;
 pub(super) mod str_value;
@@ -14,6 +14,7 @@ use crate::{
 use arrow::{
     array::{Array, BinaryArray, BinaryBuilder},
     datatypes::Field,
+    error::ArrowError,
     util::bit_util,
 };
 use roaring::RoaringBitmap;
@@ -68,8 +69,12 @@ impl ArrowWriteableValue for RoaringBitmap {
         serialized
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         builder.append_value(value);
+        Ok(())
     }
 
     fn finish(mut builder: Self::ArrowBuilder, _: &Self::SizeTracker) -> (Field, Arc<dyn Array>) {
@@ -82,9 +87,9 @@ impl ArrowWriteableValue for RoaringBitmap {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::RoaringBitmap(builder) => builder.get_owned_value(prefix, key),
             _ => panic!("Invalid builder type"),
         }
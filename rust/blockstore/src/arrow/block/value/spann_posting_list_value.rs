@@ -6,6 +6,7 @@ use arrow::{
         ListArray, ListBuilder, StructArray, UInt32Array, UInt32Builder,
     },
     datatypes::{DataType, Field, Fields},
+    error::ArrowError,
 };
 use chroma_types::SpannPostingList;
 
@@ -101,7 +102,10 @@ impl ArrowWriteableValue for &SpannPostingList<'_> {
         )
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         let doc_offset_ids = value.0;
         let doc_versions = value.1;
         let doc_embeddings = value.2;
@@ -125,6 +129,7 @@ impl ArrowWriteableValue for &SpannPostingList<'_> {
         builder.doc_offset_ids_builder.append(true);
         builder.doc_versions_builder.append(true);
         builder.doc_embeddings_builder.append(true);
+        Ok(())
     }
 
     fn finish(
@@ -182,9 +187,9 @@ impl ArrowWriteableValue for &SpannPostingList<'_> {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::SpannPostingListDelta(builder) => builder.get_owned_value(prefix, key),
             _ => panic!("Invalid builder type"),
         }
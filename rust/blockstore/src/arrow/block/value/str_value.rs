@@ -12,6 +12,7 @@ use crate::{
 use arrow::{
     array::{Array, StringArray, StringBuilder},
     datatypes::Field,
+    error::ArrowError,
     util::bit_util,
 };
 use std::sync::Arc;
@@ -56,8 +57,12 @@ impl ArrowWriteableValue for String {
         value
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         builder.append_value(value);
+        Ok(())
     }
 
     fn finish(
@@ -73,9 +78,9 @@ impl ArrowWriteableValue for String {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::String(builder) => builder.get_owned_value(prefix, key),
             _ => panic!("Invalid builder type"),
         }
@@ -12,6 +12,7 @@ use crate::{
 use arrow::{
     array::{Array, UInt32Array, UInt32Builder},
     datatypes::Field,
+    error::ArrowError,
 };
 use std::sync::Arc;
 
@@ -55,8 +56,12 @@ impl ArrowWriteableValue for u32 {
         value
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         builder.append_value(value);
+        Ok(())
     }
 
     fn finish(
@@ -72,11 +77,11 @@ impl ArrowWriteableValue for u32 {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::UInt32(builder) => builder.get_owned_value(prefix, key),
-            _ => panic!("Invalid builder type: {:?}", &delta.builder),
+            _ => panic!("Invalid builder type: {:?}", delta),
         }
     }
 }
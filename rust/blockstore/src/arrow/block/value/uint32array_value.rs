@@ -12,6 +12,7 @@ use crate::{
 use arrow::{
     array::{Array, Int32Array, ListArray, ListBuilder, UInt32Array, UInt32Builder},
     datatypes::Field,
+    error::ArrowError,
     util::bit_util,
 };
 use std::{mem::size_of, sync::Arc};
@@ -64,11 +65,15 @@ impl ArrowWriteableValue for Vec<u32> {
         value
     }
 
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder) {
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError> {
         for v in value {
             builder.values().append_value(v);
         }
         builder.append(true);
+        Ok(())
     }
 
     fn finish(mut builder: Self::ArrowBuilder, _: &Self::SizeTracker) -> (Field, Arc<dyn Array>) {
@@ -90,9 +95,9 @@ impl ArrowWriteableValue for Vec<u32> {
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue> {
-        match &delta.builder {
+        match delta {
             BlockStorage::VecUInt32(builder) => builder.get_owned_value(prefix, key),
             _ => panic!("Invalid builder type"),
         }
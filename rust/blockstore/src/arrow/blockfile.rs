@@ -1,15 +1,21 @@
+use super::fsck::{FsckReport, FsckViolation};
 use super::migrations::{apply_migrations_to_blockfile, MigrationError};
 use super::provider::{GetError, RootManager};
-use super::root::{RootReader, RootWriter, Version};
+use super::root::{BlockfileStats, RootReader, RootWriter, Version};
+use super::spill::DeltaSpill;
 use super::{block::delta::UnorderedBlockDelta, provider::BlockManager};
 use super::{
     block::Block,
     flusher::ArrowBlockfileFlusher,
-    types::{ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue},
+    types::{
+        ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue,
+        ValueDimension,
+    },
 };
 use crate::arrow::root::CURRENT_VERSION;
-use crate::arrow::sparse_index::SparseIndexWriter;
+use crate::arrow::sparse_index::{SparseIndexDelimiter, SparseIndexWriter};
 use crate::key::CompositeKey;
+use crate::key::CompositeKeyRange;
 use crate::key::KeyWrapper;
 use chroma_cache::AysncPartitionedMutex;
 use chroma_error::ChromaError;
@@ -33,6 +39,9 @@ pub struct ArrowUnorderedBlockfileWriter {
     root: RootWriter,
     id: Uuid,
     deltas_mutex: Arc<AysncPartitionedMutex<Uuid>>,
+    // Only set for writers with a memory budget
+    spill: Option<Arc<DeltaSpill>>,
+    value_dimension: ValueDimension,
 }
 // TODO: method visibility should not be pub(crate)
 
@@ -81,6 +90,8 @@ impl ArrowUnorderedBlockfileWriter {
             root: root_writer,
             id,
             deltas_mutex: Arc::new(AysncPartitionedMutex::new(())),
+            spill: None,
+            value_dimension: ValueDimension::default(),
         }
     }
 
@@ -100,12 +111,25 @@ impl ArrowUnorderedBlockfileWriter {
             root: new_root,
             id,
             deltas_mutex: Arc::new(AysncPartitionedMutex::new(())),
+            spill: None,
+            value_dimension: ValueDimension::default(),
         }
     }
 
+    /// Spill deltas to local temp files whenever the deltas in memory could exceed the budget.
+    /// Spilled deltas are loaded back when they are mutated again, and at commit.
+    pub(super) fn with_memory_budget(mut self, memory_budget_bytes: usize) -> Self {
+        self.spill = Some(Arc::new(DeltaSpill::new(memory_budget_bytes)));
+        self
+    }
+
     pub(crate) async fn commit<K: ArrowWriteableKey, V: ArrowWriteableValue>(
         mut self,
     ) -> Result<ArrowBlockfileFlusher, Box<dyn ChromaError>> {
+        if let Some(min_block_size_bytes) = self.block_manager.min_block_size_bytes() {
+            self.merge_underfilled_deltas::<K, V>(min_block_size_bytes)?;
+        }
+
         let mut blocks = Vec::new();
         let mut new_block_ids = HashSet::new();
         let mut deltas_to_commit = Vec::new();
@@ -126,10 +150,37 @@ impl ArrowUnorderedBlockfileWriter {
             }
         }
 
+        // Spilled deltas were already finished into blocks with the same ids
+        let mut spilled_blocks_to_commit = Vec::new();
+        if let Some(spill) = self.spill.as_ref() {
+            for (block, len) in spill
+                .drain_blocks()
+                .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?
+            {
+                new_block_ids.insert(block.id);
+                let mut removed = false;
+                if len == 0 {
+                    tracing::info!("Spilled delta with id {:?} is empty", block.id);
+                    removed = self.root.sparse_index.remove_block(&block.id);
+                }
+                if !removed {
+                    self.root
+                        .sparse_index
+                        .set_count(block.id, len as u32)
+                        .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+                    spilled_blocks_to_commit.push(block);
+                }
+            }
+        }
+
         for delta in deltas_to_commit {
             let block = self.block_manager.commit::<K, V>(delta).await;
             blocks.push(block);
         }
+        for block in spilled_blocks_to_commit {
+            let block = self.block_manager.commit_block(block).await;
+            blocks.push(block);
+        }
 
         apply_migrations_to_blockfile(&mut self.root, &self.block_manager, &new_block_ids)
             .await
@@ -168,7 +219,7 @@ impl ArrowUnorderedBlockfileWriter {
         value: V,
     ) -> Result<(), Box<dyn ChromaError>> {
         let search_key = CompositeKey::new(prefix.to_string(), key.clone());
-        let (_guard, target_block_id) = loop {
+        let (guard, target_block_id) = loop {
             // Get the target block id for the key
             let target_block_id = self.root.sparse_index.get_target_block_id(&search_key);
 
@@ -185,14 +236,12 @@ impl ArrowUnorderedBlockfileWriter {
             break (delta_guard, target_block_id);
         };
 
-        let delta = {
-            let deltas = self.block_deltas.lock();
-            deltas.get(&target_block_id).cloned()
-        };
+        let delta = self.get_delta::<K, V>(&target_block_id)?;
 
         if let Some(delta) = delta {
             // Add the key, value pair to delta.
             // Then check if its over size and split as needed
+            delta.check(&value, &self.value_dimension)?;
             delta.add(prefix, key, value);
 
             if delta.get_size::<K, V>() > self.block_manager.max_block_size_bytes() {
@@ -221,6 +270,7 @@ impl ArrowUnorderedBlockfileWriter {
                 .get(
                     &self.root.prefix_path,
                     &target_block_id,
+                    self.root.sparse_index.get_checksum(&target_block_id),
                     StorageRequestPriority::P0,
                 )
                 .await
@@ -235,7 +285,11 @@ impl ArrowUnorderedBlockfileWriter {
             };
             let new_delta = match self
                 .block_manager
-                .fork::<K, V, UnorderedBlockDelta>(&block.id, &self.root.prefix_path)
+                .fork::<K, V, UnorderedBlockDelta>(
+                    &block.id,
+                    &self.root.prefix_path,
+                    self.root.sparse_index.get_checksum(&block.id),
+                )
                 .await
             {
                 Ok(delta) => delta,
@@ -246,6 +300,7 @@ impl ArrowUnorderedBlockfileWriter {
 
             // Add the key, value pair to delta.
             // Then check if its over size and split as needed
+            new_delta.check(&value, &self.value_dimension)?;
             new_delta.add(prefix, key, value);
 
             if new_delta.get_size::<K, V>() > self.block_manager.max_block_size_bytes() {
@@ -276,7 +331,8 @@ impl ArrowUnorderedBlockfileWriter {
             }
         }
 
-        Ok(())
+        drop(guard);
+        self.spill_over_budget::<K, V>().await
     }
 
     pub async fn get_owned<K: ArrowWriteableKey, V: ArrowWriteableValue>(
@@ -285,7 +341,7 @@ impl ArrowUnorderedBlockfileWriter {
         key: K,
     ) -> Result<Option<V::PreparedValue>, Box<dyn ChromaError>> {
         let search_key = CompositeKey::new(prefix.to_string(), key.clone());
-        let (_guard, target_block_id) = loop {
+        let (guard, target_block_id) = loop {
             // Get the target block id for the key
             let target_block_id = self.root.sparse_index.get_target_block_id(&search_key);
 
@@ -302,18 +358,16 @@ impl ArrowUnorderedBlockfileWriter {
             break (delta_guard, target_block_id);
         };
 
-        let delta = {
-            let deltas = self.block_deltas.lock();
-            deltas.get(&target_block_id).cloned()
-        };
+        let delta = self.get_delta::<K, V>(&target_block_id)?;
 
-        Ok(match delta {
+        let value = match delta {
             None => {
                 let block = match self
                     .block_manager
                     .get(
                         &self.root.prefix_path,
                         &target_block_id,
+                        self.root.sparse_index.get_checksum(&target_block_id),
                         StorageRequestPriority::P0,
                     )
                     .await
@@ -328,7 +382,11 @@ impl ArrowUnorderedBlockfileWriter {
                 };
                 let new_delta = match self
                     .block_manager
-                    .fork::<K, V, UnorderedBlockDelta>(&block.id, &self.root.prefix_path)
+                    .fork::<K, V, UnorderedBlockDelta>(
+                        &block.id,
+                        &self.root.prefix_path,
+                        self.root.sparse_index.get_checksum(&block.id),
+                    )
                     .await
                 {
                     Ok(delta) => delta,
@@ -337,7 +395,7 @@ impl ArrowUnorderedBlockfileWriter {
                     }
                 };
                 // Read the value before making the delta visible through the sparse index.
-                let value = V::get_owned_value_from_delta(prefix, key.into(), &new_delta);
+                let value = V::get_owned_value_from_delta(prefix, key.into(), &new_delta.builder);
                 // Insert to delta first and then make it visible through the sparse index to
                 // prevent dangling references.
                 let mut deltas = self.block_deltas.lock();
@@ -347,8 +405,12 @@ impl ArrowUnorderedBlockfileWriter {
                     .replace_block(target_block_id, new_delta.id);
                 value
             }
-            Some(delta) => V::get_owned_value_from_delta(prefix, key.into(), &delta),
-        })
+            Some(delta) => V::get_owned_value_from_delta(prefix, key.into(), &delta.builder),
+        };
+
+        drop(guard);
+        self.spill_over_budget::<K, V>().await?;
+        Ok(value)
     }
 
     pub(crate) async fn delete<K: ArrowWriteableKey, V: ArrowWriteableValue>(
@@ -357,7 +419,7 @@ impl ArrowUnorderedBlockfileWriter {
         key: K,
     ) -> Result<(), Box<dyn ChromaError>> {
         let search_key = CompositeKey::new(prefix.to_string(), key.clone());
-        let (_guard, target_block_id) = loop {
+        let (guard, target_block_id) = loop {
             // Get the target block id for the key
             let target_block_id = self.root.sparse_index.get_target_block_id(&search_key);
 
@@ -374,10 +436,7 @@ impl ArrowUnorderedBlockfileWriter {
             break (delta_guard, target_block_id);
         };
 
-        let delta = {
-            let deltas = self.block_deltas.lock();
-            deltas.get(&target_block_id).cloned()
-        };
+        let delta = self.get_delta::<K, V>(&target_block_id)?;
 
         match delta {
             None => {
@@ -386,6 +445,7 @@ impl ArrowUnorderedBlockfileWriter {
                     .get(
                         &self.root.prefix_path,
                         &target_block_id,
+                        self.root.sparse_index.get_checksum(&target_block_id),
                         StorageRequestPriority::P0,
                     )
                     .await
@@ -400,7 +460,11 @@ impl ArrowUnorderedBlockfileWriter {
                 };
                 let new_delta = match self
                     .block_manager
-                    .fork::<K, V, UnorderedBlockDelta>(&block.id, &self.root.prefix_path)
+                    .fork::<K, V, UnorderedBlockDelta>(
+                        &block.id,
+                        &self.root.prefix_path,
+                        self.root.sparse_index.get_checksum(&block.id),
+                    )
                     .await
                 {
                     Ok(delta) => delta,
@@ -409,6 +473,7 @@ impl ArrowUnorderedBlockfileWriter {
                     }
                 };
                 // Delete the key before making the delta visible through the sparse index.
+                new_delta.observe_dimension::<V>(&self.value_dimension);
                 new_delta.delete::<K, V>(prefix, key);
                 // Insert to delta first and then make it visible through the sparse index to
                 // prevent dangling references.
@@ -419,12 +484,223 @@ impl ArrowUnorderedBlockfileWriter {
                     .replace_block(target_block_id, new_delta.id);
             }
             Some(delta) => {
+                delta.observe_dimension::<V>(&self.value_dimension);
                 delta.delete::<K, V>(prefix, key);
             }
         };
+
+        drop(guard);
+        self.spill_over_budget::<K, V>().await
+    }
+
+    /// Deletes every key of the prefix in the key range. Blocks that only hold keys in the range
+    /// are dropped from the sparse index without being fetched, and only the blocks at the
+    /// boundaries of the range are rewritten.
+    pub(crate) async fn delete_range<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let range = CompositeKeyRange::new(prefix, key_range);
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        'restart: loop {
+            for (block_id, covered) in self.root.sparse_index.get_blocks_in_range(&range) {
+                let _guard = self.deltas_mutex.lock(&block_id).await;
+                // Someone concurrently converted the block to delta and/or split it so restart all over.
+                // Blocks that were already handled hold no keys in the range anymore.
+                if !self.root.sparse_index.contains_block(&block_id) {
+                    continue 'restart;
+                }
+
+                if covered && self.root.sparse_index.remove_block(&block_id) {
+                    self.block_deltas.lock().remove(&block_id);
+                    if let Some(spill) = self.spill.as_ref() {
+                        spill.remove(&block_id);
+                    }
+                    continue;
+                }
+
+                let delta = self.get_delta::<K, V>(&block_id)?;
+                match delta {
+                    Some(delta) => {
+                        delta.observe_dimension::<V>(&self.value_dimension);
+                        delta.delete_range(&range);
+                    }
+                    None => {
+                        let block = match self
+                            .block_manager
+                            .get(
+                                &self.root.prefix_path,
+                                &block_id,
+                                self.root.sparse_index.get_checksum(&block_id),
+                                StorageRequestPriority::P0,
+                            )
+                            .await
+                        {
+                            Ok(Some(block)) => block,
+                            Ok(None) => {
+                                return Err(Box::new(ArrowBlockfileError::BlockNotFound));
+                            }
+                            Err(e) => {
+                                return Err(Box::new(e));
+                            }
+                        };
+                        let new_delta = match self
+                            .block_manager
+                            .fork::<K, V, UnorderedBlockDelta>(
+                                &block.id,
+                                &self.root.prefix_path,
+                                self.root.sparse_index.get_checksum(&block.id),
+                            )
+                            .await
+                        {
+                            Ok(delta) => delta,
+                            Err(e) => {
+                                return Err(Box::new(e));
+                            }
+                        };
+                        new_delta.observe_dimension::<V>(&self.value_dimension);
+                        // The block may only overlap the range without holding any of its keys,
+                        // in which case it is left as is.
+                        if new_delta.delete_range(&range) == 0 {
+                            continue;
+                        }
+                        // Insert to delta first and then make it visible through the sparse index to
+                        // prevent dangling references.
+                        let mut deltas = self.block_deltas.lock();
+                        deltas.insert(new_delta.id, new_delta.clone());
+                        self.root.sparse_index.replace_block(block_id, new_delta.id);
+                    }
+                }
+            }
+            break;
+        }
+
+        self.spill_over_budget::<K, V>().await
+    }
+
+    /// Merge adjacent deltas of which one is underfilled, see
+    /// `SparseIndexWriter::get_underfilled_block_groups()`. Spilled deltas are loaded back into
+    /// memory if they are merged. Blocks that were not rewritten by this writer are left as is.
+    fn merge_underfilled_deltas<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        min_block_size_bytes: usize,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let mut block_sizes = self
+            .block_deltas
+            .lock()
+            .iter()
+            .map(|(block_id, delta)| (*block_id, delta.get_size::<K, V>()))
+            .collect::<HashMap<_, _>>();
+        if let Some(spill) = self.spill.as_ref() {
+            block_sizes.extend(spill.sizes());
+        }
+        let groups = self.root.sparse_index.get_underfilled_block_groups(
+            &block_sizes,
+            min_block_size_bytes,
+            self.block_manager.max_block_size_bytes(),
+        );
+
+        for group in groups {
+            let mut group_deltas = Vec::with_capacity(group.len());
+            for block_id in group.iter() {
+                // Loads the delta back into memory if it was spilled
+                self.get_delta::<K, V>(block_id)?;
+                let delta = self
+                    .block_deltas
+                    .lock()
+                    .remove(block_id)
+                    .ok_or(Box::new(ArrowBlockfileError::BlockNotFound) as Box<dyn ChromaError>)?;
+                group_deltas.push(delta);
+            }
+            let mut group_deltas = group_deltas.into_iter();
+            let mut merged_delta = group_deltas
+                .next()
+                .expect("Groups hold more than one block");
+            for delta in group_deltas {
+                self.root.sparse_index.remove_block(&delta.id);
+                merged_delta.merge::<K, V>(delta);
+            }
+            tracing::debug!(
+                "Merged {} underfilled blocks into delta {:?}",
+                group.len(),
+                merged_delta.id
+            );
+            self.block_deltas
+                .lock()
+                .insert(merged_delta.id, merged_delta);
+        }
         Ok(())
     }
 
+    /// Returns the delta of the block if it was already forked, loading it back into memory if it
+    /// was spilled. The caller must hold the lock of the block in `deltas_mutex`.
+    fn get_delta<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        block_id: &Uuid,
+    ) -> Result<Option<UnorderedBlockDelta>, Box<dyn ChromaError>> {
+        let delta = self.block_deltas.lock().get(block_id).cloned();
+        if delta.is_some() {
+            return Ok(delta);
+        }
+        let Some(spill) = self.spill.as_ref() else {
+            return Ok(None);
+        };
+        let delta = spill
+            .load::<K, V>(block_id)
+            .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+        if let Some(delta) = delta.as_ref() {
+            self.block_deltas.lock().insert(*block_id, delta.clone());
+        }
+        Ok(delta)
+    }
+
+    /// Spill deltas until the deltas left in memory fit in the memory budget of the writer.
+    /// Deltas are split once they exceed the max block size, so that is the size assumed for each.
+    /// Must not be called while holding a lock in `deltas_mutex`.
+    async fn spill_over_budget<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let Some(spill) = self.spill.as_ref() else {
+            return Ok(());
+        };
+        let max_deltas_in_memory =
+            (spill.memory_budget_bytes() / self.block_manager.max_block_size_bytes()).max(1);
+        loop {
+            let block_id = {
+                let deltas = self.block_deltas.lock();
+                if deltas.len() <= max_deltas_in_memory {
+                    return Ok(());
+                }
+                // Deltas that are not in the sparse index yet are still being split
+                match deltas
+                    .keys()
+                    .find(|block_id| self.root.sparse_index.contains_block(block_id))
+                {
+                    Some(block_id) => *block_id,
+                    None => return Ok(()),
+                }
+            };
+
+            let _guard = self.deltas_mutex.lock(&block_id).await;
+            // Someone concurrently spilled or removed the delta
+            let Some(delta) = self.block_deltas.lock().remove(&block_id) else {
+                continue;
+            };
+            spill
+                .spill::<K, V>(delta)
+                .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+            tracing::debug!(
+                "Writer {:?} has {} deltas spilled to disk",
+                self.id,
+                spill.len()
+            );
+        }
+    }
+
     pub(crate) fn id(&self) -> Uuid {
         self.id
     }
@@ -465,7 +741,12 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         if !self.loaded_blocks.read().contains_key(&block_id) {
             let block = match self
                 .block_manager
-                .get(&self.root.prefix_path, &block_id, priority)
+                .get(
+                    &self.root.prefix_path,
+                    &block_id,
+                    self.root.sparse_index.get_checksum(&block_id),
+                    priority,
+                )
                 .await
             {
                 Ok(Some(block)) => block,
@@ -553,6 +834,9 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         key: K,
     ) -> Result<Option<V>, Box<dyn ChromaError>> {
         let search_key = CompositeKey::new(prefix.to_string(), key.clone());
+        if !self.root.sparse_index.may_contain(&search_key) {
+            return Ok(None);
+        }
         let target_block_id = self.root.sparse_index.get_target_block_id(&search_key);
         let block = self
             .get_block(target_block_id, StorageRequestPriority::P0)
@@ -567,6 +851,55 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         }
     }
 
+    /// Gets the values of many keys at once, in the order of the input. Keys are grouped by the
+    /// block that may contain them, and each of these blocks is fetched once, concurrently.
+    pub(crate) async fn get_many(
+        &'me self,
+        keys: impl IntoIterator<Item = (String, K)>,
+    ) -> Result<Vec<Option<V>>, Box<dyn ChromaError>> {
+        let keys_and_target_block_ids = keys
+            .into_iter()
+            .map(|(prefix, key)| {
+                let search_key = CompositeKey::new(prefix.clone(), key.clone());
+                let target_block_id = self
+                    .root
+                    .sparse_index
+                    .may_contain(&search_key)
+                    .then(|| self.root.sparse_index.get_target_block_id(&search_key));
+                (prefix, key, target_block_id)
+            })
+            .collect::<Vec<_>>();
+
+        let target_block_ids = keys_and_target_block_ids
+            .iter()
+            .filter_map(|(_, _, target_block_id)| *target_block_id)
+            .collect::<HashSet<_>>();
+        let blocks = join_all(target_block_ids.into_iter().map(|block_id| async move {
+            let block = self.get_block(block_id, StorageRequestPriority::P0).await;
+            (block_id, block)
+        }))
+        .await
+        .into_iter()
+        .map(|(block_id, block)| match block {
+            Ok(block) => {
+                if block.is_none() {
+                    tracing::error!("Block with id {:?} not found", block_id);
+                }
+                Ok((block_id, block))
+            }
+            Err(e) => Err(Box::new(e) as Box<dyn ChromaError>),
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(keys_and_target_block_ids
+            .into_iter()
+            .map(|(prefix, key, target_block_id)| {
+                let block = (*blocks.get(&target_block_id?)?)?;
+                block.get(&prefix, key)
+            })
+            .collect())
+    }
+
     // Returns all Arrow records in the specified range.
     pub(crate) fn get_range_stream<'prefix, PrefixRange, KeyRange>(
         &'me self,
@@ -605,6 +938,46 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         .flatten()
     }
 
+    // Returns all Arrow records in the specified range, in descending key order.
+    // Blocks are fetched from the end of the range first.
+    pub(crate) fn get_range_stream_rev<'prefix, PrefixRange, KeyRange>(
+        &'me self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> impl Stream<Item = Result<(&'me str, K, V), Box<dyn ChromaError>>> + Send + 'me
+    where
+        PrefixRange: RangeBounds<&'prefix str> + Clone + Send + 'me,
+        KeyRange: RangeBounds<K> + Clone + Send + 'me,
+        K: Sync,
+        V: Sync,
+    {
+        futures::stream::iter(
+            self.root
+                .sparse_index
+                .get_block_ids_range(prefix_range.clone())
+                .into_iter()
+                .rev()
+                .map(Ok),
+        )
+        .try_filter_map(move |block_id| async move {
+            match self.get_block(block_id, StorageRequestPriority::P0).await {
+                Ok(Some(block)) => Ok(Some(block)),
+                Ok(None) => Err(Box::new(ArrowBlockfileError::BlockNotFound)),
+                Err(e) => Err(Box::new(ArrowBlockfileError::BlockFetchError(e))),
+            }
+        })
+        .map(move |block| match block {
+            Ok(block) => futures::stream::iter(
+                block
+                    .get_range_rev::<K, V, _, _>(prefix_range.clone(), key_range.clone())
+                    .map(Ok),
+            )
+            .boxed(),
+            Err(e) => futures::stream::once(async { Err(e as Box<dyn ChromaError>) }).boxed(),
+        })
+        .flatten()
+    }
+
     pub async fn get_range<'prefix, PrefixRange, KeyRange>(
         &'me self,
         prefix_range: PrefixRange,
@@ -643,12 +1016,46 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         Ok(result)
     }
 
+    pub async fn get_range_rev<'prefix, PrefixRange, KeyRange>(
+        &'me self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> Result<Vec<(&'me str, K, V)>, Box<dyn ChromaError>>
+    where
+        PrefixRange: RangeBounds<&'prefix str> + Clone,
+        KeyRange: RangeBounds<K> + Clone,
+    {
+        let block_ids = self
+            .root
+            .sparse_index
+            .get_block_ids_range(prefix_range.clone());
+
+        let mut result: Vec<(&str, K, V)> = vec![];
+        for block_id in block_ids.into_iter().rev() {
+            let block = match self.get_block(block_id, StorageRequestPriority::P0).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    return Err(Box::new(ArrowBlockfileError::BlockNotFound));
+                }
+                Err(e) => {
+                    return Err(Box::new(e));
+                }
+            };
+            result.extend(block.get_range_rev(prefix_range.clone(), key_range.clone()));
+        }
+
+        Ok(result)
+    }
+
     pub(crate) async fn contains(
         &'me self,
         prefix: &str,
         key: K,
     ) -> Result<bool, Box<dyn ChromaError>> {
         let search_key = CompositeKey::new(prefix.to_string(), key.clone());
+        if !self.root.sparse_index.may_contain(&search_key) {
+            return Ok(false);
+        }
         let target_block_id = self.root.sparse_index.get_target_block_id(&search_key);
         let block = match self
             .get_block(target_block_id, StorageRequestPriority::P0)
@@ -710,6 +1117,61 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         self.root.id
     }
 
+    /// Returns every distinct prefix in the blockfile with its number of keys, in prefix order.
+    /// Blocks whose keys are known to share one prefix are counted from the sparse index, so
+    /// only the blocks at prefix boundaries are fetched.
+    pub(crate) async fn get_prefixes_with_counts(
+        &self,
+    ) -> Result<Vec<(String, usize)>, Box<dyn ChromaError>> {
+        let blocks = self.root.sparse_index.get_blocks_with_prefix();
+        // Roots before V1_1 do not record the number of keys in each block
+        let counts_recorded = self.root.version >= Version::V1_1;
+        let block_ids_to_fetch = blocks
+            .iter()
+            .filter(|(_, prefix)| prefix.is_none() || !counts_recorded)
+            .map(|(block_value, _)| block_value.id)
+            .collect::<Vec<_>>();
+        self.load_blocks(&block_ids_to_fetch).await;
+
+        let mut prefix_counts: Vec<(String, usize)> = Vec::new();
+        let mut add_count = |prefix: &str, count: usize| match prefix_counts.last_mut() {
+            Some((last_prefix, last_count)) if last_prefix == prefix => *last_count += count,
+            _ => prefix_counts.push((prefix.to_string(), count)),
+        };
+        for (block_value, prefix) in blocks {
+            match prefix {
+                Some(prefix) if counts_recorded => add_count(prefix, block_value.count as usize),
+                _ => {
+                    let block =
+                        self.get_block(block_value.id, StorageRequestPriority::P0)
+                            .await
+                            .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?
+                            .ok_or(Box::new(ArrowBlockfileError::BlockNotFound)
+                                as Box<dyn ChromaError>)?;
+                    for (prefix, count) in block.prefix_counts() {
+                        add_count(prefix, count);
+                    }
+                }
+            }
+        }
+        Ok(prefix_counts)
+    }
+
+    /// Returns the storage statistics of the blockfile, without fetching any blocks
+    pub fn stats(&self) -> BlockfileStats {
+        self.root.stats(..)
+    }
+
+    /// Estimates the number of keys and bytes a scan of `prefix_range` would read,
+    /// without fetching any blocks. The estimate covers every block the scan would load,
+    /// so it is an upper bound of the result.
+    pub fn estimate_range<'prefix, PrefixRange>(&self, prefix_range: PrefixRange) -> BlockfileStats
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+    {
+        self.root.stats(prefix_range)
+    }
+
     /// Returns the number of elements strictly less than the given prefix-key pair in the blockfile
     /// In other words, the rank is the position where the given prefix-key pair can be inserted while maintaining the order of the blockfile
     pub(crate) async fn rank(
@@ -764,6 +1226,234 @@ impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me
         Ok(rank)
     }
 
+    /// Returns the key and value at the given position among the keys of the prefix, or None if
+    /// the prefix has fewer keys. This is the inverse of `.rank()` within a prefix.
+    /// Blocks whose keys are known to share the prefix are skipped using the counts in the
+    /// sparse index, so only the boundary blocks and the block holding the record are fetched.
+    pub(crate) async fn get_by_rank(
+        &'me self,
+        prefix: &str,
+        rank: usize,
+    ) -> Result<Option<(K, V)>, Box<dyn ChromaError>> {
+        let block_ids = self
+            .root
+            .sparse_index
+            .get_block_ids_range(prefix..=prefix)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        // Roots before V1_1 do not record the number of keys in each block
+        let counts_recorded = self.root.version >= Version::V1_1;
+        if !counts_recorded {
+            self.load_blocks(&block_ids.iter().copied().collect::<Vec<_>>())
+                .await;
+        }
+
+        let mut remaining = rank;
+        for (block_value, block_prefix) in self.root.sparse_index.get_blocks_with_prefix() {
+            if !block_ids.contains(&block_value.id) {
+                continue;
+            }
+            match block_prefix {
+                Some(block_prefix) if block_prefix != prefix => continue,
+                Some(_) if counts_recorded && remaining >= block_value.count as usize => {
+                    remaining -= block_value.count as usize;
+                    continue;
+                }
+                _ => {}
+            }
+            let block = self
+                .get_block(block_value.id, StorageRequestPriority::P0)
+                .await
+                .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?
+                .ok_or(Box::new(ArrowBlockfileError::BlockNotFound) as Box<dyn ChromaError>)?;
+            let indices = block.get_prefix_indices::<K>(prefix);
+            if remaining < indices.len() {
+                return Ok(block
+                    .get_at_index::<K, V>(indices.start + remaining)
+                    .map(|(_, key, value)| (key, value)));
+            }
+            remaining -= indices.len();
+        }
+        Ok(None)
+    }
+
+    /// Returns `n` distinct records picked uniformly at random, in key order, or every record if
+    /// the blockfile holds fewer. The positions are mapped to blocks with the counts in the
+    /// sparse index, so only the blocks holding a picked record are fetched.
+    pub(crate) async fn sample(
+        &'me self,
+        n: usize,
+    ) -> Result<Vec<(&'me str, K, V)>, Box<dyn ChromaError>> {
+        let block_ids = self
+            .root
+            .sparse_index
+            .data
+            .forward
+            .values()
+            .map(|block_value| block_value.id)
+            .collect::<Vec<_>>();
+        let block_counts = if self.root.version >= Version::V1_1 {
+            self.root
+                .sparse_index
+                .data
+                .forward
+                .values()
+                .map(|block_value| block_value.count as usize)
+                .collect::<Vec<_>>()
+        } else {
+            self.load_blocks(&block_ids).await;
+            let mut block_counts = Vec::with_capacity(block_ids.len());
+            for block_id in block_ids.iter() {
+                let block = self
+                    .get_block(*block_id, StorageRequestPriority::P0)
+                    .await
+                    .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?
+                    .ok_or(Box::new(ArrowBlockfileError::BlockNotFound) as Box<dyn ChromaError>)?;
+                block_counts.push(block.len());
+            }
+            block_counts
+        };
+        let block_ends = block_counts
+            .iter()
+            .scan(0, |end, count| {
+                *end += count;
+                Some(*end)
+            })
+            .collect::<Vec<_>>();
+        let total = block_ends.last().copied().unwrap_or_default();
+
+        let mut positions =
+            rand::seq::index::sample(&mut rand::thread_rng(), total, n.min(total)).into_vec();
+        positions.sort_unstable();
+        // The block holding each position and the offset of the position in that block
+        let picks = positions
+            .into_iter()
+            .map(|position| {
+                let block_index = block_ends.partition_point(|end| *end <= position);
+                let block_start = block_ends[block_index] - block_counts[block_index];
+                (block_ids[block_index], position - block_start)
+            })
+            .collect::<Vec<_>>();
+
+        let target_block_ids = picks
+            .iter()
+            .map(|(block_id, _)| *block_id)
+            .collect::<HashSet<_>>();
+        let blocks = join_all(target_block_ids.into_iter().map(|block_id| async move {
+            let block = self.get_block(block_id, StorageRequestPriority::P0).await;
+            (block_id, block)
+        }))
+        .await
+        .into_iter()
+        .map(|(block_id, block)| match block {
+            Ok(Some(block)) => Ok((block_id, block)),
+            Ok(None) => Err(Box::new(ArrowBlockfileError::BlockNotFound) as Box<dyn ChromaError>),
+            Err(e) => Err(Box::new(e) as Box<dyn ChromaError>),
+        })
+        .collect::<Result<HashMap<_, _>, _>>()?;
+
+        Ok(picks
+            .into_iter()
+            .filter_map(|(block_id, offset)| {
+                blocks
+                    .get(&block_id)
+                    .and_then(|block| block.get_at_index::<K, V>(offset))
+            })
+            .collect())
+    }
+
+    /// Verify the whole blockfile and report every violation that is found, see `FsckViolation`.
+    /// Unlike `is_valid`, this fetches and scans every block even after a violation is found.
+    /// Blocks are read from storage one at a time, bypassing the block cache, so that a block
+    /// missing from storage is reported even if it is still cached.
+    pub async fn fsck(&self) -> FsckReport {
+        let mut report = FsckReport::default();
+        if !self.root.sparse_index.is_valid() {
+            report.violations.push(FsckViolation::InvalidSparseIndex);
+        }
+
+        let max_block_size_bytes = self.block_manager.max_block_size_bytes();
+        let mut seen_block_ids = HashSet::new();
+        let mut previous_key: Option<CompositeKey> = None;
+        let mut entries = self.root.sparse_index.data.forward.iter().peekable();
+        while let Some((delimiter, value)) = entries.next() {
+            let block_id = value.id;
+            let next_delimiter = entries.peek().map(|(next_delimiter, _)| *next_delimiter);
+            if !seen_block_ids.insert(block_id) {
+                report
+                    .violations
+                    .push(FsckViolation::DuplicateBlockId { block_id });
+                continue;
+            }
+            let block = match self
+                .block_manager
+                .get_from_storage(
+                    &self.root.prefix_path,
+                    &block_id,
+                    self.root.sparse_index.get_checksum(&block_id),
+                    StorageRequestPriority::P0,
+                )
+                .await
+            {
+                Ok(block) => block,
+                Err(e) if e.code() == ErrorCodes::NotFound => {
+                    report
+                        .violations
+                        .push(FsckViolation::MissingBlock { block_id });
+                    continue;
+                }
+                Err(e) => {
+                    report.violations.push(FsckViolation::UnreadableBlock {
+                        block_id,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            report.blocks_checked += 1;
+
+            let size_bytes = block.get_size();
+            if size_bytes > max_block_size_bytes {
+                report.violations.push(FsckViolation::BlockTooLarge {
+                    block_id,
+                    size_bytes,
+                    max_block_size_bytes,
+                });
+            }
+            if self.root.version >= Version::V1_1 && value.count as usize != block.len() {
+                report.violations.push(FsckViolation::CountMismatch {
+                    block_id,
+                    recorded: value.count,
+                    actual: block.len(),
+                });
+            }
+
+            for (index, key) in block.composite_keys().enumerate() {
+                report.keys_checked += 1;
+                let key_delimiter = SparseIndexDelimiter::Key(key.clone());
+                if &key_delimiter < delimiter
+                    || next_delimiter.is_some_and(|next_delimiter| &key_delimiter >= next_delimiter)
+                {
+                    report
+                        .violations
+                        .push(FsckViolation::key_out_of_range(block_id, &key));
+                }
+                if let Some(previous_key) = &previous_key {
+                    if &key <= previous_key {
+                        report.violations.push(FsckViolation::unsorted_key(
+                            block_id,
+                            &key,
+                            index == 0,
+                        ));
+                    }
+                }
+                previous_key = Some(key);
+            }
+        }
+
+        report
+    }
+
     /// Check if the blockfile is valid.
     /// Validates that the sparse index is valid and that no block exceeds the max block size.
     pub async fn is_valid(&self) -> bool {
@@ -799,17 +1489,21 @@ mod tests {
     use crate::arrow::block::delta::types::Delta;
     use crate::arrow::block::delta::UnorderedBlockDelta;
     use crate::arrow::block::Block;
-    use crate::arrow::blockfile::ArrowUnorderedBlockfileWriter;
+    use crate::arrow::blockfile::{ArrowBlockfileReader, ArrowUnorderedBlockfileWriter};
+    use crate::arrow::fsck::FsckViolation;
     use crate::arrow::provider::{BlockManager, BlockfileReaderOptions, RootManager};
-    use crate::arrow::root::{RootWriter, Version};
-    use crate::arrow::sparse_index::SparseIndexWriter;
+    use crate::arrow::root::{RootReader, RootWriter, Version};
+    use crate::arrow::sparse_index::{SparseIndexReader, SparseIndexValue, SparseIndexWriter};
+    use crate::arrow::types::ValueDimension;
     use crate::key::CompositeKey;
+    use crate::BytesKey;
     use crate::{
         arrow::config::TEST_MAX_BLOCK_SIZE_BYTES, arrow::provider::ArrowBlockfileProvider,
     };
     use crate::{BlockfileReader, BlockfileWriter, BlockfileWriterOptions};
     use chroma_cache::{new_cache_for_test, AysncPartitionedMutex};
-    use chroma_storage::{local::LocalStorage, Storage};
+    use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+    use chroma_storage::{local::LocalStorage, GetOptions, Storage};
     use chroma_types::{CollectionUuid, DataRecord, DatabaseUuid, MetadataValue, SegmentUuid};
     use futures::{StreamExt, TryStreamExt};
     use parking_lot::Mutex;
@@ -1229,8 +1923,41 @@ mod tests {
                 ".get_range() and .get_range_stream() should return the same result"
             );
 
-            let mut kv_map = HashMap::new();
-            for entry in materialized_range {
+            let key_range = match operation {
+                ComparisonOperation::GreaterThan => {
+                    (Bound::Excluded(query.as_str()), Bound::Unbounded)
+                }
+                ComparisonOperation::GreaterThanOrEquals => {
+                    (Bound::Included(query.as_str()), Bound::Unbounded)
+                }
+                ComparisonOperation::LessThan => {
+                    (Bound::Unbounded, Bound::Excluded(query.as_str()))
+                }
+                ComparisonOperation::LessThanOrEquals => {
+                    (Bound::Unbounded, Bound::Included(query.as_str()))
+                }
+            };
+            let reversed_range = materialized_range.iter().rev().cloned().collect::<Vec<_>>();
+            let materialized_range_rev = reader
+                .get_range_rev(prefix..=prefix, key_range)
+                .await
+                .unwrap();
+            assert_eq!(
+                reversed_range, materialized_range_rev,
+                ".get_range_rev() should return .get_range() in reverse"
+            );
+            let stream_result_rev = reader
+                .get_range_stream_rev(prefix..=prefix, key_range)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(
+                reversed_range, stream_result_rev,
+                ".get_range_stream_rev() should return .get_range() in reverse"
+            );
+
+            let mut kv_map = HashMap::new();
+            for entry in materialized_range {
                 kv_map.insert(entry.1, entry.2);
             }
             for i in 1..num_keys {
@@ -1591,6 +2318,63 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_reverse_range_float_key() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let block_cache = new_cache_for_test();
+        let sparse_index_cache = new_cache_for_test();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            block_cache,
+            sparse_index_cache,
+        );
+        let prefix_path = String::from("");
+
+        let writer = provider
+            .write::<f32, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 2000;
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                writer.set(prefix, i as f32 / 2.0, i).await.unwrap();
+            }
+        }
+
+        let flusher = writer.commit::<f32, u32>().await.unwrap();
+        flusher.flush::<f32, u32>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = provider.read::<f32, u32>(read_options).await.unwrap();
+
+        // Top k of a single prefix without materializing the range
+        let top_k = reader
+            .get_range_stream_rev("b"..="b", ..)
+            .take(10)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            top_k,
+            (n - 10..n)
+                .rev()
+                .map(|i| ("b", i as f32 / 2.0, i))
+                .collect::<Vec<_>>()
+        );
+
+        // Descending order spans prefixes and respects the key bounds
+        let values = reader.get_range_rev("a"..="b", 10.0..=20.0).await.unwrap();
+        let expected = ["b", "a"]
+            .into_iter()
+            .flat_map(|prefix| (20..=40).rev().map(move |i| (prefix, i as f32 / 2.0, i)))
+            .collect::<Vec<_>>();
+        assert_eq!(values, expected);
+    }
+
     #[tokio::test]
     async fn test_roaring_bitmap_value() {
         let tmp_dir = tempfile::tempdir().unwrap();
@@ -1685,6 +2469,121 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_64_bit_keys() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        let prefix_path = String::from("");
+        let u64_writer = blockfile_provider
+            .write::<u64, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let i64_writer = blockfile_provider
+            .write::<i64, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let u64_id = u64_writer.id();
+        let i64_id = i64_writer.id();
+
+        // Keys above u32::MAX and negative keys must keep their numeric order across blocks
+        let n = 2000;
+        for i in 0..n {
+            u64_writer
+                .set("key", u32::MAX as u64 + i as u64, i)
+                .await
+                .unwrap();
+            i64_writer
+                .set("key", i as i64 - n as i64 / 2, i)
+                .await
+                .unwrap();
+        }
+
+        let flusher = u64_writer.commit::<u64, u32>().await.unwrap();
+        flusher.flush::<u64, u32>().await.unwrap();
+        let flusher = i64_writer.commit::<i64, u32>().await.unwrap();
+        flusher.flush::<i64, u32>().await.unwrap();
+
+        let u64_reader = blockfile_provider
+            .read::<u64, u32>(BlockfileReaderOptions::new(u64_id, prefix_path.clone()))
+            .await
+            .unwrap();
+        let i64_reader = blockfile_provider
+            .read::<i64, u32>(BlockfileReaderOptions::new(i64_id, prefix_path))
+            .await
+            .unwrap();
+        for i in 0..n {
+            let value = u64_reader
+                .get("key", u32::MAX as u64 + i as u64)
+                .await
+                .unwrap();
+            assert_eq!(value, Some(i));
+            let value = i64_reader
+                .get("key", i as i64 - n as i64 / 2)
+                .await
+                .unwrap();
+            assert_eq!(value, Some(i));
+        }
+
+        let negative = i64_reader.get_range("key"..="key", ..0).await.unwrap();
+        assert_eq!(
+            negative.into_iter().map(|(_, _, v)| v).collect::<Vec<_>>(),
+            (0..n / 2).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bytes_key() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<BytesKey, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 2000u32;
+        for i in 0..n {
+            // Big endian so that the byte order matches the numeric order
+            let key = i.to_be_bytes();
+            writer.set("hash", BytesKey(&key), i).await.unwrap();
+        }
+
+        let flusher = writer.commit::<BytesKey, u32>().await.unwrap();
+        flusher.flush::<BytesKey, u32>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = blockfile_provider
+            .read::<BytesKey, u32>(read_options)
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = i.to_be_bytes();
+            let value = reader.get("hash", BytesKey(&key)).await.unwrap();
+            assert_eq!(value, Some(i));
+        }
+        let values = reader.get_range("hash"..="hash", ..).await.unwrap();
+        assert_eq!(values.len(), n as usize);
+        for (i, (_, key, value)) in values.into_iter().enumerate() {
+            assert_eq!(key.0, (i as u32).to_be_bytes());
+            assert_eq!(value, i as u32);
+        }
+    }
+
     #[tokio::test]
     async fn test_data_record_val() {
         let tmp_dir = tempfile::tempdir().unwrap();
@@ -1741,6 +2640,200 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_f32_vector_and_bytes_values() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        let prefix_path = String::from("");
+        let n = 2000u32;
+        let dimension = 8;
+
+        let vector_writer = blockfile_provider
+            .write::<u32, Vec<f32>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let vector_id = vector_writer.id();
+        let bytes_writer = blockfile_provider
+            .write::<u32, Vec<u8>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let bytes_id = bytes_writer.id();
+
+        for i in 0..n {
+            let vector = (0..dimension).map(|d| (i * d) as f32).collect::<Vec<_>>();
+            vector_writer.set("vector", i, vector).await.unwrap();
+            // Variable length blobs, including an empty one
+            let blob = vec![i as u8; (i % 17) as usize];
+            bytes_writer.set("blob", i, blob).await.unwrap();
+        }
+
+        let flusher = vector_writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+        let flusher = bytes_writer.commit::<u32, Vec<u8>>().await.unwrap();
+        flusher.flush::<u32, Vec<u8>>().await.unwrap();
+
+        let vector_reader = blockfile_provider
+            .read::<u32, &[f32]>(BlockfileReaderOptions::new(vector_id, prefix_path.clone()))
+            .await
+            .unwrap();
+        let bytes_reader = blockfile_provider
+            .read::<u32, &[u8]>(BlockfileReaderOptions::new(bytes_id, prefix_path))
+            .await
+            .unwrap();
+        for i in 0..n {
+            let expected_vector = (0..dimension).map(|d| (i * d) as f32).collect::<Vec<_>>();
+            let vector = vector_reader.get("vector", i).await.unwrap().unwrap();
+            assert_eq!(vector, expected_vector.as_slice());
+
+            let blob = bytes_reader.get("blob", i).await.unwrap().unwrap();
+            assert_eq!(blob, vec![i as u8; (i % 17) as usize].as_slice());
+        }
+
+        let vectors = vector_reader.get_range(.., ..).await.unwrap();
+        assert_eq!(vectors.len(), n as usize);
+        for (i, (_, key, vector)) in vectors.into_iter().enumerate() {
+            assert_eq!(key, i as u32);
+            assert_eq!(vector.len(), dimension as usize);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_f32_vector_dimension_mismatch() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        writer.set("vector", 0u32, vec![1.0; 8]).await.unwrap();
+        let err = writer.set("vector", 1u32, vec![1.0; 4]).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        // The dimension of the vectors already in the forked block is enforced as well
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(BlockfileWriterOptions::new(prefix_path.clone()).fork(id))
+            .await
+            .unwrap();
+        let err = writer.set("vector", 2u32, vec![1.0; 4]).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        writer.set("vector", 2u32, vec![2.0; 8]).await.unwrap();
+        let forked_id = writer.id();
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(
+                BlockfileWriterOptions::new(prefix_path.clone())
+                    .ordered_mutations()
+                    .fork(id),
+            )
+            .await
+            .unwrap();
+        let err = writer.set("vector", 3u32, vec![1.0; 4]).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<u32, &[f32]>(BlockfileReaderOptions::new(forked_id, prefix_path))
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.get("vector", 0).await.unwrap(),
+            Some([1.0; 8].as_slice())
+        );
+        assert_eq!(reader.get("vector", 1).await.unwrap(), None);
+        assert_eq!(
+            reader.get("vector", 2).await.unwrap(),
+            Some([2.0; 8].as_slice())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_f32_vector_dimension_is_fixed_per_blockfile() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_min_block_fill_factor(0.5);
+
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        let n = 2000u32;
+        for i in 0..n {
+            writer.set("vector", i, vec![i as f32; 8]).await.unwrap();
+        }
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        // Emptying the block of a key does not allow another dimension in that block, as it
+        // would be merged with its neighbors
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(BlockfileWriterOptions::new(prefix_path.clone()).fork(id))
+            .await
+            .unwrap();
+        for i in 0..n / 2 {
+            writer.delete::<u32, Vec<f32>>("vector", i).await.unwrap();
+        }
+        let err = writer.set("vector", 0u32, vec![0.0; 4]).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        writer.set("vector", 0u32, vec![0.0; 8]).await.unwrap();
+        let forked_id = writer.id();
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        let writer = blockfile_provider
+            .write::<u32, Vec<f32>>(
+                BlockfileWriterOptions::new(prefix_path.clone())
+                    .ordered_mutations()
+                    .fork(id),
+            )
+            .await
+            .unwrap();
+        writer
+            .delete_range::<u32, Vec<f32>>("vector", ..n / 2)
+            .await
+            .unwrap();
+        let err = writer.set("vector", n / 2, vec![0.0; 4]).await.unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        let flusher = writer.commit::<u32, Vec<f32>>().await.unwrap();
+        flusher.flush::<u32, Vec<f32>>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<u32, &[f32]>(BlockfileReaderOptions::new(forked_id, prefix_path))
+            .await
+            .unwrap();
+        let values = reader.get_range("vector"..="vector", ..).await.unwrap();
+        assert_eq!(values.len(), n as usize / 2 + 1);
+        assert!(values.iter().all(|(_, _, value)| value.len() == 8));
+    }
+
     #[tokio::test]
     async fn test_large_split_value() {
         // Tests the case where a value is larger than half the block size
@@ -1868,7 +2961,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_rank() {
+    async fn test_delete_range() {
         let tmp_dir = tempfile::tempdir().unwrap();
         let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
         let block_cache = new_cache_for_test();
@@ -1881,34 +2974,672 @@ mod tests {
         );
         let prefix_path = String::from("");
         let writer = blockfile_provider
-            .write::<&str, Vec<u32>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.clone()))
             .await
             .unwrap();
-        let id_1 = writer.id();
+        let id = writer.id();
 
-        let n = 1200;
-        for i in 0..n {
-            let key = format!("{:04}", i);
-            let value = vec![i];
-            writer.set("key", key.as_str(), value).await.unwrap();
+        let n = 1000;
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                writer
+                    .set(prefix, key.as_str(), format!("{}{}", prefix, key))
+                    .await
+                    .unwrap();
+            }
         }
-        let flusher = writer.commit::<&str, Vec<u32>>().await.unwrap();
-        flusher.flush::<&str, Vec<u32>>().await.unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
 
-        let read_options = BlockfileReaderOptions::new(id_1, prefix_path);
-        let reader = blockfile_provider
-            .read::<&str, &[u32]>(read_options)
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.clone()).fork(id))
             .await
             .unwrap();
+        let id = writer.id();
 
-        for i in 0..n {
-            let rank_key = format!("{:04}", i);
-            let rank = reader.rank("key", &rank_key).await.unwrap();
-            assert_eq!(rank, i as usize);
+        writer.delete_prefix::<&str, String>("b").await.unwrap();
+        // Only the blocks at the boundaries of the prefix are rewritten
+        match &writer {
+            BlockfileWriter::ArrowUnorderedBlockfileWriter(writer) => {
+                assert!(writer.block_deltas.lock().len() <= 2);
+            }
+            _ => panic!("Unexpected writer type"),
         }
-    }
+        writer
+            .delete_range::<&str, String>("c", "0100".."0200")
+            .await
+            .unwrap();
+        // Deleting a key that was set in this writer
+        writer
+            .set("c", "0500", "new value".to_string())
+            .await
+            .unwrap();
+        writer
+            .delete_range::<&str, String>("c", "0500"..="0501")
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
 
-    #[tokio::test]
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = blockfile_provider
+            .read::<&str, &str>(read_options)
+            .await
+            .unwrap();
+        assert_eq!(reader.count().await.unwrap(), 2 * n - 102);
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                let deleted = prefix == "b"
+                    || (prefix == "c" && ((100..200).contains(&i) || (500..=501).contains(&i)));
+                let value = reader.get(prefix, &key).await.unwrap();
+                if deleted {
+                    assert!(value.is_none());
+                } else {
+                    assert_eq!(value.unwrap(), format!("{}{}", prefix, key));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spill_to_disk() {
+        use rand::seq::SliceRandom;
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+
+        // The same mutations in random order, with and without a memory budget
+        let n = 3000;
+        let mut keys = (0..n).collect::<Vec<_>>();
+        keys.shuffle(&mut rand::thread_rng());
+        let mut ids = Vec::new();
+        for memory_budget_bytes in [None, Some(2 * TEST_MAX_BLOCK_SIZE_BYTES)] {
+            let mut options = BlockfileWriterOptions::new(prefix_path.clone());
+            if let Some(memory_budget_bytes) = memory_budget_bytes {
+                options = options.memory_budget_bytes(memory_budget_bytes);
+            }
+            let writer = blockfile_provider
+                .write::<&str, String>(options)
+                .await
+                .unwrap();
+            for i in keys.iter() {
+                let key = format!("{:04}", i);
+                writer
+                    .set("key", key.as_str(), format!("value{}", i))
+                    .await
+                    .unwrap();
+                if i % 10 == 0 {
+                    writer
+                        .delete::<&str, String>("key", key.as_str())
+                        .await
+                        .unwrap();
+                }
+            }
+            writer
+                .delete_range::<&str, String>("key", "1000".."1100")
+                .await
+                .unwrap();
+            if memory_budget_bytes.is_some() {
+                match &writer {
+                    BlockfileWriter::ArrowUnorderedBlockfileWriter(writer) => {
+                        assert!(writer.block_deltas.lock().len() <= 2);
+                        assert!(writer.spill.as_ref().unwrap().len() > 0);
+                    }
+                    _ => panic!("Unexpected writer type"),
+                }
+            }
+            ids.push(writer.id());
+            let flusher = writer.commit::<&str, String>().await.unwrap();
+            flusher.flush::<&str, String>().await.unwrap();
+        }
+
+        // Both writers produce the same blocks
+        let mut readers = Vec::new();
+        let mut blocks = Vec::new();
+        for id in ids {
+            let reader = match blockfile_provider
+                .read::<&str, &str>(BlockfileReaderOptions::new(id, prefix_path.clone()))
+                .await
+                .unwrap()
+            {
+                BlockfileReader::ArrowBlockfileReader(reader) => reader,
+                _ => panic!("Unexpected reader type"),
+            };
+            let mut block_bytes = Vec::new();
+            for block_value in reader.root.sparse_index.data.forward.values() {
+                let block = reader
+                    .get_block(block_value.id, StorageRequestPriority::P0)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                block_bytes.push(block.to_bytes().unwrap());
+            }
+            blocks.push(block_bytes);
+            readers.push(reader);
+        }
+        assert!(blocks[0].len() > 2);
+        assert_eq!(blocks[0], blocks[1]);
+
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            let value = readers[1].get("key", key.as_str()).await.unwrap();
+            if i % 10 == 0 || (1000..1100).contains(&i) {
+                assert_eq!(value, None);
+            } else {
+                assert_eq!(value, Some(format!("value{}", i).as_str()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_underfilled_blocks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let merging_blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_min_block_fill_factor(0.5);
+        let prefix_path = String::from("");
+
+        let n = 3000;
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            writer
+                .set("key", key.as_str(), format!("value{}", i))
+                .await
+                .unwrap();
+        }
+        let id = writer.id();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Delete most keys of every block, with and without merging underfilled blocks
+        let mut block_counts = Vec::new();
+        for provider in [&blockfile_provider, &merging_blockfile_provider] {
+            let writer = provider
+                .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.clone()).fork(id))
+                .await
+                .unwrap();
+            for i in (0..n).filter(|i| i % 10 != 0) {
+                let key = format!("{:04}", i);
+                writer
+                    .delete::<&str, String>("key", key.as_str())
+                    .await
+                    .unwrap();
+            }
+            let forked_id = writer.id();
+            let flusher = writer.commit::<&str, String>().await.unwrap();
+            flusher.flush::<&str, String>().await.unwrap();
+
+            let reader = match provider
+                .read::<&str, &str>(BlockfileReaderOptions::new(forked_id, prefix_path.clone()))
+                .await
+                .unwrap()
+            {
+                BlockfileReader::ArrowBlockfileReader(reader) => reader,
+                _ => panic!("Unexpected reader type"),
+            };
+            assert_eq!(reader.count().await.unwrap(), n / 10);
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                let value = reader.get("key", key.as_str()).await.unwrap();
+                if i % 10 == 0 {
+                    assert_eq!(value, Some(format!("value{}", i).as_str()));
+                } else {
+                    assert_eq!(value, None);
+                }
+            }
+            block_counts.push(reader.root.sparse_index.len());
+        }
+        assert!(block_counts[0] > 2);
+        assert!(block_counts[1] < block_counts[0]);
+    }
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<u32, String>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 2000;
+        for i in 0..n {
+            writer.set("key", i, format!("value{}", i)).await.unwrap();
+        }
+        let flusher = writer.commit::<u32, String>().await.unwrap();
+        flusher.flush::<u32, String>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = blockfile_provider
+            .read::<u32, &str>(read_options)
+            .await
+            .unwrap();
+
+        // Keys out of order, repeated, missing and with an unknown prefix
+        let mut rng = rand::thread_rng();
+        let mut keys = (0..n + 100)
+            .choose_multiple(&mut rng, 500)
+            .into_iter()
+            .map(|i| ("key".to_string(), i))
+            .collect::<Vec<_>>();
+        keys.push(("key".to_string(), keys[0].1));
+        keys.push(("other".to_string(), 1));
+
+        let values = reader.get_many(keys.clone()).await.unwrap();
+        assert_eq!(values.len(), keys.len());
+        for ((prefix, key), value) in keys.into_iter().zip(values) {
+            if prefix == "key" && key < n {
+                assert_eq!(value, Some(format!("value{}", key).as_str()));
+            } else {
+                assert_eq!(value, None);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_prefixes_with_counts() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let expected = vec![
+            ("a".to_string(), 1),
+            ("b".to_string(), 2000),
+            ("c".to_string(), 10),
+            ("d".to_string(), 3000),
+        ];
+        for (prefix, n) in &expected {
+            for i in 0..*n {
+                let key = format!("{:04}", i);
+                writer
+                    .set(prefix, key.as_str(), format!("{}{}", prefix, key))
+                    .await
+                    .unwrap();
+            }
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = blockfile_provider
+            .read::<&str, &str>(read_options)
+            .await
+            .unwrap();
+        assert_eq!(reader.get_prefixes_with_counts().await.unwrap(), expected);
+        // Blocks that only hold keys of one prefix are not fetched
+        match &reader {
+            BlockfileReader::ArrowBlockfileReader(reader) => {
+                assert!(reader.root.sparse_index.len() > 4);
+                assert!(reader.loaded_blocks.read().len() <= 3);
+            }
+            _ => panic!("Unexpected reader type"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_by_rank_and_sample() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<u32, String>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let prefixes = [("a", 10u32), ("b", 5000), ("c", 10)];
+        for (prefix, n) in prefixes {
+            for i in 0..n {
+                writer
+                    .set(prefix, i * 2, format!("{}{}", prefix, i * 2))
+                    .await
+                    .unwrap();
+            }
+        }
+        let flusher = writer.commit::<u32, String>().await.unwrap();
+        flusher.flush::<u32, String>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id, prefix_path);
+        let reader = blockfile_provider
+            .read::<u32, &str>(read_options)
+            .await
+            .unwrap();
+
+        // Blocks that only hold keys of the prefix are skipped using the counts
+        assert_eq!(
+            reader.get_by_rank("b", 1500).await.unwrap(),
+            Some((3000, "b3000"))
+        );
+        match &reader {
+            BlockfileReader::ArrowBlockfileReader(reader) => {
+                assert!(reader.root.sparse_index.len() > 3);
+                assert!(reader.loaded_blocks.read().len() <= 2);
+            }
+            _ => panic!("Unexpected reader type"),
+        }
+
+        for (prefix, n) in prefixes {
+            for rank in (0..n).step_by(7).chain([n - 1]) {
+                let value = format!("{}{}", prefix, rank * 2);
+                assert_eq!(
+                    reader.get_by_rank(prefix, rank as usize).await.unwrap(),
+                    Some((rank * 2, value.as_str()))
+                );
+                assert_eq!(
+                    reader.rank(prefix, rank * 2).await.unwrap()
+                        - reader.rank(prefix, 0).await.unwrap(),
+                    rank as usize
+                );
+            }
+            assert_eq!(reader.get_by_rank(prefix, n as usize).await.unwrap(), None);
+        }
+        assert_eq!(reader.get_by_rank("d", 0).await.unwrap(), None);
+
+        let sample = reader.sample(100).await.unwrap();
+        assert_eq!(sample.len(), 100);
+        for window in sample.windows(2) {
+            assert!((window[0].0, window[0].1) < (window[1].0, window[1].1));
+        }
+        for (prefix, key, value) in sample {
+            assert_eq!(value, format!("{}{}", prefix, key));
+        }
+        assert_eq!(reader.sample(10000).await.unwrap().len(), 5020);
+    }
+
+    #[tokio::test]
+    async fn test_fsck() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<&str, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 5000u32;
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            writer.set("key", key.as_str(), i).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, u32>().await.unwrap();
+        flusher.flush::<&str, u32>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<&str, u32>(BlockfileReaderOptions::new(id, prefix_path))
+            .await
+            .unwrap();
+        let reader = match reader {
+            BlockfileReader::ArrowBlockfileReader(reader) => reader,
+            _ => panic!("Expected an arrow blockfile reader"),
+        };
+        let report = reader.fsck().await;
+        assert!(report.is_ok(), "{:?}", report.violations);
+        assert_eq!(report.keys_checked, n as usize);
+        assert_eq!(
+            report.blocks_checked,
+            reader.root.sparse_index.data.forward.len()
+        );
+
+        // Swap the blocks of the second and third entries, point the fourth entry at the
+        // same block as the third, and tamper with the count of the first entry
+        let mut entries = reader
+            .root
+            .sparse_index
+            .data
+            .forward
+            .iter()
+            .map(|(delimiter, value)| (delimiter.clone(), (value.id, value.count, value.checksum)))
+            .collect::<Vec<_>>();
+        assert!(entries.len() >= 4);
+        let (first_block, second_block) = (entries[1].1, entries[2].1);
+        entries[1].1 = second_block;
+        entries[2].1 = first_block;
+        entries[3].1 = first_block;
+        entries[0].1 .1 += 1;
+        let forward = entries
+            .iter()
+            .map(|(delimiter, (block_id, count, checksum))| {
+                (
+                    delimiter.clone(),
+                    SparseIndexValue::new(*block_id, *count).with_checksum(*checksum),
+                )
+            })
+            .collect();
+        let tampered = ArrowBlockfileReader::<&str, u32>::new(
+            reader.block_manager.clone(),
+            RootReader {
+                sparse_index: SparseIndexReader::new(forward),
+                id: reader.root.id,
+                version: reader.root.version,
+                prefix_path: reader.root.prefix_path.clone(),
+            },
+        );
+        let report = tampered.fsck().await;
+        assert!(!report.is_ok());
+        assert!(report.violations.contains(&FsckViolation::CountMismatch {
+            block_id: entries[0].1 .0,
+            recorded: entries[0].1 .1,
+            actual: entries[0].1 .1 as usize - 1,
+        }));
+        assert!(report
+            .violations
+            .contains(&FsckViolation::DuplicateBlockId {
+                block_id: first_block.0
+            }));
+        assert!(report.violations.iter().any(|violation| matches!(
+            violation,
+            FsckViolation::UnsortedAcrossBlocks { block_id, .. } if *block_id == first_block.0
+        )));
+        for block_id in [first_block.0, second_block.0] {
+            assert!(report.violations.iter().any(|violation| matches!(
+                violation,
+                FsckViolation::KeyOutOfRange { block_id: out_of_range, .. } if *out_of_range == block_id
+            )));
+        }
+        assert!(!report
+            .violations
+            .iter()
+            .any(|violation| matches!(violation, FsckViolation::UnsortedKey { .. })));
+
+        // A block that is cached but missing from storage is reported as missing
+        let (_, missing_block) = reader.root.sparse_index.data.forward.iter().next().unwrap();
+        let missing_block_id = missing_block.id;
+        assert!(reader
+            .get_block(missing_block_id, StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .is_some());
+        storage
+            .delete(&BlockManager::format_key(
+                &reader.root.prefix_path,
+                &missing_block_id,
+            ))
+            .await
+            .unwrap();
+        let report = reader.fsck().await;
+        assert_eq!(
+            report.violations,
+            vec![FsckViolation::MissingBlock {
+                block_id: missing_block_id
+            }]
+        );
+        assert_eq!(
+            report.keys_checked,
+            n as usize - missing_block.count as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stats_and_estimate_range() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<&str, u32>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 2000u32;
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                writer.set(prefix, key.as_str(), i).await.unwrap();
+            }
+        }
+        let flusher = writer.commit::<&str, u32>().await.unwrap();
+        flusher.flush::<&str, u32>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<&str, u32>(BlockfileReaderOptions::new(id, prefix_path))
+            .await
+            .unwrap();
+        let reader = match reader {
+            BlockfileReader::ArrowBlockfileReader(reader) => reader,
+            _ => panic!("Expected an arrow blockfile reader"),
+        };
+        assert_eq!(reader.root.version, Version::V2);
+
+        let stats = reader.stats();
+        assert_eq!(stats.num_blocks, reader.root.sparse_index.len());
+        assert_eq!(stats.num_keys, 3 * n as u64);
+        assert_eq!(stats.blocks_without_stats, 0);
+        assert!(stats.oldest_block_created_at.is_some());
+        assert!(stats.oldest_block_created_at <= stats.newest_block_created_at);
+
+        // The recorded sizes and last keys match the blocks in storage
+        let mut size_bytes = 0;
+        for value in reader.root.sparse_index.data.forward.values() {
+            let block = reader
+                .get_block(value.id, StorageRequestPriority::P0)
+                .await
+                .unwrap()
+                .unwrap();
+            size_bytes += block.to_bytes().unwrap().len() as u64;
+            let block_stats = value.stats.as_ref().unwrap();
+            assert_eq!(
+                block.last_key::<&str>().as_ref(),
+                Some(&block_stats.last_key)
+            );
+        }
+        assert_eq!(stats.size_bytes, size_bytes);
+
+        // A range scan is estimated by the blocks it would load
+        let estimate = reader.estimate_range("b"..="b");
+        assert!(estimate.num_blocks < stats.num_blocks);
+        assert!(estimate.num_keys >= n as u64);
+        assert!(estimate.num_keys < stats.num_keys);
+        assert!(estimate.size_bytes < stats.size_bytes);
+        assert_eq!(
+            reader.get_range("b"..="b", ..).await.unwrap().len(),
+            n as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rank() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let block_cache = new_cache_for_test();
+        let sparse_index_cache = new_cache_for_test();
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            block_cache,
+            sparse_index_cache,
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<&str, Vec<u32>>(BlockfileWriterOptions::new(prefix_path.clone()))
+            .await
+            .unwrap();
+        let id_1 = writer.id();
+
+        let n = 1200;
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            let value = vec![i];
+            writer.set("key", key.as_str(), value).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, Vec<u32>>().await.unwrap();
+        flusher.flush::<&str, Vec<u32>>().await.unwrap();
+
+        let read_options = BlockfileReaderOptions::new(id_1, prefix_path);
+        let reader = blockfile_provider
+            .read::<&str, &[u32]>(read_options)
+            .await
+            .unwrap();
+
+        for i in 0..n {
+            let rank_key = format!("{:04}", i);
+            let rank = reader.rank("key", &rank_key).await.unwrap();
+            assert_eq!(rank, i as usize);
+        }
+    }
+
+    #[tokio::test]
     async fn test_first_block_removal() {
         let tmp_dir = tempfile::tempdir().unwrap();
         let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
@@ -2075,6 +3806,8 @@ mod tests {
             root: root_writer,
             id: Uuid::new_v4(),
             deltas_mutex: Arc::new(AysncPartitionedMutex::new(())),
+            spill: None,
+            value_dimension: ValueDimension::default(),
         };
 
         let n = 2000;
@@ -2158,14 +3891,17 @@ mod tests {
         let old_block_2_record_batch = old_block_delta_2.finish::<&str, String>(None);
         let old_block_2 = Block::from_record_batch(old_block_id_2, old_block_2_record_batch);
         block_manager
-            .flush(&old_block_1, prefix_path)
+            .flush(&old_block_1, prefix_path, StorageRequestPriority::P0)
             .await
             .unwrap();
         block_manager
-            .flush(&old_block_2, prefix_path)
+            .flush(&old_block_2, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
+        root_manager
+            .flush::<&str>(&old_root_writer, StorageRequestPriority::P0)
             .await
             .unwrap();
-        root_manager.flush::<&str>(&old_root_writer).await.unwrap();
 
         // We now have a v1 blockfile with 2 blocks and no counts in the root
 
@@ -2251,7 +3987,7 @@ mod tests {
             _ => panic!("Unexpected reader type"),
         };
 
-        assert_eq!(reader.root.version, Version::V1_1);
+        assert_eq!(reader.root.version, Version::V2);
         assert_eq!(reader.root.sparse_index.len(), 2);
 
         // Manually verify sparse index counts
@@ -2265,5 +4001,24 @@ mod tests {
             .sum();
         assert_eq!(count_in_index, 3);
         assert_eq!(reader.count().await.unwrap(), 3);
+
+        // Only the rewritten block has statistics, the migrated block is not fetched for them
+        for value in reader.root.sparse_index.data.forward.values() {
+            if value.count != 2 {
+                assert!(value.stats.is_none());
+                continue;
+            }
+            let stats = value.stats.as_ref().expect("Stats to be recorded");
+            let stored_bytes = storage
+                .get(
+                    &BlockManager::format_key(prefix_path, &value.id),
+                    GetOptions::new(StorageRequestPriority::P0),
+                )
+                .await
+                .unwrap();
+            assert_eq!(stats.size_bytes, stored_bytes.len() as u64);
+            assert!(stats.created_at.is_some());
+        }
+        assert_eq!(reader.stats().blocks_without_stats, 1);
     }
 }
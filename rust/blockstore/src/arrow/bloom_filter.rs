@@ -0,0 +1,204 @@
+use crate::key::{CompositeKey, KeyWrapper};
+use serde::{Deserialize, Serialize};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+// Separates the prefix from the key when hashing, 0xff never appears in UTF-8
+const PREFIX_SEPARATOR: u8 = 0xff;
+const MAX_NUM_HASHES: u32 = 30;
+
+/// A Bloom filter over the composite keys stored in a single block.
+/// It is stored in the root next to the block's sparse index entry so that
+/// point lookups for keys that are not in the block can skip fetching it.
+/// # Notes
+/// - The hash is stable across processes and releases since the filter is persisted.
+/// - A filter never returns a false negative, so a block is only skipped when the key
+///   is definitely not in it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockBloomFilter {
+    bits: Vec<u64>,
+    num_hashes: u32,
+}
+
+impl BlockBloomFilter {
+    /// Create an empty filter sized for `num_keys` keys with `bits_per_key` bits each
+    pub(crate) fn new(num_keys: usize, bits_per_key: usize) -> Self {
+        let bits_per_key = bits_per_key.max(1);
+        let num_bits = (num_keys.max(1) * bits_per_key).max(64);
+        let num_words = num_bits.div_ceil(64);
+        // The false positive rate is minimized at ln(2) * bits per key hash functions
+        let num_hashes = ((bits_per_key as f64) * std::f64::consts::LN_2).round() as u32;
+        Self {
+            bits: vec![0; num_words],
+            num_hashes: num_hashes.clamp(1, MAX_NUM_HASHES),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, key: &CompositeKey) {
+        let num_bits = self.num_bits();
+        let (h1, h2) = hash_composite_key(key);
+        for i in 0..self.num_hashes as u64 {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns false if the key is definitely not in the block
+    pub(crate) fn may_contain(&self, key: &CompositeKey) -> bool {
+        let num_bits = self.num_bits();
+        let (h1, h2) = hash_composite_key(key);
+        (0..self.num_hashes as u64).all(|i| {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % num_bits;
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    fn num_bits(&self) -> u64 {
+        self.bits.len() as u64 * 64
+    }
+
+    /// Serialize the filter as the number of hashes followed by the bit words, all little endian
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + self.bits.len() * 8);
+        bytes.extend_from_slice(&self.num_hashes.to_le_bytes());
+        for word in self.bits.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize a filter written by `to_bytes`, returns None if the bytes are malformed
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 12 || (bytes.len() - 4) % 8 != 0 {
+            return None;
+        }
+        let num_hashes = u32::from_le_bytes(bytes[0..4].try_into().ok()?);
+        if num_hashes == 0 || num_hashes > MAX_NUM_HASHES {
+            return None;
+        }
+        let bits = bytes[4..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("Chunk is 8 bytes")))
+            .collect();
+        Some(Self { bits, num_hashes })
+    }
+}
+
+/// Hashes a composite key into the two hashes used for double hashing
+fn hash_composite_key(key: &CompositeKey) -> (u64, u64) {
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+    write(key.prefix.as_bytes());
+    write(&[PREFIX_SEPARATOR]);
+    match &key.key {
+        KeyWrapper::String(s) => {
+            write(&[0]);
+            write(s.as_bytes());
+        }
+        KeyWrapper::Float32(f) => {
+            write(&[1]);
+            // -0.0 and 0.0 compare equal so they must hash the same
+            let f = if *f == 0.0 { 0.0f32 } else { *f };
+            write(&f.to_bits().to_le_bytes());
+        }
+        KeyWrapper::Bool(b) => {
+            write(&[2]);
+            write(&[*b as u8]);
+        }
+        KeyWrapper::Uint32(u) => {
+            write(&[3]);
+            write(&u.to_le_bytes());
+        }
+        KeyWrapper::Uint64(u) => {
+            write(&[4]);
+            write(&u.to_le_bytes());
+        }
+        KeyWrapper::Int64(i) => {
+            write(&[5]);
+            write(&i.to_le_bytes());
+        }
+        KeyWrapper::Bytes(b) => {
+            write(&[6]);
+            write(b);
+        }
+    }
+    // Derive the second hash with a splitmix64 finalizer, it must be odd so
+    // that it is coprime with the number of bits
+    let mut h2 = hash;
+    h2 = (h2 ^ (h2 >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h2 = (h2 ^ (h2 >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h2 ^= h2 >> 31;
+    (hash, h2 | 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = BlockBloomFilter::new(1000, 10);
+        for i in 0..1000u32 {
+            filter.insert(&CompositeKey::new("prefix".to_string(), i));
+        }
+        for i in 0..1000u32 {
+            assert!(filter.may_contain(&CompositeKey::new("prefix".to_string(), i)));
+        }
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = BlockBloomFilter::new(1000, 10);
+        for i in 0..1000u32 {
+            filter.insert(&CompositeKey::new(
+                "prefix".to_string(),
+                format!("{:04}", i).as_str(),
+            ));
+        }
+        let false_positives = (1000..11000)
+            .filter(|i| {
+                filter.may_contain(&CompositeKey::new(
+                    "prefix".to_string(),
+                    format!("{:04}", i).as_str(),
+                ))
+            })
+            .count();
+        // 10 bits per key gives a ~1% false positive rate
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn test_prefix_is_part_of_the_key() {
+        let mut filter = BlockBloomFilter::new(1, 10);
+        filter.insert(&CompositeKey::new("ab".to_string(), "c"));
+        assert!(filter.may_contain(&CompositeKey::new("ab".to_string(), "c")));
+        assert!(!filter.may_contain(&CompositeKey::new("a".to_string(), "bc")));
+    }
+
+    #[test]
+    fn test_negative_zero() {
+        let mut filter = BlockBloomFilter::new(1, 10);
+        filter.insert(&CompositeKey::new("prefix".to_string(), 0.0f32));
+        assert!(filter.may_contain(&CompositeKey::new("prefix".to_string(), -0.0f32)));
+    }
+
+    #[test]
+    fn test_to_from_bytes() {
+        let mut filter = BlockBloomFilter::new(100, 8);
+        for i in 0..100u32 {
+            filter.insert(&CompositeKey::new("prefix".to_string(), i));
+        }
+        let bytes = filter.to_bytes();
+        assert_eq!(BlockBloomFilter::from_bytes(&bytes), Some(filter));
+        assert_eq!(
+            BlockBloomFilter::from_bytes(&bytes[..bytes.len() - 1]),
+            None
+        );
+        assert_eq!(BlockBloomFilter::from_bytes(&[]), None);
+    }
+}
@@ -11,6 +11,37 @@ pub struct ArrowBlockfileProviderConfig {
     #[serde(default)]
     #[serde(alias = "sparse_index_manager_config")]
     pub root_manager_config: RootManagerConfig,
+    /// When set, blocks and roots are encrypted at rest, in storage as well as in the disk cache.
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Serialize)]
+pub struct EncryptionConfig {
+    pub key_provider: KeyProviderConfig,
+    /// Read blocks and roots that were written in plain text instead of rejecting them.
+    /// Only meant for migrating blockfiles written before encryption was enabled.
+    #[serde(default)]
+    pub allow_plaintext: bool,
+}
+
+/// Where the keys that blocks and roots are encrypted with come from.
+#[derive(Deserialize, Debug, Clone, Serialize)]
+pub enum KeyProviderConfig {
+    /// Keys read from files in a local directory, see `LocalFileKeyProvider`
+    #[serde(rename = "local_file")]
+    LocalFile(LocalFileKeyProviderConfig),
+    /// The `BlockKeyProvider` registered in the registry
+    #[serde(rename = "registry")]
+    Registry,
+}
+
+#[derive(Deserialize, Debug, Clone, Serialize)]
+pub struct LocalFileKeyProviderConfig {
+    /// The directory that holds a file per key, named after the key id
+    pub key_dir: String,
+    /// The id of the key that new blocks and roots are encrypted with
+    pub current_key_id: String,
 }
 
 #[derive(Deserialize, Debug, Clone, Serialize)]
@@ -19,6 +50,25 @@ pub struct BlockManagerConfig {
     pub max_block_size_bytes: usize,
     #[serde(default)]
     pub block_cache_config: CacheConfig,
+    #[serde(default)]
+    pub block_compression: BlockCompression,
+    /// When set, a bloom filter with this many bits per key is stored in the root for
+    /// every flushed block, so that point lookups can skip blocks that do not contain a key.
+    #[serde(default)]
+    pub bloom_filter_bits_per_key: Option<usize>,
+    #[serde(default)]
+    pub flush_config: BlockFlushConfig,
+    /// When set, adjacent blocks rewritten by a writer are merged at commit if one of them is
+    /// filled below this fraction of `max_block_size_bytes` and the merged block fits. Must be
+    /// between 0 and 1 exclusive. Blocks that the writer did not rewrite are never merged, so an
+    /// underfilled block is only merged once a writer rewrites one of its neighbors too.
+    #[serde(default)]
+    pub min_block_fill_factor: Option<f64>,
+    /// When set, blocks that miss the block cache are memory mapped from files under this
+    /// directory. It should be the root of a local storage, otherwise blocks are written to it
+    /// when they are first fetched.
+    #[serde(default)]
+    pub mmap_dir: Option<String>,
 }
 
 impl BlockManagerConfig {
@@ -35,10 +85,77 @@ impl Default for BlockManagerConfig {
                 capacity: 1000,
                 ..Default::default()
             }),
+            block_compression: BlockCompression::default(),
+            bloom_filter_bits_per_key: None,
+            flush_config: BlockFlushConfig::default(),
+            min_block_fill_factor: None,
+            mmap_dir: None,
         }
     }
 }
 
+/// How blocks and roots are written to storage when a blockfile is flushed.
+/// Writes that fail with a transient storage error are retried, and a flush that still fails
+/// can be retried by the caller without rewriting the blocks that were already written.
+#[derive(Deserialize, Debug, Clone, Serialize)]
+pub struct BlockFlushConfig {
+    /// The maximum number of blocks written to storage concurrently.
+    #[serde(default = "BlockFlushConfig::default_max_concurrent_uploads")]
+    pub max_concurrent_uploads: usize,
+    /// The number of times a block or root write that failed with a transient storage error is
+    /// retried before the flush fails.
+    #[serde(default = "BlockFlushConfig::default_max_retries")]
+    pub max_retries: u32,
+    /// The delay before the first retry, doubled after every retry up to `max_backoff_ms`.
+    #[serde(default = "BlockFlushConfig::default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "BlockFlushConfig::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl BlockFlushConfig {
+    fn default_max_concurrent_uploads() -> usize {
+        64
+    }
+
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_initial_backoff_ms() -> u64 {
+        100
+    }
+
+    fn default_max_backoff_ms() -> u64 {
+        5000
+    }
+}
+
+impl Default for BlockFlushConfig {
+    fn default() -> Self {
+        BlockFlushConfig {
+            max_concurrent_uploads: BlockFlushConfig::default_max_concurrent_uploads(),
+            max_retries: BlockFlushConfig::default_max_retries(),
+            initial_backoff_ms: BlockFlushConfig::default_initial_backoff_ms(),
+            max_backoff_ms: BlockFlushConfig::default_max_backoff_ms(),
+        }
+    }
+}
+
+/// The codec used to compress the buffers of a block when it is written
+/// in Arrow IPC format. The codec is recorded in the IPC message itself,
+/// so readers do not need to know how a block was written in order to load it.
+#[derive(Default, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BlockCompression {
+    #[default]
+    #[serde(alias = "none")]
+    None,
+    #[serde(alias = "lz4")]
+    Lz4,
+    #[serde(alias = "zstd")]
+    Zstd,
+}
+
 #[derive(Deserialize, Debug, Clone, Serialize)]
 pub struct RootManagerConfig {
     #[serde(alias = "sparse_index_cache_config")]
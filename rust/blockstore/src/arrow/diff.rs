@@ -0,0 +1,138 @@
+use super::blockfile::{ArrowBlockfileError, ArrowBlockfileReader};
+use super::types::ArrowReadableKey;
+use super::types::ArrowReadableValue;
+use crate::key::KeyWrapper;
+use chroma_error::ChromaError;
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// A single difference between a base blockfile and another blockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffEntry<'me, K, V> {
+    /// The key is only present in the other blockfile
+    Added { prefix: &'me str, key: K, value: V },
+    /// The key is only present in the base blockfile
+    Removed { prefix: &'me str, key: K, value: V },
+    /// The key is present in both blockfiles with different values
+    Changed {
+        prefix: &'me str,
+        key: K,
+        old_value: V,
+        new_value: V,
+    },
+}
+
+/// Compares two blockfiles, typically a fork and the blockfile it was forked from.
+/// Blocks are immutable, so blocks that are referenced by both sparse indexes hold
+/// identical records and are never fetched. Only the remaining blocks are compared.
+pub struct BlockfileDiff<
+    'me,
+    K: ArrowReadableKey<'me> + Into<KeyWrapper>,
+    V: ArrowReadableValue<'me>,
+> {
+    base: ArrowBlockfileReader<'me, K, V>,
+    other: ArrowBlockfileReader<'me, K, V>,
+}
+
+impl<'me, K: ArrowReadableKey<'me> + Into<KeyWrapper>, V: ArrowReadableValue<'me> + PartialEq>
+    BlockfileDiff<'me, K, V>
+{
+    pub(super) fn new(
+        base: ArrowBlockfileReader<'me, K, V>,
+        other: ArrowBlockfileReader<'me, K, V>,
+    ) -> Self {
+        Self { base, other }
+    }
+
+    /// Returns the ids of the blocks that are referenced by both blockfiles
+    pub fn shared_block_ids(&self) -> HashSet<Uuid> {
+        let base_ids = self
+            .base
+            .root
+            .sparse_index
+            .get_block_ids_range(..)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        self.other
+            .root
+            .sparse_index
+            .get_block_ids_range(..)
+            .into_iter()
+            .filter(|id| base_ids.contains(id))
+            .collect()
+    }
+
+    /// Returns the added, removed and changed keys in ascending (prefix, key) order
+    pub async fn changes(&'me self) -> Result<Vec<DiffEntry<'me, K, V>>, Box<dyn ChromaError>> {
+        let shared_block_ids = self.shared_block_ids();
+        let base_records = Self::unshared_records(&self.base, &shared_block_ids).await?;
+        let other_records = Self::unshared_records(&self.other, &shared_block_ids).await?;
+
+        let mut changes = Vec::new();
+        let mut base_iter = base_records.into_iter().peekable();
+        let mut other_iter = other_records.into_iter().peekable();
+        loop {
+            let ordering = match (base_iter.peek(), other_iter.peek()) {
+                (Some((base_prefix, base_key, _)), Some((other_prefix, other_key, _))) => {
+                    base_prefix.cmp(other_prefix).then_with(|| {
+                        base_key
+                            .partial_cmp(other_key)
+                            // NaN is not allowed in keys, so keys always have a total order
+                            .expect("Keys should be comparable.")
+                    })
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                Ordering::Less => {
+                    let (prefix, key, value) = base_iter.next().expect("Peeked above");
+                    changes.push(DiffEntry::Removed { prefix, key, value });
+                }
+                Ordering::Greater => {
+                    let (prefix, key, value) = other_iter.next().expect("Peeked above");
+                    changes.push(DiffEntry::Added { prefix, key, value });
+                }
+                Ordering::Equal => {
+                    let (prefix, key, old_value) = base_iter.next().expect("Peeked above");
+                    let (_, _, new_value) = other_iter.next().expect("Peeked above");
+                    if old_value != new_value {
+                        changes.push(DiffEntry::Changed {
+                            prefix,
+                            key,
+                            old_value,
+                            new_value,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    async fn unshared_records(
+        reader: &'me ArrowBlockfileReader<'me, K, V>,
+        shared_block_ids: &HashSet<Uuid>,
+    ) -> Result<Vec<(&'me str, K, V)>, Box<dyn ChromaError>> {
+        let mut records = Vec::new();
+        for block_id in reader.root.sparse_index.get_block_ids_range(..) {
+            if shared_block_ids.contains(&block_id) {
+                continue;
+            }
+            let block = match reader.get_block(block_id, StorageRequestPriority::P0).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    return Err(Box::new(ArrowBlockfileError::BlockNotFound));
+                }
+                Err(e) => {
+                    return Err(Box::new(e));
+                }
+            };
+            records.extend(block.get_range::<K, V, _, _>(.., ..));
+        }
+        Ok(records)
+    }
+}
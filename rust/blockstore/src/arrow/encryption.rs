@@ -0,0 +1,453 @@
+use super::{block::BlockLoadError, config::LocalFileKeyProviderConfig};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use async_trait::async_trait;
+use chroma_config::registry::Injectable;
+use chroma_error::{ChromaError, ErrorCodes};
+use parking_lot::Mutex;
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, path::PathBuf, sync::Arc};
+use thiserror::Error;
+use uuid::Uuid;
+
+// Encrypted blocks and roots start with this magic instead of the Arrow IPC magic,
+// so readers can tell them apart from objects that were written in plain text.
+const ENVELOPE_MAGIC: &[u8; 8] = b"CHRMENC1";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const WRAPPED_KEY_LEN: usize = KEY_LEN + TAG_LEN;
+
+/// A 256 bit AES key. The key material is not printed by `Debug`.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl EncryptionKey {
+    pub fn new(key: [u8; KEY_LEN]) -> Self {
+        Self(key)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum KeyProviderError {
+    #[error("Key {0} not found")]
+    KeyNotFound(String),
+    #[error("Could not read key {0}: {1}")]
+    ReadError(String, #[source] std::io::Error),
+    #[error("Key {0} is not a 256 bit key")]
+    InvalidKey(String),
+}
+
+impl ChromaError for KeyProviderError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            KeyProviderError::KeyNotFound(_) => ErrorCodes::NotFound,
+            KeyProviderError::ReadError(_, _) => ErrorCodes::Internal,
+            KeyProviderError::InvalidKey(_) => ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+/// Provides the key encryption keys of the envelope encryption of blocks and roots.
+/// Every object is encrypted with its own data key, which is stored in the object wrapped
+/// with the current key of the provider. Keys are looked up by id when objects are decrypted,
+/// so keys that were rotated out must stay available for as long as objects use them.
+/// Providers may fetch keys from a remote key management service, and should cache them.
+#[async_trait]
+pub trait KeyProvider: Debug + Send + Sync {
+    /// The id of the key that new data keys are wrapped with
+    async fn current_key_id(&self) -> Result<String, KeyProviderError>;
+
+    async fn get_key(&self, key_id: &str) -> Result<EncryptionKey, KeyProviderError>;
+}
+
+/// The key provider of the blockstore, as registered in `chroma_config::registry::Registry`.
+/// Used by `ArrowBlockfileProvider` when its config sets `KeyProviderConfig::Registry`.
+#[derive(Clone, Debug)]
+pub struct BlockKeyProvider(pub Arc<dyn KeyProvider>);
+
+impl Injectable for BlockKeyProvider {}
+
+/// A key provider that reads keys from the files of a local directory, named after the key id.
+/// A key file holds the 32 bytes of the key, either raw or hex encoded.
+/// Keys are read once and kept in memory.
+#[derive(Debug)]
+pub struct LocalFileKeyProvider {
+    key_dir: PathBuf,
+    current_key_id: String,
+    keys: Mutex<HashMap<String, EncryptionKey>>,
+}
+
+impl LocalFileKeyProvider {
+    pub fn new(key_dir: impl Into<PathBuf>, current_key_id: impl Into<String>) -> Self {
+        Self {
+            key_dir: key_dir.into(),
+            current_key_id: current_key_id.into(),
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_config(config: &LocalFileKeyProviderConfig) -> Self {
+        Self::new(&config.key_dir, &config.current_key_id)
+    }
+
+    async fn read_key(&self, key_id: &str) -> Result<EncryptionKey, KeyProviderError> {
+        // Key ids are file names, so they must not reach outside of the key directory
+        if key_id.is_empty() || key_id.contains(['/', '\\']) || key_id == ".." {
+            return Err(KeyProviderError::KeyNotFound(key_id.to_string()));
+        }
+        let bytes = match tokio::fs::read(self.key_dir.join(key_id)).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(KeyProviderError::KeyNotFound(key_id.to_string()))
+            }
+            Err(e) => return Err(KeyProviderError::ReadError(key_id.to_string(), e)),
+        };
+        let key = match bytes.len() {
+            KEY_LEN => bytes,
+            _ => decode_hex(std::str::from_utf8(&bytes).unwrap_or_default().trim())
+                .ok_or_else(|| KeyProviderError::InvalidKey(key_id.to_string()))?,
+        };
+        key.try_into()
+            .map(EncryptionKey)
+            .map_err(|_| KeyProviderError::InvalidKey(key_id.to_string()))
+    }
+}
+
+#[async_trait]
+impl KeyProvider for LocalFileKeyProvider {
+    async fn current_key_id(&self) -> Result<String, KeyProviderError> {
+        Ok(self.current_key_id.clone())
+    }
+
+    async fn get_key(&self, key_id: &str) -> Result<EncryptionKey, KeyProviderError> {
+        if let Some(key) = self.keys.lock().get(key_id) {
+            return Ok(key.clone());
+        }
+        let key = self.read_key(key_id).await?;
+        self.keys.lock().insert(key_id.to_string(), key.clone());
+        Ok(key)
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error(transparent)]
+    KeyProviderError(#[from] KeyProviderError),
+    #[error("Could not encrypt")]
+    EncryptFailed,
+    #[error("Could not decrypt, the key is wrong or the data was modified")]
+    DecryptFailed,
+    #[error("Invalid encryption envelope")]
+    InvalidEnvelope,
+    #[error("Object is not encrypted and plain text objects are not allowed")]
+    Plaintext,
+}
+
+impl ChromaError for EncryptionError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            EncryptionError::KeyProviderError(e) => e.code(),
+            EncryptionError::EncryptFailed => ErrorCodes::Internal,
+            EncryptionError::DecryptFailed => ErrorCodes::DataLoss,
+            EncryptionError::InvalidEnvelope => ErrorCodes::DataLoss,
+            EncryptionError::Plaintext => ErrorCodes::FailedPrecondition,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum EncryptionConfigError {
+    #[error("The root cache must not be a disk cache when encryption is enabled")]
+    DiskRootCache,
+    #[error("Writers cannot spill to disk when encryption is enabled")]
+    Spill,
+}
+
+impl ChromaError for EncryptionConfigError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            EncryptionConfigError::DiskRootCache => ErrorCodes::InvalidArgument,
+            EncryptionConfigError::Spill => ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+/// Returns true if the bytes of a block or root were encrypted by `BlockEncryption`
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(ENVELOPE_MAGIC)
+}
+
+/// Envelope encryption of blocks and roots with AES-256-GCM.
+///
+/// The envelope is laid out as
+/// `magic | key id length (u16 le) | key id | data key nonce | wrapped data key | nonce | ciphertext`
+/// where the data key is wrapped with the key of the provider. The id of the block or root is
+/// authenticated along with the ciphertext, so an object cannot be swapped for another one.
+#[derive(Clone, Debug)]
+pub(crate) struct BlockEncryption {
+    key_provider: Arc<dyn KeyProvider>,
+    // Objects written before encryption was enabled are only readable during a migration
+    allow_plaintext: bool,
+}
+
+impl BlockEncryption {
+    pub(crate) fn new(key_provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            key_provider,
+            allow_plaintext: false,
+        }
+    }
+
+    /// Accept objects that are not encrypted when they are read
+    pub(crate) fn with_allow_plaintext(mut self, allow_plaintext: bool) -> Self {
+        self.allow_plaintext = allow_plaintext;
+        self
+    }
+
+    pub(crate) async fn encrypt(
+        &self,
+        plaintext: &[u8],
+        id: &Uuid,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let key_id = self.key_provider.current_key_id().await?;
+        let key = self.key_provider.get_key(&key_id).await?;
+        let key_id_len = u16::try_from(key_id.len())
+            .map_err(|_| KeyProviderError::InvalidKey(key_id.clone()))?;
+
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let data_key_nonce = Aes256Gcm::generate_nonce(OsRng);
+        let wrapped_data_key = key
+            .cipher()
+            .encrypt(&data_key_nonce, data_key.as_slice())
+            .map_err(|_| EncryptionError::EncryptFailed)?;
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = Aes256Gcm::new(&data_key)
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptFailed)?;
+
+        let mut envelope = Vec::with_capacity(
+            ENVELOPE_MAGIC.len()
+                + 2
+                + key_id.len()
+                + NONCE_LEN
+                + WRAPPED_KEY_LEN
+                + NONCE_LEN
+                + ciphertext.len(),
+        );
+        envelope.extend_from_slice(ENVELOPE_MAGIC);
+        envelope.extend_from_slice(&key_id_len.to_le_bytes());
+        envelope.extend_from_slice(key_id.as_bytes());
+        envelope.extend_from_slice(&data_key_nonce);
+        envelope.extend_from_slice(&wrapped_data_key);
+        envelope.extend_from_slice(&nonce);
+        envelope.extend_from_slice(&ciphertext);
+        Ok(envelope)
+    }
+
+    pub(crate) async fn decrypt(
+        &self,
+        envelope: &[u8],
+        id: &Uuid,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let mut reader = EnvelopeReader(
+            envelope
+                .strip_prefix(ENVELOPE_MAGIC)
+                .ok_or(EncryptionError::InvalidEnvelope)?,
+        );
+        let key_id_len =
+            u16::from_le_bytes(reader.take(2)?.try_into().expect("Took exactly two bytes"));
+        let key_id = std::str::from_utf8(reader.take(key_id_len as usize)?)
+            .map_err(|_| EncryptionError::InvalidEnvelope)?;
+        let data_key_nonce = Nonce::from_slice(reader.take(NONCE_LEN)?);
+        let wrapped_data_key = reader.take(WRAPPED_KEY_LEN)?;
+        let nonce = Nonce::from_slice(reader.take(NONCE_LEN)?);
+        let ciphertext = reader.0;
+
+        let key = self.key_provider.get_key(key_id).await?;
+        let data_key = key
+            .cipher()
+            .decrypt(data_key_nonce, wrapped_data_key)
+            .map_err(|_| EncryptionError::DecryptFailed)?;
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key))
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: id.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptFailed)
+    }
+}
+
+struct EnvelopeReader<'a>(&'a [u8]);
+
+impl<'a> EnvelopeReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EncryptionError> {
+        if self.0.len() < len {
+            return Err(EncryptionError::InvalidEnvelope);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+}
+
+/// Returns the plain text of the bytes of a block or root read from storage. When encryption
+/// is enabled, bytes that were not encrypted are rejected unless plain text is allowed while
+/// blockfiles written before encryption was enabled are migrated.
+pub(crate) async fn decrypt_if_encrypted<'a>(
+    encryption: Option<&BlockEncryption>,
+    bytes: &'a [u8],
+    id: &Uuid,
+) -> Result<Cow<'a, [u8]>, BlockLoadError> {
+    match encryption {
+        Some(encryption) if is_encrypted(bytes) => {
+            Ok(Cow::Owned(encryption.decrypt(bytes, id).await?))
+        }
+        Some(encryption) if encryption.allow_plaintext => Ok(Cow::Borrowed(bytes)),
+        Some(_) => Err(EncryptionError::Plaintext.into()),
+        None if is_encrypted(bytes) => Err(BlockLoadError::Encrypted),
+        None => Ok(Cow::Borrowed(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_key(dir: &std::path::Path, key_id: &str, key: [u8; KEY_LEN]) {
+        std::fs::write(dir.join(key_id), key).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_decrypt() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        write_key(tmp_dir.path(), "key1", [1; KEY_LEN]);
+        // Hex encoded keys are accepted as well
+        std::fs::write(
+            tmp_dir.path().join("key2"),
+            format!("{}\n", "02".repeat(KEY_LEN)),
+        )
+        .unwrap();
+
+        let id = Uuid::new_v4();
+        let encryption =
+            BlockEncryption::new(Arc::new(LocalFileKeyProvider::new(tmp_dir.path(), "key1")));
+        let envelope = encryption.encrypt(b"plain text", &id).await.unwrap();
+        assert!(is_encrypted(&envelope));
+        assert!(!envelope
+            .windows(b"plain text".len())
+            .any(|window| window == b"plain text"));
+        assert_eq!(
+            encryption.decrypt(&envelope, &id).await.unwrap(),
+            b"plain text"
+        );
+
+        // Objects encrypted with a rotated key stay readable
+        let rotated =
+            BlockEncryption::new(Arc::new(LocalFileKeyProvider::new(tmp_dir.path(), "key2")));
+        assert_eq!(
+            rotated.decrypt(&envelope, &id).await.unwrap(),
+            b"plain text"
+        );
+        let rotated_envelope = rotated.encrypt(b"plain text", &id).await.unwrap();
+        assert_eq!(
+            encryption.decrypt(&rotated_envelope, &id).await.unwrap(),
+            b"plain text"
+        );
+
+        // The envelope is bound to the id of the object
+        assert!(matches!(
+            encryption.decrypt(&envelope, &Uuid::new_v4()).await,
+            Err(EncryptionError::DecryptFailed)
+        ));
+
+        // Tampering is detected
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            encryption.decrypt(&tampered, &id).await,
+            Err(EncryptionError::DecryptFailed)
+        ));
+        assert!(matches!(
+            encryption
+                .decrypt(&envelope[..ENVELOPE_MAGIC.len() + 4], &id)
+                .await,
+            Err(EncryptionError::InvalidEnvelope)
+        ));
+
+        // A provider that does not have the key cannot decrypt
+        let other_dir = tempfile::tempdir().unwrap();
+        write_key(other_dir.path(), "key3", [3; KEY_LEN]);
+        let other = BlockEncryption::new(Arc::new(LocalFileKeyProvider::new(
+            other_dir.path(),
+            "key3",
+        )));
+        assert!(matches!(
+            other.decrypt(&envelope, &id).await,
+            Err(EncryptionError::KeyProviderError(
+                KeyProviderError::KeyNotFound(_)
+            ))
+        ));
+
+        // Without a key provider plain text passes through and encrypted bytes are rejected
+        assert_eq!(
+            decrypt_if_encrypted(None, b"plain text", &id)
+                .await
+                .unwrap(),
+            &b"plain text"[..]
+        );
+        assert!(matches!(
+            decrypt_if_encrypted(None, &envelope, &id).await,
+            Err(BlockLoadError::Encrypted)
+        ));
+
+        // With a key provider plain text is rejected, unless it is allowed for a migration
+        assert!(matches!(
+            decrypt_if_encrypted(Some(&encryption), b"plain text", &id).await,
+            Err(BlockLoadError::DecryptionError(EncryptionError::Plaintext))
+        ));
+        let migrating = encryption.clone().with_allow_plaintext(true);
+        assert_eq!(
+            decrypt_if_encrypted(Some(&migrating), b"plain text", &id)
+                .await
+                .unwrap(),
+            &b"plain text"[..]
+        );
+        assert_eq!(
+            decrypt_if_encrypted(Some(&migrating), &envelope, &id)
+                .await
+                .unwrap(),
+            &b"plain text"[..]
+        );
+    }
+}
@@ -1,13 +1,25 @@
 use super::{
     block::Block,
-    provider::{BlockManager, RootManager},
+    config::BlockFlushConfig,
+    provider::{BlockFlushError, BlockManager, RootManager},
     root::RootWriter,
+    sparse_index::BlockStats,
     types::{ArrowWriteableKey, ArrowWriteableValue},
 };
 use chroma_error::ChromaError;
-use futures::{StreamExt, TryStreamExt};
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use futures::{Future, StreamExt};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
+/// The priority of the storage writes of a flush. A blockfile is unreadable until it is
+/// flushed, so flushes are not deprioritized.
+const FLUSH_PRIORITY: StorageRequestPriority = StorageRequestPriority::P0;
+
 pub struct ArrowBlockfileFlusher {
     block_manager: BlockManager,
     root_manager: RootManager,
@@ -15,6 +27,16 @@ pub struct ArrowBlockfileFlusher {
     root: RootWriter,
     id: Uuid,
     count: u64,
+    state: Mutex<FlushState>,
+}
+
+/// The progress of a flush, so that a flush that failed can be retried without
+/// rewriting the blocks that are already in storage.
+#[derive(Default)]
+struct FlushState {
+    /// The checksum and size in bytes of every block that was written to storage
+    flushed_blocks: HashMap<Uuid, (u32, usize)>,
+    root_flushed: bool,
 }
 
 impl ArrowBlockfileFlusher {
@@ -33,38 +55,108 @@ impl ArrowBlockfileFlusher {
             root,
             id,
             count,
+            state: Mutex::new(FlushState::default()),
         }
     }
 
+    /// Write the blocks and then the root to storage. The root is only written once every block
+    /// is, so readers never see a root that points at missing blocks. If the flush fails it can be
+    /// called again, which only writes what is not in storage yet. Flushing a blockfile that was
+    /// already flushed is a no-op.
     #[allow(clippy::extra_unused_type_parameters)]
     pub(crate) async fn flush<K: ArrowWriteableKey, V: ArrowWriteableValue>(
-        self,
+        &self,
     ) -> Result<(), Box<dyn ChromaError>> {
+        // Held for the whole flush so that concurrent calls do not write the same blocks
+        let mut state = self.state.lock().await;
+        if state.root_flushed {
+            return Ok(());
+        }
+
         if self.root.sparse_index.len() == 0 {
             panic!("Invariant violation. Sparse index should be not empty during flush.");
         }
 
-        // Flush all blocks in parallel using futures unordered
-        // NOTE(hammadb) we do not use try_join_all here because we want to flush all blocks
-        // in parallel and try_join_all / join_all switches to using futures_ordered if the
-        // number of futures is high.
-        let mut futures = Vec::new();
+        let block_manager = &self.block_manager;
+        let prefix_path = &self.root.prefix_path;
+        let flush_config = block_manager.flush_config();
+        let pending = self
+            .blocks
+            .iter()
+            .filter(|block| !state.flushed_blocks.contains_key(&block.id))
+            .map(|block| async move {
+                let (checksum, size_bytes) =
+                    with_retries(flush_config, BlockFlushError::is_transient, || {
+                        block_manager.flush(block, prefix_path, FLUSH_PRIORITY)
+                    })
+                    .await?;
+                Ok::<_, Box<dyn ChromaError>>((block.id, checksum, size_bytes))
+            })
+            .collect::<Vec<_>>();
+        tracing::debug!("Flushing {} of {} blocks", pending.len(), self.blocks.len());
+        // buffer_unordered hangs with a limit of 0.
+        let mut uploads = futures::stream::iter(pending)
+            .buffer_unordered(flush_config.max_concurrent_uploads.max(1));
+        let mut blocks_written = 0;
+        while let Some(result) = uploads.next().await {
+            let (block_id, checksum, size_bytes) = result?;
+            state
+                .flushed_blocks
+                .insert(block_id, (checksum, size_bytes));
+            blocks_written += 1;
+        }
+        self.block_manager
+            .metrics()
+            .blocks_flushed(blocks_written, FLUSH_PRIORITY);
+
+        // Record the checksum of every flushed block in the root so that
+        // readers can verify the blocks they load
+        for (block_id, (checksum, _)) in state.flushed_blocks.iter() {
+            self.root
+                .sparse_index
+                .set_checksum(*block_id, *checksum)
+                .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+        }
+
+        // Record the statistics of every flushed block so that readers can prune
+        // and size range scans without fetching blocks. Empty blocks have no last key.
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_millis() as u64);
         for block in &self.blocks {
-            futures.push(self.block_manager.flush(block, &self.root.prefix_path));
+            let last_key = match block.last_key::<K::ReadableKey<'_>>() {
+                Some(last_key) => last_key,
+                None => continue,
+            };
+            let stats = BlockStats {
+                last_key,
+                size_bytes: state.flushed_blocks[&block.id].1 as u64,
+                created_at,
+            };
+            self.root
+                .sparse_index
+                .set_stats(block.id, stats)
+                .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
         }
-        let num_futures = futures.len();
-        // buffer_unordered hangs with 0 futures.
-        if num_futures == 0 {
-            self.root_manager.flush::<K>(&self.root).await?;
-            return Ok(());
+
+        // Build bloom filters after the blocks are durable, they are only
+        // visible to readers once the root is flushed
+        if let Some(bits_per_key) = self.block_manager.bloom_filter_bits_per_key() {
+            for block in &self.blocks {
+                let bloom_filter = block.build_bloom_filter::<K::ReadableKey<'_>>(bits_per_key);
+                self.root
+                    .sparse_index
+                    .set_bloom_filter(block.id, bloom_filter)
+                    .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+            }
         }
-        tracing::debug!("Flushing {} blocks", num_futures);
-        futures::stream::iter(futures)
-            .buffer_unordered(num_futures)
-            .try_collect::<Vec<_>>()
-            .await?;
 
-        self.root_manager.flush::<K>(&self.root).await?;
+        with_retries(flush_config, BlockFlushError::is_transient, || {
+            self.root_manager.flush::<K>(&self.root, FLUSH_PRIORITY)
+        })
+        .await?;
+        state.root_flushed = true;
         Ok(())
     }
 
@@ -84,3 +176,168 @@ impl ArrowBlockfileFlusher {
         &self.root.prefix_path
     }
 }
+
+/// Run a storage write until it succeeds, retrying it up to `max_retries` times with
+/// exponential backoff while it fails with an error that `is_transient` accepts. Block and
+/// root writes are idempotent, so retrying them is safe.
+async fn with_retries<T, E, F, Fut>(
+    config: &BlockFlushConfig,
+    is_transient: fn(&E) -> bool,
+    mut write: F,
+) -> Result<T, Box<dyn ChromaError>>
+where
+    E: ChromaError + 'static,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut backoff = Duration::from_millis(config.initial_backoff_ms);
+    let max_backoff = Duration::from_millis(config.max_backoff_ms);
+    let mut retries = 0;
+    loop {
+        match write().await {
+            Ok(result) => return Ok(result),
+            Err(e) if is_transient(&e) && retries < config.max_retries => {
+                retries += 1;
+                tracing::warn!(
+                    "Storage write failed, retrying in {:?} ({}/{}): {}",
+                    backoff,
+                    retries,
+                    config.max_retries,
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+            }
+            Err(e) => return Err(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        arrow::{
+            config::TEST_MAX_BLOCK_SIZE_BYTES,
+            provider::{ArrowBlockfileProvider, BlockfileReaderOptions},
+        },
+        BlockfileError, BlockfileFlusher, BlockfileWriterOptions,
+    };
+    use chroma_cache::new_cache_for_test;
+    use chroma_storage::{local::LocalStorage, Storage};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn test_flush_config() -> BlockFlushConfig {
+        BlockFlushConfig {
+            max_concurrent_uploads: 2,
+            max_retries: 2,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_retries() {
+        let config = test_flush_config();
+        let attempts = &AtomicU32::new(0);
+        let result = with_retries(
+            &config,
+            |_| true,
+            || async move {
+                if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return Err(BlockfileError::BlockNotFound);
+                }
+                Ok(42)
+            },
+        )
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let attempts = &AtomicU32::new(0);
+        let result = with_retries(
+            &config,
+            |_| true,
+            || async move {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(BlockfileError::BlockNotFound)
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Errors that are not transient are returned without retrying
+        let attempts = &AtomicU32::new(0);
+        let result = with_retries(&config, BlockFlushError::is_transient, || async move {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(BlockFlushError::EncodeError(Box::new(
+                BlockfileError::BlockNotFound,
+            )))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_after_failure() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_flush_config(test_flush_config());
+        let prefix_path = "prefix";
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.to_string()))
+            .await
+            .unwrap();
+        let n = 2000;
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            writer
+                .set("key", key.as_str(), format!("value_{}", i))
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        let id = flusher.id();
+
+        // A file where the root directory should be makes every root write fail
+        let root_dir = tmp_dir.path().join(prefix_path).join("root");
+        std::fs::create_dir_all(root_dir.parent().unwrap()).unwrap();
+        std::fs::write(&root_dir, b"").unwrap();
+        assert!(flusher.flush::<&str, String>().await.is_err());
+        match &flusher {
+            BlockfileFlusher::ArrowBlockfileFlusher(flusher) => {
+                let state = flusher.state.lock().await;
+                assert!(flusher.blocks.len() > 1);
+                assert_eq!(state.flushed_blocks.len(), flusher.blocks.len());
+                assert!(!state.root_flushed);
+            }
+            _ => panic!("Unexpected flusher type"),
+        }
+
+        std::fs::remove_file(&root_dir).unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+        // Flushing again is a no-op
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, prefix_path.to_string()))
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            let expected = format!("value_{}", i);
+            assert_eq!(
+                reader.get("key", &key).await.unwrap(),
+                Some(expected.as_str())
+            );
+        }
+    }
+}
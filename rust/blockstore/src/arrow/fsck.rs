@@ -0,0 +1,86 @@
+use crate::key::CompositeKey;
+use uuid::Uuid;
+
+/// A single problem found by `ArrowBlockfileReader::fsck`.
+/// Keys are reported as their prefix and the debug representation of the key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FsckViolation {
+    /// The sparse index does not start with the start delimiter
+    InvalidSparseIndex,
+    /// The block is referenced by more than one sparse index entry
+    DuplicateBlockId { block_id: Uuid },
+    /// The block is not present in storage
+    MissingBlock { block_id: Uuid },
+    /// The block could not be fetched or loaded, e.g. because of a checksum mismatch
+    UnreadableBlock { block_id: Uuid, error: String },
+    BlockTooLarge {
+        block_id: Uuid,
+        size_bytes: usize,
+        max_block_size_bytes: usize,
+    },
+    /// The key count recorded in a v1.1 root does not match the length of the block
+    CountMismatch {
+        block_id: Uuid,
+        recorded: u32,
+        actual: usize,
+    },
+    /// The key is not greater than the key before it in the same block
+    UnsortedKey {
+        block_id: Uuid,
+        prefix: String,
+        key: String,
+    },
+    /// The first key of the block is not greater than the last key of the previous block
+    UnsortedAcrossBlocks {
+        block_id: Uuid,
+        prefix: String,
+        key: String,
+    },
+    /// The key is outside of the range the sparse index assigns to the block
+    KeyOutOfRange {
+        block_id: Uuid,
+        prefix: String,
+        key: String,
+    },
+}
+
+impl FsckViolation {
+    pub(super) fn unsorted_key(block_id: Uuid, key: &CompositeKey, first_in_block: bool) -> Self {
+        let (prefix, key) = (key.prefix.clone(), format!("{:?}", key.key));
+        if first_in_block {
+            FsckViolation::UnsortedAcrossBlocks {
+                block_id,
+                prefix,
+                key,
+            }
+        } else {
+            FsckViolation::UnsortedKey {
+                block_id,
+                prefix,
+                key,
+            }
+        }
+    }
+
+    pub(super) fn key_out_of_range(block_id: Uuid, key: &CompositeKey) -> Self {
+        FsckViolation::KeyOutOfRange {
+            block_id,
+            prefix: key.prefix.clone(),
+            key: format!("{:?}", key.key),
+        }
+    }
+}
+
+/// The result of verifying a blockfile with `ArrowBlockfileReader::fsck`.
+#[derive(Debug, Default, Clone)]
+pub struct FsckReport {
+    pub blocks_checked: usize,
+    pub keys_checked: usize,
+    pub violations: Vec<FsckViolation>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
@@ -0,0 +1,371 @@
+use super::block::{Block, BlockLoadError};
+use super::encryption::{decrypt_if_encrypted, BlockEncryption, KeyProvider};
+use super::provider::{BlockManager, RootManager};
+use super::root::{FromBytesError, RootReader, Version};
+use arrow::array::{Array, StringArray, UInt32Array};
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use chroma_error::{ChromaError, ErrorCodes};
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use chroma_storage::{GetOptions, Storage, StorageError};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum InspectError {
+    #[error("Error reading from storage: {0}")]
+    Storage(#[from] StorageError),
+    #[error("Error reading root: {0}")]
+    Root(#[from] FromBytesError),
+    #[error("Error decrypting root: {0}")]
+    RootDecryption(BlockLoadError),
+    #[error("Error loading block {0}: {1}")]
+    Block(Uuid, BlockLoadError),
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+}
+
+impl ChromaError for InspectError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            InspectError::Storage(e) => e.code(),
+            InspectError::Root(e) => e.code(),
+            InspectError::RootDecryption(e) => e.code(),
+            InspectError::Block(_, e) => e.code(),
+            InspectError::Arrow(_) => ErrorCodes::Internal,
+        }
+    }
+}
+
+/// A block of a blockfile as recorded in the root, along with what was found in storage.
+pub struct BlockInspection {
+    pub id: Uuid,
+    /// The (prefix, key) delimiter of the block in the sparse index, `None` for the first block
+    pub delimiter: Option<(String, String)>,
+    /// The key count recorded in the root, roots before v1.1 do not record counts
+    pub recorded_count: Option<u32>,
+    pub recorded_checksum: Option<u32>,
+    /// The block as found in storage, or why it could not be read
+    pub contents: Result<BlockContents, InspectError>,
+}
+
+impl BlockInspection {
+    /// Whether the checksum of the bytes in storage matches the one recorded in the root,
+    /// `None` if no checksum is recorded or the block could not be read
+    pub fn checksum_matches(&self) -> Option<bool> {
+        let contents = self.contents.as_ref().ok()?;
+        self.recorded_checksum
+            .map(|recorded_checksum| recorded_checksum == contents.checksum)
+    }
+
+    /// A block is bad if it could not be read or does not match its recorded checksum
+    pub fn is_bad(&self) -> bool {
+        self.contents.is_err() || self.checksum_matches() == Some(false)
+    }
+}
+
+/// What was found in storage for a block that could be read.
+pub struct BlockContents {
+    /// The checksum of the bytes in storage
+    pub checksum: u32,
+    /// The size of the block in storage
+    pub stored_size_bytes: usize,
+    /// The in-memory size of the block, which is what is checked against the max block size
+    pub size_bytes: usize,
+    pub num_keys: usize,
+    pub schema: SchemaRef,
+    data: RecordBatch,
+}
+
+impl BlockContents {
+    /// Returns every (prefix, key, value) of the block formatted as strings
+    pub fn entries(&self) -> Result<Vec<(String, String, String)>, ArrowError> {
+        let options = FormatOptions::default();
+        let prefixes = ArrayFormatter::try_new(self.data.column(0).as_ref(), &options)?;
+        let keys = ArrayFormatter::try_new(self.data.column(1).as_ref(), &options)?;
+        let values = ArrayFormatter::try_new(self.data.column(2).as_ref(), &options)?;
+        Ok((0..self.data.num_rows())
+            .map(|i| {
+                (
+                    prefixes.value(i).to_string(),
+                    keys.value(i).to_string(),
+                    values.value(i).to_string(),
+                )
+            })
+            .collect())
+    }
+}
+
+/// The root of a blockfile and all of its blocks, in sparse index order.
+pub struct BlockfileInspection {
+    pub id: Uuid,
+    pub version: String,
+    pub blocks: Vec<BlockInspection>,
+}
+
+impl BlockfileInspection {
+    pub fn bad_blocks(&self) -> impl Iterator<Item = &BlockInspection> {
+        self.blocks.iter().filter(|block| block.is_bad())
+    }
+}
+
+/// Reads the root of the blockfile `id` under `prefix_path` and every block it references.
+/// The key and value types of the blockfile do not need to be known, keys and values are
+/// only ever formatted. Only an unreadable root is an error, blocks that cannot be read are
+/// recorded in their `BlockInspection` and the remaining blocks are still inspected. Blockfiles that are encrypted at rest need the key provider they
+/// were written with, plain text blockfiles are read either way.
+pub async fn inspect_blockfile(
+    storage: &Storage,
+    prefix_path: &str,
+    id: Uuid,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+) -> Result<BlockfileInspection, InspectError> {
+    let encryption = key_provider
+        .map(|key_provider| BlockEncryption::new(key_provider).with_allow_plaintext(true));
+    let root_bytes = storage
+        .get(
+            &RootManager::get_storage_key(prefix_path, &id),
+            GetOptions::new(StorageRequestPriority::P0),
+        )
+        .await?;
+    let root_bytes = decrypt_if_encrypted(encryption.as_ref(), &root_bytes, &id)
+        .await
+        .map_err(InspectError::RootDecryption)?;
+    let (version, root) = RootReader::record_batch_from_bytes(&root_bytes, id)?;
+
+    let block_ids = RootReader::block_ids_from_record_batch(&root, version)?;
+    let checksums = RootReader::block_checksums_from_record_batch(&root);
+    let prefixes = root
+        .column(0)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .expect("Prefix array to be a StringArray");
+    let options = FormatOptions::default();
+    let keys = ArrayFormatter::try_new(root.column(1).as_ref(), &options)?;
+    // Version 1.1 is the first version to have a count column
+    let counts = (version >= Version::V1_1).then(|| {
+        root.column(3)
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .expect("Count array to be a UInt32Array")
+    });
+
+    let mut blocks = Vec::with_capacity(block_ids.len());
+    for (i, block_id) in block_ids.into_iter().enumerate() {
+        let delimiter = match prefixes.value(i) {
+            "START" => None,
+            prefix => Some((prefix.to_string(), keys.value(i).to_string())),
+        };
+        blocks.push(BlockInspection {
+            id: block_id,
+            delimiter,
+            recorded_count: counts.map(|counts| counts.value(i)),
+            recorded_checksum: RootReader::checksum_at(checksums, i),
+            contents: inspect_block(storage, prefix_path, block_id, encryption.as_ref()).await,
+        });
+    }
+
+    Ok(BlockfileInspection {
+        id,
+        version: version.to_string(),
+        blocks,
+    })
+}
+
+async fn inspect_block(
+    storage: &Storage,
+    prefix_path: &str,
+    block_id: Uuid,
+    encryption: Option<&BlockEncryption>,
+) -> Result<BlockContents, InspectError> {
+    let bytes = storage
+        .get(
+            &BlockManager::format_key(prefix_path, &block_id),
+            GetOptions::new(StorageRequestPriority::P0),
+        )
+        .await?;
+    let block = match decrypt_if_encrypted(encryption, &bytes, &block_id).await {
+        Ok(plaintext) => Block::from_bytes(&plaintext, block_id),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| InspectError::Block(block_id, e))?;
+    Ok(BlockContents {
+        checksum: Block::checksum_of(&bytes),
+        stored_size_bytes: bytes.len(),
+        size_bytes: block.get_size(),
+        num_keys: block.len(),
+        schema: block.data.schema(),
+        data: block.data.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arrow::config::TEST_MAX_BLOCK_SIZE_BYTES;
+    use crate::arrow::encryption::LocalFileKeyProvider;
+    use crate::arrow::provider::ArrowBlockfileProvider;
+    use crate::BlockfileWriterOptions;
+    use chroma_cache::new_cache_for_test;
+    use chroma_storage::{test_storage, PutOptions};
+
+    #[tokio::test]
+    async fn test_inspect_blockfile() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, u32>(BlockfileWriterOptions::new("prefix".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..2000u32 {
+            let key = format!("{:04}", i);
+            writer.set("prefix", key.as_str(), i).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, u32>().await.unwrap();
+        flusher.flush::<&str, u32>().await.unwrap();
+
+        let inspection = inspect_blockfile(&storage, "prefix", id, None)
+            .await
+            .unwrap();
+        assert_eq!(inspection.id, id);
+        assert_eq!(inspection.version, "v2");
+        assert!(inspection.blocks.len() > 1);
+        assert_eq!(inspection.blocks[0].delimiter, None);
+        assert_eq!(inspection.bad_blocks().count(), 0);
+        for block in inspection.blocks.iter() {
+            let contents = block.contents.as_ref().unwrap();
+            assert_eq!(block.recorded_count, Some(contents.num_keys as u32));
+            assert_eq!(block.checksum_matches(), Some(true));
+            assert!(contents.size_bytes <= TEST_MAX_BLOCK_SIZE_BYTES);
+            assert_eq!(contents.schema.fields().len(), 3);
+        }
+        let second_block = &inspection.blocks[1];
+        let second_contents = second_block.contents.as_ref().unwrap();
+        let entries = second_contents.entries().unwrap();
+        assert_eq!(entries.len(), second_contents.num_keys);
+        assert_eq!(
+            second_block.delimiter,
+            Some((entries[0].0.clone(), entries[0].1.clone()))
+        );
+        assert_eq!(
+            inspection
+                .blocks
+                .iter()
+                .map(|block| block.contents.as_ref().unwrap().num_keys)
+                .sum::<usize>(),
+            2000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inspect_blockfile_with_bad_blocks() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, u32>(BlockfileWriterOptions::new("prefix".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..2000u32 {
+            let key = format!("{:04}", i);
+            writer.set("prefix", key.as_str(), i).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, u32>().await.unwrap();
+        flusher.flush::<&str, u32>().await.unwrap();
+
+        let inspection = inspect_blockfile(&storage, "prefix", id, None)
+            .await
+            .unwrap();
+        assert!(inspection.blocks.len() > 2);
+        let missing_block_id = inspection.blocks[0].id;
+        let corrupted_block_id = inspection.blocks[1].id;
+        storage
+            .delete(&BlockManager::format_key("prefix", &missing_block_id))
+            .await
+            .unwrap();
+        storage
+            .put_bytes(
+                &BlockManager::format_key("prefix", &corrupted_block_id),
+                b"not a block".to_vec(),
+                PutOptions::with_priority(StorageRequestPriority::P0),
+            )
+            .await
+            .unwrap();
+
+        // Every block is inspected and both bad blocks are reported
+        let inspection = inspect_blockfile(&storage, "prefix", id, None)
+            .await
+            .unwrap();
+        let bad_block_ids = inspection
+            .bad_blocks()
+            .map(|block| block.id)
+            .collect::<Vec<_>>();
+        assert_eq!(bad_block_ids, vec![missing_block_id, corrupted_block_id]);
+        assert!(matches!(
+            inspection.blocks[0].contents,
+            Err(InspectError::Storage(_))
+        ));
+        assert!(matches!(
+            inspection.blocks[1].contents,
+            Err(InspectError::Block(block_id, _)) if block_id == corrupted_block_id
+        ));
+        for block in inspection.blocks.iter().skip(2) {
+            assert_eq!(block.checksum_matches(), Some(true));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inspect_encrypted_blockfile() {
+        let (_temp_dir, storage) = test_storage();
+        let key_dir = tempfile::tempdir().unwrap();
+        std::fs::write(key_dir.path().join("key1"), [7u8; 32]).unwrap();
+        let key_provider: Arc<dyn KeyProvider> =
+            Arc::new(LocalFileKeyProvider::new(key_dir.path(), "key1"));
+        let provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_encryption(key_provider.clone(), false);
+        let writer = provider
+            .write::<&str, u32>(BlockfileWriterOptions::new("prefix".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..2000u32 {
+            let key = format!("{:04}", i);
+            writer.set("prefix", key.as_str(), i).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, u32>().await.unwrap();
+        flusher.flush::<&str, u32>().await.unwrap();
+
+        assert!(matches!(
+            inspect_blockfile(&storage, "prefix", id, None).await,
+            Err(InspectError::RootDecryption(BlockLoadError::Encrypted))
+        ));
+        let inspection = inspect_blockfile(&storage, "prefix", id, Some(key_provider))
+            .await
+            .unwrap();
+        assert!(inspection.blocks.len() > 1);
+        for block in inspection.blocks.iter() {
+            let contents = block.contents.as_ref().unwrap();
+            assert_eq!(block.recorded_count, Some(contents.num_keys as u32));
+            assert_eq!(block.checksum_matches(), Some(true));
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use super::ordered_blockfile_writer::ArrowOrderedBlockfileWriter;
+use super::types::{ArrowReadableKey, ArrowWriteableKey, ArrowWriteableValue};
+use crate::key::{InvalidKeyConversion, KeyWrapper};
+use crate::memory::storage::Readable;
+use crate::{BlockfileReader, Key, Value};
+use chroma_error::{ChromaError, ErrorCodes};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use std::cmp::Ordering;
+use std::pin::Pin;
+use thiserror::Error;
+
+/// How to resolve a (prefix, key) pair that is present in more than one of the merged blockfiles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MergeDuplicatePolicy {
+    /// Keep the value from the blockfile that comes last in the list of readers
+    #[default]
+    LastWins,
+    /// Fail the merge
+    Error,
+}
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("Key {key} with prefix {prefix} is present in more than one blockfile")]
+    DuplicateKey { prefix: String, key: String },
+    #[error("Error reading blockfile: {0}")]
+    Read(Box<dyn ChromaError>),
+    #[error("Error writing merged blockfile: {0}")]
+    Write(Box<dyn ChromaError>),
+}
+
+impl ChromaError for MergeError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            MergeError::DuplicateKey { .. } => ErrorCodes::AlreadyExists,
+            MergeError::Read(e) => e.code(),
+            MergeError::Write(e) => e.code(),
+        }
+    }
+}
+
+type RangeStream<'me, K, V> =
+    Pin<Box<dyn Stream<Item = Result<(&'me str, K, V), Box<dyn ChromaError>>> + Send + 'me>>;
+
+fn compare_entries<K: PartialOrd>(a: (&str, &K), b: (&str, &K)) -> Ordering {
+    a.0.cmp(b.0).then_with(|| {
+        a.1.partial_cmp(b.1)
+            // NaN is not allowed in keys, so keys always have a total order
+            .expect("Keys should be comparable.")
+    })
+}
+
+/// Streams every record of `readers` into `writer` in ascending (prefix, key) order.
+/// With `MergeDuplicatePolicy::Error`, the readers are scanned for duplicates before anything is
+/// written, so a duplicate leaves `writer` untouched. Any other error may leave part of the
+/// merged records in `writer`, which must then be discarded rather than committed.
+pub(super) async fn merge_into<'readers, K, V>(
+    readers: &'readers [BlockfileReader<'readers, K, V::ReadableValue<'readers>>],
+    duplicate_policy: MergeDuplicatePolicy,
+    writer: &ArrowOrderedBlockfileWriter,
+) -> Result<(), MergeError>
+where
+    K: Key
+        + Into<KeyWrapper>
+        + TryFrom<&'readers KeyWrapper, Error = InvalidKeyConversion>
+        + ArrowReadableKey<'readers>
+        + ArrowWriteableKey
+        + Sync
+        + Send
+        + 'readers,
+    V: ArrowWriteableValue + From<V::ReadableValue<'readers>>,
+    V::ReadableValue<'readers>: Value + Readable<'readers> + Sync + Send + 'readers,
+{
+    if duplicate_policy == MergeDuplicatePolicy::Error {
+        merge_pass::<K, V>(readers, MergeDuplicatePolicy::Error, None).await?;
+    }
+    // Any duplicate left is resolved the same way by both policies
+    merge_pass::<K, V>(readers, MergeDuplicatePolicy::LastWins, Some(writer)).await
+}
+
+/// Merges `readers` in ascending (prefix, key) order, writing the records to `writer` if set.
+/// The readers are merged with a linear scan over the current head of each reader,
+/// which is cheap for the handful of blockfiles that are merged in practice.
+async fn merge_pass<'readers, K, V>(
+    readers: &'readers [BlockfileReader<'readers, K, V::ReadableValue<'readers>>],
+    duplicate_policy: MergeDuplicatePolicy,
+    writer: Option<&ArrowOrderedBlockfileWriter>,
+) -> Result<(), MergeError>
+where
+    K: Key
+        + Into<KeyWrapper>
+        + TryFrom<&'readers KeyWrapper, Error = InvalidKeyConversion>
+        + ArrowReadableKey<'readers>
+        + ArrowWriteableKey
+        + Sync
+        + Send
+        + 'readers,
+    V: ArrowWriteableValue + From<V::ReadableValue<'readers>>,
+    V::ReadableValue<'readers>: Value + Readable<'readers> + Sync + Send + 'readers,
+{
+    let mut streams: Vec<RangeStream<'readers, K, V::ReadableValue<'readers>>> = readers
+        .iter()
+        .map(|reader| match reader {
+            // A range read of an empty memory blockfile fails instead of yielding nothing
+            BlockfileReader::MemoryBlockfileReader(reader) => match reader.get_range_iter(.., ..) {
+                Ok(records) => stream::iter(records.map(Ok)).boxed(),
+                Err(_) => stream::empty().boxed(),
+            },
+            BlockfileReader::ArrowBlockfileReader(_) => reader.get_range_stream(.., ..).boxed(),
+        })
+        .collect();
+    let mut heads = Vec::with_capacity(streams.len());
+    for stream in streams.iter_mut() {
+        heads.push(stream.try_next().await.map_err(MergeError::Read)?);
+    }
+
+    loop {
+        // Find the smallest head, and every reader that has that same (prefix, key)
+        let mut duplicates: Vec<usize> = Vec::new();
+        for (index, head) in heads.iter().enumerate() {
+            let Some((prefix, key, _)) = head else {
+                continue;
+            };
+            let ordering = match duplicates.first() {
+                Some(&min_index) => {
+                    let (min_prefix, min_key, _) = heads[min_index]
+                        .as_ref()
+                        .expect("Only populated heads are tracked");
+                    compare_entries((*prefix, key), (*min_prefix, min_key))
+                }
+                None => Ordering::Less,
+            };
+            match ordering {
+                Ordering::Less => {
+                    duplicates.clear();
+                    duplicates.push(index);
+                }
+                Ordering::Equal => duplicates.push(index),
+                Ordering::Greater => {}
+            }
+        }
+
+        let winner = match duplicates.last() {
+            Some(&winner) => winner,
+            None => break,
+        };
+        if duplicates.len() > 1 && duplicate_policy == MergeDuplicatePolicy::Error {
+            let (prefix, key, _) = heads[winner]
+                .as_ref()
+                .expect("Only populated heads are tracked");
+            return Err(MergeError::DuplicateKey {
+                prefix: prefix.to_string(),
+                key: key.to_string(),
+            });
+        }
+
+        let (prefix, key, value) = heads[winner]
+            .take()
+            .expect("Only populated heads are tracked");
+        for index in duplicates {
+            heads[index] = streams[index].try_next().await.map_err(MergeError::Read)?;
+        }
+        if let Some(writer) = writer {
+            writer
+                .set::<K, V>(prefix, key, V::from(value))
+                .await
+                .map_err(MergeError::Write)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram, Meter},
+    KeyValue,
+};
+use std::time::Duration;
+
+/// What a block or root is read or written for, recorded as the `operation` label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Operation {
+    /// Read by a blockfile reader
+    Get,
+    /// Read to be forked by a blockfile writer
+    Fork,
+    /// Read to warm the cache
+    Prefetch,
+    /// Written when a blockfile is flushed
+    Flush,
+}
+
+impl Operation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Get => "get",
+            Operation::Fork => "fork",
+            Operation::Prefetch => "prefetch",
+            Operation::Flush => "flush",
+        }
+    }
+}
+
+/// The OpenTelemetry metrics of the block manager, the root manager and the flusher.
+/// Every metric is labeled with the operation and the storage request priority. By default the
+/// instruments come from the global meter provider, so they do nothing unless the process installs one.
+#[derive(Clone)]
+pub(super) struct BlockstoreMetrics {
+    block_cache_hit: Counter<u64>,
+    block_cache_miss: Counter<u64>,
+    root_cache_hit: Counter<u64>,
+    root_cache_miss: Counter<u64>,
+    block_bytes_fetched: Counter<u64>,
+    root_bytes_fetched: Counter<u64>,
+    block_decryption_latency: Histogram<u64>,
+    block_deserialization_latency: Histogram<u64>,
+    block_bytes_written: Counter<u64>,
+    root_bytes_written: Counter<u64>,
+    blocks_per_flush: Histogram<u64>,
+}
+
+impl Default for BlockstoreMetrics {
+    fn default() -> Self {
+        Self::new(&global::meter("chroma"))
+    }
+}
+
+impl BlockstoreMetrics {
+    pub(super) fn new(meter: &Meter) -> Self {
+        Self {
+            block_cache_hit: meter.u64_counter("blockstore_block_cache_hit").build(),
+            block_cache_miss: meter.u64_counter("blockstore_block_cache_miss").build(),
+            root_cache_hit: meter.u64_counter("blockstore_root_cache_hit").build(),
+            root_cache_miss: meter.u64_counter("blockstore_root_cache_miss").build(),
+            block_bytes_fetched: meter
+                .u64_counter("blockstore_block_bytes_fetched")
+                .with_unit("By")
+                .build(),
+            root_bytes_fetched: meter
+                .u64_counter("blockstore_root_bytes_fetched")
+                .with_unit("By")
+                .build(),
+            block_decryption_latency: meter
+                .u64_histogram("blockstore_block_decryption_latency")
+                .with_unit("us")
+                .build(),
+            block_deserialization_latency: meter
+                .u64_histogram("blockstore_block_deserialization_latency")
+                .with_unit("us")
+                .build(),
+            block_bytes_written: meter
+                .u64_counter("blockstore_block_bytes_written")
+                .with_unit("By")
+                .build(),
+            root_bytes_written: meter
+                .u64_counter("blockstore_root_bytes_written")
+                .with_unit("By")
+                .build(),
+            blocks_per_flush: meter.u64_histogram("blockstore_blocks_per_flush").build(),
+        }
+    }
+
+    fn labels(operation: Operation, priority: StorageRequestPriority) -> [KeyValue; 2] {
+        [
+            KeyValue::new("operation", operation.as_str()),
+            KeyValue::new("priority", format!("{:?}", priority)),
+        ]
+    }
+
+    pub(super) fn block_cache_lookup(
+        &self,
+        hit: bool,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        let counter = match hit {
+            true => &self.block_cache_hit,
+            false => &self.block_cache_miss,
+        };
+        counter.add(1, &Self::labels(operation, priority));
+    }
+
+    pub(super) fn root_cache_lookup(
+        &self,
+        hit: bool,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        let counter = match hit {
+            true => &self.root_cache_hit,
+            false => &self.root_cache_miss,
+        };
+        counter.add(1, &Self::labels(operation, priority));
+    }
+
+    pub(super) fn block_fetched(
+        &self,
+        len: usize,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        self.block_bytes_fetched
+            .add(len as u64, &Self::labels(operation, priority));
+    }
+
+    /// Record the time taken to decrypt a fetched block, only called for encrypted blocks
+    pub(super) fn block_decrypted(
+        &self,
+        latency: Duration,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        self.block_decryption_latency.record(
+            latency.as_micros() as u64,
+            &Self::labels(operation, priority),
+        );
+    }
+
+    pub(super) fn block_deserialized(
+        &self,
+        latency: Duration,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        self.block_deserialization_latency.record(
+            latency.as_micros() as u64,
+            &Self::labels(operation, priority),
+        );
+    }
+
+    pub(super) fn root_fetched(
+        &self,
+        len: usize,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) {
+        self.root_bytes_fetched
+            .add(len as u64, &Self::labels(operation, priority));
+    }
+
+    pub(super) fn block_written(&self, len: usize, priority: StorageRequestPriority) {
+        self.block_bytes_written
+            .add(len as u64, &Self::labels(Operation::Flush, priority));
+    }
+
+    pub(super) fn root_written(&self, len: usize, priority: StorageRequestPriority) {
+        self.root_bytes_written
+            .add(len as u64, &Self::labels(Operation::Flush, priority));
+    }
+
+    /// Record the number of blocks written to storage by one flush
+    pub(super) fn blocks_flushed(&self, blocks: usize, priority: StorageRequestPriority) {
+        self.blocks_per_flush
+            .record(blocks as u64, &Self::labels(Operation::Flush, priority));
+    }
+}
@@ -51,8 +51,14 @@ async fn migrate_v1_to_v1_1(
                 .collect::<Vec<Uuid>>();
         }
         for block_id in block_ids.iter() {
+            // V1 roots predate block checksums, so there is nothing to verify against
             let block = block_manager
-                .get(&root.prefix_path, block_id, StorageRequestPriority::P0)
+                .get(
+                    &root.prefix_path,
+                    block_id,
+                    None,
+                    StorageRequestPriority::P0,
+                )
                 .await;
             match block {
                 Ok(Some(block)) => {
@@ -76,10 +82,22 @@ async fn migrate_v1_to_v1_1(
     Ok(())
 }
 
+fn migrate_v1_1_to_v2(root: &mut RootWriter) {
+    // Blocks that were not written by this writer are left without statistics instead of being
+    // fetched, which would read every block of the blockfile on the first write after the
+    // upgrade. They get statistics when they are rewritten, until then they are counted in
+    // `BlockfileStats::blocks_without_stats`.
+    if root.version == Version::V1_1 {
+        root.version = Version::V2;
+    }
+}
+
 pub async fn apply_migrations_to_blockfile(
     root: &mut RootWriter,
     block_manager: &BlockManager,
     new_block_ids: &HashSet<Uuid>,
 ) -> Result<(), MigrationError> {
-    migrate_v1_to_v1_1(root, block_manager, new_block_ids).await
+    migrate_v1_to_v1_1(root, block_manager, new_block_ids).await?;
+    migrate_v1_1_to_v2(root);
+    Ok(())
 }
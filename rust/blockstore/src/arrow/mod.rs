@@ -1,12 +1,23 @@
 pub(crate) mod block;
 pub(crate) mod blockfile;
+pub(crate) mod bloom_filter;
 #[cfg(test)]
 mod concurrency_test;
 pub mod config;
+pub mod diff;
+pub mod encryption;
 pub(crate) mod flusher;
+pub mod fsck;
+pub mod inspect;
+pub mod merge;
+mod metrics;
 mod migrations;
 pub(crate) mod ordered_blockfile_writer;
+pub mod orphans;
+pub mod parquet;
+pub mod prefetch;
 pub mod provider;
 pub mod root;
 pub(crate) mod sparse_index;
+mod spill;
 pub mod types;
@@ -8,16 +8,20 @@ use super::root::RootWriter;
 use super::sparse_index::SparseIndexDelimiter;
 use super::{
     flusher::ArrowBlockfileFlusher,
-    types::{ArrowWriteableKey, ArrowWriteableValue},
+    types::{ArrowWriteableKey, ArrowWriteableValue, ValueDimension},
 };
 use crate::arrow::root::CURRENT_VERSION;
 use crate::arrow::sparse_index::SparseIndexWriter;
 use crate::key::CompositeKey;
+use crate::key::CompositeKeyRange;
+use crate::types::MutationOrderChecker;
 use chroma_error::ChromaError;
 use chroma_error::ErrorCodes;
 use itertools::Itertools;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::ops::RangeBounds;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::Mutex;
@@ -37,6 +41,8 @@ struct Inner {
     current_block_delta: Option<CurrentDeltaAndEndKey>,
     /// Deltas in this vec can no longer receive writes and are ready to be committed.
     completed_block_deltas: Vec<OrderedBlockDelta>,
+    /// Rejects keys that are provided out of order or mutated more than once.
+    order_checker: MutationOrderChecker,
 }
 
 #[derive(Clone)]
@@ -46,6 +52,7 @@ pub struct ArrowOrderedBlockfileWriter {
     root: RootWriter,
     inner: Arc<Mutex<Inner>>,
     id: Uuid,
+    value_dimension: ValueDimension,
 }
 
 #[derive(Error, Debug)]
@@ -83,7 +90,9 @@ impl ArrowOrderedBlockfileWriter {
                 current_block_delta: Some((initial_block, None)),
                 completed_block_deltas: Vec::new(),
                 remaining_block_stack: VecDeque::new(),
+                order_checker: MutationOrderChecker::default(),
             })),
+            value_dimension: ValueDimension::default(),
         }
     }
 
@@ -124,7 +133,9 @@ impl ArrowOrderedBlockfileWriter {
                 current_block_delta: None,
                 completed_block_deltas: Vec::new(),
                 remaining_block_stack,
+                order_checker: MutationOrderChecker::default(),
             })),
+            value_dimension: ValueDimension::default(),
         }
     }
 
@@ -170,6 +181,11 @@ impl ArrowOrderedBlockfileWriter {
             split_block_deltas.push(delta);
         }
 
+        if let Some(min_block_size_bytes) = self.block_manager.min_block_size_bytes() {
+            split_block_deltas =
+                self.merge_underfilled_deltas::<K, V>(split_block_deltas, min_block_size_bytes);
+        }
+
         let mut blocks = Vec::new();
         let mut new_block_ids = HashSet::new();
         for delta in split_block_deltas.drain(..) {
@@ -218,6 +234,54 @@ impl ArrowOrderedBlockfileWriter {
         Ok(flusher)
     }
 
+    /// Merge adjacent deltas of which one is underfilled, see
+    /// `SparseIndexWriter::get_underfilled_block_groups()`. The deltas must be complete.
+    /// Blocks that were not rewritten by this writer are left as is.
+    fn merge_underfilled_deltas<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        deltas: Vec<OrderedBlockDelta>,
+        min_block_size_bytes: usize,
+    ) -> Vec<OrderedBlockDelta> {
+        let block_sizes = deltas
+            .iter()
+            .map(|delta| (delta.id(), delta.get_size::<K, V>()))
+            .collect::<HashMap<_, _>>();
+        let groups = self.root.sparse_index.get_underfilled_block_groups(
+            &block_sizes,
+            min_block_size_bytes,
+            self.block_manager.max_block_size_bytes(),
+        );
+        if groups.is_empty() {
+            return deltas;
+        }
+
+        let mut deltas = deltas
+            .into_iter()
+            .map(|delta| (delta.id(), delta))
+            .collect::<HashMap<_, _>>();
+        for group in groups {
+            let mut group_deltas = group.iter().map(|block_id| {
+                deltas
+                    .remove(block_id)
+                    .expect("Groups only hold blocks with a delta")
+            });
+            let mut merged_delta = group_deltas
+                .next()
+                .expect("Groups hold more than one block");
+            for delta in group_deltas {
+                self.root.sparse_index.remove_block(&delta.id());
+                merged_delta.merge::<K, V>(delta);
+            }
+            tracing::debug!(
+                "Merged {} underfilled blocks into delta {:?}",
+                group.len(),
+                merged_delta.id()
+            );
+            deltas.insert(merged_delta.id(), merged_delta);
+        }
+        deltas.into_values().collect()
+    }
+
     fn complete_current_delta<K: ArrowWriteableKey, V: ArrowWriteableValue>(inner: &mut Inner) {
         if let Some((mut delta, _)) = inner.current_block_delta.take() {
             delta.copy_to_end::<K, V>();
@@ -235,7 +299,11 @@ impl ArrowOrderedBlockfileWriter {
 
         let new_delta = self
             .block_manager
-            .fork::<K, V, OrderedBlockDelta>(new_delta_block_id, &self.root.prefix_path)
+            .fork::<K, V, OrderedBlockDelta>(
+                new_delta_block_id,
+                &self.root.prefix_path,
+                self.root.sparse_index.get_checksum(new_delta_block_id),
+            )
             .await
             .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
 
@@ -248,25 +316,49 @@ impl ArrowOrderedBlockfileWriter {
         Ok(())
     }
 
+    /// Make the delta that covers the key the current delta. `mutation` is false for reads, which
+    /// may repeat the last mutated key. Returns an error if the key is out of order.
     async fn advance_current_delta_and_get_inner<K: ArrowWriteableKey, V: ArrowWriteableValue>(
         &self,
         prefix: &str,
         key: &K,
+        mutation: bool,
     ) -> Result<MutexGuard<'_, Inner>, Box<dyn ChromaError>> {
         let mut inner = self.inner.lock().await;
 
+        let wrapped_key = key.clone().into();
+        if mutation {
+            inner.order_checker.check_mutation(prefix, &wrapped_key)
+        } else {
+            inner.order_checker.check_read(prefix, &wrapped_key)
+        }
+        .map_err(|e| e.boxed())?;
+
+        self.advance_current_delta::<K, V>(&mut inner, |end_key| {
+            prefix < end_key.prefix.as_str()
+                || (prefix == end_key.prefix.as_str() && key.clone().into() < end_key.key)
+            // todo: avoid cloning key
+        })
+        .await?;
+
+        Ok(inner)
+    }
+
+    /// Make the first delta whose end key satisfies `is_before_end_key` the current delta.
+    async fn advance_current_delta<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        inner: &mut Inner,
+        is_before_end_key: impl Fn(&CompositeKey) -> bool,
+    ) -> Result<(), Box<dyn ChromaError>> {
         if let Some((_, end_key)) = inner.current_block_delta.as_ref() {
             if let Some(end_key) = end_key {
-                if prefix < end_key.prefix.as_str()
-                    || (prefix == end_key.prefix.as_str() && key.clone().into() < end_key.key)
-                // todo: avoid cloning key
-                {
-                    // Provided prefix/key pair is less than the current delta's end key, so there's nothing to do
-                    return Ok(inner);
+                if is_before_end_key(end_key) {
+                    // Provided position is less than the current delta's end key, so there's nothing to do
+                    return Ok(());
                 }
             } else {
                 // Open-ended delta
-                return Ok(inner);
+                return Ok(());
             }
         }
 
@@ -274,17 +366,14 @@ impl ArrowOrderedBlockfileWriter {
         loop {
             match inner.remaining_block_stack.pop_front() {
                 Some((block_id, Some(end_key))) => {
-                    if prefix < end_key.prefix.as_str()
-                        || (prefix == end_key.prefix.as_str() && key.clone().into() < end_key.key)
-                    // todo: avoid cloning key
-                    {
-                        self.swap_current_delta::<K, V>(&mut inner, &block_id, Some(end_key))
+                    if is_before_end_key(&end_key) {
+                        self.swap_current_delta::<K, V>(inner, &block_id, Some(end_key))
                             .await?;
                         break;
                     }
                 }
                 Some((block_id, None)) => {
-                    self.swap_current_delta::<K, V>(&mut inner, &block_id, None)
+                    self.swap_current_delta::<K, V>(inner, &block_id, None)
                         .await?;
                     break;
                 }
@@ -294,7 +383,7 @@ impl ArrowOrderedBlockfileWriter {
             }
         }
 
-        Ok(inner)
+        Ok(())
     }
 
     pub(crate) async fn set<K: ArrowWriteableKey, V: ArrowWriteableValue>(
@@ -304,10 +393,11 @@ impl ArrowOrderedBlockfileWriter {
         value: V,
     ) -> Result<(), Box<dyn ChromaError>> {
         let inner = &mut self
-            .advance_current_delta_and_get_inner::<K, V>(prefix, &key)
+            .advance_current_delta_and_get_inner::<K, V>(prefix, &key, true)
             .await?;
         let current_materialized_delta_size = {
             let delta = &mut inner.current_block_delta.as_mut().expect("Invariant violation: advance_current_delta_and_get_inner() did not populate current delta").0;
+            delta.check(&value, &self.value_dimension)?;
             delta.add(prefix, key, value);
             delta.get_size::<K, V>()
         };
@@ -343,13 +433,80 @@ impl ArrowOrderedBlockfileWriter {
         key: K,
     ) -> Result<(), Box<dyn ChromaError>> {
         let inner = &mut self
-            .advance_current_delta_and_get_inner::<K, V>(prefix, &key)
+            .advance_current_delta_and_get_inner::<K, V>(prefix, &key, true)
             .await?;
         let delta = &mut inner.current_block_delta.as_mut().expect("Invariant violation: advance_current_delta_and_get_inner() did not populate current delta").0;
         delta.skip::<K, V>(prefix, key);
         Ok(())
     }
 
+    /// Deletes every key of the prefix in the key range, which must not start before the last key
+    /// provided to this writer. Blocks that only hold keys in the range are dropped from the sparse
+    /// index without being fetched.
+    pub(crate) async fn delete_range<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let range = CompositeKeyRange::new(prefix, key_range);
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock().await;
+        inner
+            .order_checker
+            .check_range_mutation(&range)
+            .map_err(|e| e.boxed())?;
+
+        self.advance_current_delta::<K, V>(&mut inner, |end_key| range.starts_before(end_key))
+            .await?;
+
+        loop {
+            let (delta, end_key) = inner.current_block_delta.as_mut().expect(
+                "Invariant violation: advance_current_delta() did not populate current delta",
+            );
+            delta.skip_range::<K, V>(&range);
+            // The range may continue into the blocks after the current delta.
+            match end_key {
+                Some(end_key) if range.is_before_end(end_key) => {}
+                _ => break,
+            }
+
+            loop {
+                match inner.remaining_block_stack.pop_front() {
+                    Some((block_id, Some(next_end_key)))
+                        if range.covers_up_to(&next_end_key)
+                            && self.root.sparse_index.remove_block(&block_id) => {}
+                    Some((block_id, next_end_key)) => {
+                        self.swap_current_delta::<K, V>(&mut inner, &block_id, next_end_key)
+                            .await?;
+                        break;
+                    }
+                    None => {
+                        panic!("Invariant violated: no blocks left in the stack.")
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the value of a key as seen by this writer. Reads follow the same ordering rules as
+    /// mutations, except that the last mutated key can be read back.
+    pub(crate) async fn get_owned<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        prefix: &str,
+        key: K,
+    ) -> Result<Option<V::PreparedValue>, Box<dyn ChromaError>> {
+        let inner = &mut self
+            .advance_current_delta_and_get_inner::<K, V>(prefix, &key, false)
+            .await?;
+        let delta = &mut inner.current_block_delta.as_mut().expect("Invariant violation: advance_current_delta_and_get_inner() did not populate current delta").0;
+        Ok(delta.get_owned::<K, V>(prefix, key))
+    }
+
     pub(crate) fn id(&self) -> Uuid {
         self.id
     }
@@ -367,13 +524,18 @@ mod tests {
     use crate::arrow::provider::{BlockManager, BlockfileReaderOptions, RootManager};
     use crate::arrow::root::{RootWriter, Version};
     use crate::arrow::sparse_index::SparseIndexWriter;
+    use crate::arrow::types::ValueDimension;
     use crate::key::CompositeKey;
+    use crate::types::MutationOrderChecker;
     use crate::{
         arrow::config::TEST_MAX_BLOCK_SIZE_BYTES, arrow::provider::ArrowBlockfileProvider,
     };
     use crate::{BlockfileReader, BlockfileWriter, BlockfileWriterOptions};
     use chroma_cache::new_cache_for_test;
-    use chroma_storage::{local::LocalStorage, Storage};
+    use chroma_error::ErrorCodes;
+    use chroma_storage::{
+        admissioncontrolleds3::StorageRequestPriority, local::LocalStorage, Storage,
+    };
     use rand::seq::IteratorRandom;
     use tokio::sync::Mutex;
     use uuid::Uuid;
@@ -939,7 +1101,9 @@ mod tests {
                 remaining_block_stack: VecDeque::new(),
                 current_block_delta: Some((initial_block, None)),
                 completed_block_deltas: Vec::new(),
+                order_checker: MutationOrderChecker::default(),
             })),
+            value_dimension: ValueDimension::default(),
         };
 
         let n = 2000;
@@ -1023,14 +1187,17 @@ mod tests {
         let old_block_2_record_batch = old_block_delta_2.finish::<&str, String>(None);
         let old_block_2 = Block::from_record_batch(old_block_id_2, old_block_2_record_batch);
         block_manager
-            .flush(&old_block_1, prefix_path)
+            .flush(&old_block_1, prefix_path, StorageRequestPriority::P0)
             .await
             .unwrap();
         block_manager
-            .flush(&old_block_2, prefix_path)
+            .flush(&old_block_2, prefix_path, StorageRequestPriority::P0)
+            .await
+            .unwrap();
+        root_manager
+            .flush::<&str>(&old_root_writer, StorageRequestPriority::P0)
             .await
             .unwrap();
-        root_manager.flush::<&str>(&old_root_writer).await.unwrap();
 
         // We now have a v1 blockfile with 2 blocks and no counts in the root
 
@@ -1118,7 +1285,7 @@ mod tests {
             _ => panic!("Unexpected reader type"),
         };
 
-        assert_eq!(reader.root.version, Version::V1_1);
+        assert_eq!(reader.root.version, Version::V2);
         assert_eq!(reader.root.sparse_index.len(), 2);
 
         // Manually verify sparse index counts
@@ -1133,4 +1300,310 @@ mod tests {
         assert_eq!(count_in_index, 3);
         assert_eq!(reader.count().await.unwrap(), 3);
     }
+
+    #[tokio::test]
+    async fn test_get_owned() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = String::from("");
+        let writer = blockfile_provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new(prefix_path.clone()).ordered_mutations(),
+            )
+            .await
+            .unwrap();
+        let id = writer.id();
+        let n = 2000;
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            writer
+                .set("key", key.as_str(), format!("value_{}", i))
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let writer = blockfile_provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new(prefix_path.clone())
+                    .fork(id)
+                    .ordered_mutations(),
+            )
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            match i % 3 {
+                // Values pending in the forked blocks
+                0 => {
+                    let value = writer
+                        .get_owned::<&str, String>("key", key.as_str())
+                        .await
+                        .unwrap();
+                    assert_eq!(value, Some(format!("value_{}", i)));
+                }
+                // Values set by this writer
+                1 => {
+                    writer
+                        .set("key", key.as_str(), format!("new_value_{}", i))
+                        .await
+                        .unwrap();
+                    let value = writer
+                        .get_owned::<&str, String>("key", key.as_str())
+                        .await
+                        .unwrap();
+                    assert_eq!(value, Some(format!("new_value_{}", i)));
+                }
+                // Values deleted by this writer
+                _ => {
+                    writer
+                        .delete::<&str, String>("key", key.as_str())
+                        .await
+                        .unwrap();
+                    let value = writer
+                        .get_owned::<&str, String>("key", key.as_str())
+                        .await
+                        .unwrap();
+                    assert_eq!(value, None);
+                }
+            }
+        }
+        let value = writer
+            .get_owned::<&str, String>("key", "9999")
+            .await
+            .unwrap();
+        assert_eq!(value, None);
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, prefix_path))
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            let expected = match i % 3 {
+                0 => Some(format!("value_{}", i)),
+                1 => Some(format!("new_value_{}", i)),
+                _ => None,
+            };
+            assert_eq!(reader.get("key", &key).await.unwrap(), expected.as_deref());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_mutations() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).ordered_mutations())
+            .await
+            .unwrap();
+
+        writer.set("key", "b", "value".to_string()).await.unwrap();
+        // The last mutated key can be read back but not mutated again
+        assert_eq!(
+            writer.get_owned::<&str, String>("key", "b").await.unwrap(),
+            Some("value".to_string())
+        );
+        let err = writer
+            .set("key", "b", "value".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCodes::InvalidArgument);
+        assert!(writer.delete::<&str, String>("key", "b").await.is_err());
+        // Earlier keys are rejected for both mutations and reads
+        assert!(writer.set("key", "a", "value".to_string()).await.is_err());
+        assert!(writer.get_owned::<&str, String>("key", "a").await.is_err());
+        assert!(writer.set("a", "z", "value".to_string()).await.is_err());
+        // A read advances the writer as well
+        assert_eq!(
+            writer.get_owned::<&str, String>("key", "d").await.unwrap(),
+            None
+        );
+        assert!(writer.set("key", "c", "value".to_string()).await.is_err());
+        writer.set("key", "d", "value".to_string()).await.unwrap();
+        writer.set("other", "a", "value".to_string()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_range() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).ordered_mutations())
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        let n = 1000;
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                writer
+                    .set(prefix, key.as_str(), format!("{}{}", prefix, key))
+                    .await
+                    .unwrap();
+            }
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let writer = blockfile_provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new(String::new())
+                    .ordered_mutations()
+                    .fork(id),
+            )
+            .await
+            .unwrap();
+        let id = writer.id();
+
+        writer
+            .set("a", "0000", "new value".to_string())
+            .await
+            .unwrap();
+        writer
+            .delete_range::<&str, String>("a", "0500"..)
+            .await
+            .unwrap();
+        writer.delete_prefix::<&str, String>("b").await.unwrap();
+        // Keys of a deleted range cannot be mutated afterwards
+        assert!(writer.set("b", "0000", "value".to_string()).await.is_err());
+        writer
+            .set("c", "0001", "new value".to_string())
+            .await
+            .unwrap();
+        assert!(writer
+            .delete_range::<&str, String>("c", ..="0099")
+            .await
+            .is_err());
+        assert!(writer
+            .delete_range::<&str, String>("c", "0001"..="0009")
+            .await
+            .is_err());
+        writer
+            .delete_range::<&str, String>("c", "0900".."0950")
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = blockfile_provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        assert_eq!(reader.count().await.unwrap(), 500 + n - 50);
+        for prefix in ["a", "b", "c"] {
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                let value = reader.get(prefix, &key).await.unwrap();
+                match (prefix, i) {
+                    ("a", 500..) | ("b", _) | ("c", 900..950) => assert!(value.is_none()),
+                    ("a", 0) | ("c", 1) => assert_eq!(value, Some("new value")),
+                    _ => assert_eq!(value.unwrap(), format!("{}{}", prefix, key)),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_underfilled_blocks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(tmp_dir.path().to_str().unwrap()));
+        let blockfile_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let merging_blockfile_provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_min_block_fill_factor(0.5);
+
+        let n = 3000;
+        let writer = blockfile_provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).ordered_mutations())
+            .await
+            .unwrap();
+        for i in 0..n {
+            let key = format!("{:04}", i);
+            writer
+                .set("key", key.as_str(), format!("value{}", i))
+                .await
+                .unwrap();
+        }
+        let id = writer.id();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Delete most keys of every block, with and without merging underfilled blocks
+        let mut block_counts = Vec::new();
+        for provider in [&blockfile_provider, &merging_blockfile_provider] {
+            let writer = provider
+                .write::<&str, String>(
+                    BlockfileWriterOptions::new(String::new())
+                        .ordered_mutations()
+                        .fork(id),
+                )
+                .await
+                .unwrap();
+            for i in (0..n).filter(|i| i % 10 != 0) {
+                let key = format!("{:04}", i);
+                writer
+                    .delete::<&str, String>("key", key.as_str())
+                    .await
+                    .unwrap();
+            }
+            let forked_id = writer.id();
+            let flusher = writer.commit::<&str, String>().await.unwrap();
+            flusher.flush::<&str, String>().await.unwrap();
+
+            let reader = match provider
+                .read::<&str, &str>(BlockfileReaderOptions::new(forked_id, String::new()))
+                .await
+                .unwrap()
+            {
+                BlockfileReader::ArrowBlockfileReader(reader) => reader,
+                _ => panic!("Unexpected reader type"),
+            };
+            assert_eq!(reader.count().await.unwrap(), n / 10);
+            for i in 0..n {
+                let key = format!("{:04}", i);
+                let value = reader.get("key", key.as_str()).await.unwrap();
+                if i % 10 == 0 {
+                    assert_eq!(value, Some(format!("value{}", i).as_str()));
+                } else {
+                    assert_eq!(value, None);
+                }
+            }
+            block_counts.push(reader.root.sparse_index.len());
+        }
+        assert!(block_counts[0] > 2);
+        assert!(block_counts[1] < block_counts[0]);
+    }
 }
@@ -0,0 +1,66 @@
+use super::provider::RootManagerError;
+use chroma_error::{ChromaError, ErrorCodes};
+use chroma_storage::StorageError;
+use std::time::SystemTime;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum OrphanError {
+    #[error("Error listing {0}: {1}")]
+    List(String, StorageError),
+    #[error("Error reading live root {0}: {1}")]
+    LiveRoot(Uuid, RootManagerError),
+    #[error("Error deleting {0}: {1}")]
+    Delete(String, StorageError),
+    #[error("The grace period has not elapsed since the orphans were found")]
+    GracePeriodNotElapsed,
+    #[error("Orphans can only be found under a non-empty prefix path")]
+    EmptyPrefixPath,
+}
+
+impl ChromaError for OrphanError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            OrphanError::List(_, e) => e.code(),
+            OrphanError::LiveRoot(_, e) => e.code(),
+            OrphanError::Delete(_, e) => e.code(),
+            OrphanError::GracePeriodNotElapsed => ErrorCodes::FailedPrecondition,
+            OrphanError::EmptyPrefixPath => ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+/// The block and root objects under a prefix path that are not reachable from any live root.
+#[derive(Debug, Clone)]
+pub struct OrphanScan {
+    pub prefix_path: String,
+    /// When storage was listed
+    pub scanned_at: SystemTime,
+    pub orphaned_block_ids: Vec<Uuid>,
+    pub orphaned_root_ids: Vec<Uuid>,
+}
+
+impl OrphanScan {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned_block_ids.is_empty() && self.orphaned_root_ids.is_empty()
+    }
+}
+
+/// Returns the ids of the objects in `keys` that are directly under `prefix`, skipping
+/// anything that is not named by a uuid.
+pub(super) fn ids_under_prefix(prefix: &str, keys: Vec<String>) -> Vec<Uuid> {
+    let mut ids = keys
+        .iter()
+        .filter_map(|key| {
+            let (parent, name) = key.rsplit_once('/')?;
+            // Some storage backends list keys relative to their root directory, so only
+            // the end of the key is matched
+            format!("{}/", parent).ends_with(prefix).then_some(name)
+        })
+        .filter_map(|name| Uuid::parse_str(name).ok())
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    ids
+}
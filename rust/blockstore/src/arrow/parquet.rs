@@ -0,0 +1,216 @@
+use super::blockfile::ArrowUnorderedBlockfileWriter;
+use super::provider::BlockManager;
+use super::root::RootReader;
+use super::types::{ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue};
+use crate::BlockfileWriterMutationOrdering;
+use arrow::array::{Array, StringArray};
+use arrow::datatypes::{DataType, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use chroma_error::{ChromaError, ErrorCodes};
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ParquetBlockfileError {
+    #[error("Blockfile not found")]
+    NotFound,
+    #[error("Expected (prefix, key, value) columns: {0}")]
+    InvalidSchema(String),
+    #[error("Error reading blockfile: {0}")]
+    Read(Box<dyn ChromaError>),
+    #[error("Error writing blockfile: {0}")]
+    Write(Box<dyn ChromaError>),
+    #[error(transparent)]
+    Parquet(#[from] ParquetError),
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl ChromaError for ParquetBlockfileError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            ParquetBlockfileError::NotFound => ErrorCodes::NotFound,
+            ParquetBlockfileError::InvalidSchema(_) => ErrorCodes::InvalidArgument,
+            ParquetBlockfileError::Read(e) => e.code(),
+            ParquetBlockfileError::Write(e) => e.code(),
+            ParquetBlockfileError::Parquet(_) => ErrorCodes::Internal,
+            ParquetBlockfileError::Arrow(_) => ErrorCodes::Internal,
+            ParquetBlockfileError::Io(_) => ErrorCodes::Internal,
+        }
+    }
+}
+
+/// Writes every block of the blockfile described by `root` to a Parquet file at `path`, one block
+/// at a time and in (prefix, key) order. The Parquet file has the same (prefix, key, value)
+/// columns as the blocks. Returns the number of exported records.
+pub(super) async fn export_blocks(
+    block_manager: &BlockManager,
+    root: &RootReader,
+    path: &Path,
+) -> Result<usize, ParquetBlockfileError> {
+    let mut writer: Option<ArrowWriter<File>> = None;
+    let mut count = 0;
+    for block_id in root.sparse_index.get_block_ids_range(..) {
+        let block = block_manager
+            .get(
+                &root.prefix_path,
+                &block_id,
+                root.sparse_index.get_checksum(&block_id),
+                StorageRequestPriority::P0,
+            )
+            .await
+            .map_err(|e| ParquetBlockfileError::Read(Box::new(e)))?
+            .ok_or(ParquetBlockfileError::NotFound)?;
+        // Block metadata (e.g. the compression codec) only describes the block itself
+        let schema = Arc::new(Schema::new(block.data.schema().fields().clone()));
+        let batch = RecordBatch::try_new(schema.clone(), block.data.columns().to_vec())?;
+        if writer.is_none() {
+            writer = Some(ArrowWriter::try_new(File::create(path)?, schema, None)?);
+        }
+        if let Some(writer) = writer.as_mut() {
+            writer.write(&batch)?;
+        }
+        count += batch.num_rows();
+    }
+    // Every blockfile has at least one (possibly empty) block
+    if let Some(writer) = writer {
+        writer.close()?;
+    }
+    Ok(count)
+}
+
+/// A Parquet file with (prefix, key, value) columns that a blockfile can be built from with
+/// `ArrowBlockfileProvider::import_parquet`. Only the schema is read when opening the file,
+/// records are streamed one record batch at a time on import.
+pub struct ParquetBlockfileSource {
+    path: PathBuf,
+    schema: SchemaRef,
+    num_rows: usize,
+}
+
+impl ParquetBlockfileSource {
+    /// Open the Parquet file at `path`. The first three columns are used as the prefix, key and
+    /// value columns, as in files written by `ArrowBlockfileProvider::export_parquet`.
+    pub fn open(path: &Path) -> Result<Self, ParquetBlockfileError> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+        let schema = builder.schema().clone();
+        if schema.fields().len() != 3 {
+            return Err(ParquetBlockfileError::InvalidSchema(format!(
+                "got {} columns",
+                schema.fields().len()
+            )));
+        }
+        let prefix_field = schema.field(0);
+        if prefix_field.data_type() != &DataType::Utf8 {
+            return Err(ParquetBlockfileError::InvalidSchema(format!(
+                "prefix column has type {}",
+                prefix_field.data_type()
+            )));
+        }
+        let num_rows = builder.metadata().file_metadata().num_rows() as usize;
+        Ok(Self {
+            path: path.to_path_buf(),
+            schema,
+            num_rows,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.num_rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks that the key and value columns have the types that `K` and `V` are stored as
+    /// in a block, and that the value column is only nullable if `V` is.
+    fn check_types<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+    ) -> Result<(), ParquetBlockfileError> {
+        let (_, _, key_field, _) = K::get_arrow_builder(0, 0, 0).as_arrow();
+        let key_type = self.schema.field(1).data_type();
+        if key_type != key_field.data_type() {
+            return Err(ParquetBlockfileError::InvalidSchema(format!(
+                "key column has type {}, expected {}",
+                key_type,
+                key_field.data_type()
+            )));
+        }
+
+        // An empty delta has the schema of every block written for V
+        let empty_block = V::get_delta_builder(BlockfileWriterMutationOrdering::Unordered)
+            .into_record_batch::<K>(None);
+        let expected_schema = empty_block.schema();
+        let value_field = expected_schema.field(2);
+        let actual_field = self.schema.field(2);
+        if actual_field.data_type() != value_field.data_type() {
+            return Err(ParquetBlockfileError::InvalidSchema(format!(
+                "value column has type {}, expected {}",
+                actual_field.data_type(),
+                value_field.data_type()
+            )));
+        }
+        if actual_field.is_nullable() && !value_field.is_nullable() {
+            return Err(ParquetBlockfileError::InvalidSchema(
+                "value column is nullable".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Sets every record of `source` in `writer`, reading one record batch at a time. The records
+/// do not need to be sorted.
+pub(super) async fn import_into<K, V>(
+    source: &ParquetBlockfileSource,
+    writer: &ArrowUnorderedBlockfileWriter,
+) -> Result<(), ParquetBlockfileError>
+where
+    K: ArrowWriteableKey,
+    V: ArrowWriteableValue,
+    for<'data> K::ReadableKey<'data>: ArrowWriteableKey,
+    for<'data> V: From<V::ReadableValue<'data>>,
+{
+    source.check_types::<K, V>()?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&source.path)?)?.build()?;
+    for batch in reader {
+        let batch = batch?;
+        let prefixes = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| {
+                ParquetBlockfileError::InvalidSchema(format!(
+                    "prefix column has type {}",
+                    batch.column(0).data_type()
+                ))
+            })?;
+        let keys = batch.column(1);
+        let values = batch.column(2);
+        // Values are read without checking validity
+        if values.null_count() > 0 {
+            return Err(ParquetBlockfileError::InvalidSchema(
+                "value column contains nulls".to_string(),
+            ));
+        }
+        for index in 0..batch.num_rows() {
+            let key = <K::ReadableKey<'_> as ArrowReadableKey<'_>>::get(keys, index);
+            let value = <V::ReadableValue<'_> as ArrowReadableValue<'_>>::get(values, index);
+            writer
+                .set::<K::ReadableKey<'_>, V>(prefixes.value(index), key, V::from(value))
+                .await
+                .map_err(ParquetBlockfileError::Write)?;
+        }
+    }
+    Ok(())
+}
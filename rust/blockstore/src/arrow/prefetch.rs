@@ -0,0 +1,99 @@
+use crate::key::{CompositeKeyRange, KeyWrapper};
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
+use std::ops::RangeBounds;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+/// The part of a blockfile to prefetch with `ArrowBlockfileProvider::prefetch_scope()`.
+/// Only the blocks that may contain keys in the scope are fetched.
+#[derive(Clone, Debug)]
+pub struct PrefetchScope {
+    inner: Scope,
+}
+
+#[derive(Clone, Debug)]
+pub(super) enum Scope {
+    Prefixes(Vec<String>),
+    KeyRange(CompositeKeyRange),
+}
+
+impl PrefetchScope {
+    /// Every key with one of the given prefixes.
+    pub fn prefixes<P: Into<String>>(prefixes: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            inner: Scope::Prefixes(prefixes.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// Every key of the prefix in the key range.
+    pub fn key_range<K: Into<KeyWrapper> + Clone>(
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Self {
+        Self {
+            inner: Scope::KeyRange(CompositeKeyRange::new(prefix, key_range)),
+        }
+    }
+
+    pub(super) fn scope(&self) -> &Scope {
+        &self.inner
+    }
+}
+
+/// The progress of a prefetch. Blocks that were already cached count as fetched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefetchProgress {
+    /// The number of blocks in the scope of the prefetch
+    pub total_blocks: usize,
+    /// The number of blocks that were fetched so far, including those that were already cached
+    pub fetched_blocks: usize,
+    /// The number of blocks that were already cached when the prefetch started
+    pub cached_blocks: usize,
+}
+
+/// Options for `ArrowBlockfileProvider::prefetch_scope()`.
+pub struct PrefetchOptions {
+    pub(super) priority: StorageRequestPriority,
+    pub(super) cancellation_token: CancellationToken,
+    pub(super) progress: Option<watch::Sender<PrefetchProgress>>,
+}
+
+impl Default for PrefetchOptions {
+    fn default() -> Self {
+        Self {
+            priority: StorageRequestPriority::P1,
+            cancellation_token: CancellationToken::new(),
+            progress: None,
+        }
+    }
+}
+
+impl PrefetchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The priority of the storage requests for the blocks. Defaults to `P1`, below reads.
+    pub fn with_priority(mut self, priority: StorageRequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Stop the prefetch once the token is cancelled. Blocks fetched until then stay cached.
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    /// Publish the progress of the prefetch to the channel whenever a block is fetched.
+    pub fn with_progress(mut self, progress: watch::Sender<PrefetchProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub(super) fn report(&self, progress: PrefetchProgress) {
+        if let Some(sender) = &self.progress {
+            sender.send_replace(progress);
+        }
+    }
+}
@@ -1,30 +1,45 @@
 use super::{
     block::{delta::types::Delta, Block, BlockLoadError},
     blockfile::{ArrowBlockfileReader, ArrowUnorderedBlockfileWriter},
-    config::ArrowBlockfileProviderConfig,
+    config::{ArrowBlockfileProviderConfig, BlockCompression, BlockFlushConfig, KeyProviderConfig},
+    diff::BlockfileDiff,
+    encryption::{
+        decrypt_if_encrypted, BlockEncryption, BlockKeyProvider, EncryptionConfigError,
+        KeyProvider, LocalFileKeyProvider,
+    },
+    merge::{merge_into, MergeDuplicatePolicy, MergeError},
+    metrics::{BlockstoreMetrics, Operation},
     ordered_blockfile_writer::ArrowOrderedBlockfileWriter,
+    orphans::{ids_under_prefix, OrphanError, OrphanScan},
+    parquet::{export_blocks, import_into, ParquetBlockfileError, ParquetBlockfileSource},
+    prefetch::{PrefetchOptions, PrefetchProgress, PrefetchScope, Scope},
     root::{FromBytesError, RootReader, RootWriter},
     types::{ArrowReadableKey, ArrowReadableValue, ArrowWriteableKey, ArrowWriteableValue},
 };
 use crate::{
-    key::KeyWrapper,
+    key::{InvalidKeyConversion, KeyWrapper},
     memory::storage::Readable,
     provider::{CreateError, OpenError},
-    BlockfileReader, BlockfileWriter, BlockfileWriterMutationOrdering, BlockfileWriterOptions, Key,
-    Value,
+    BlockfileFlusher, BlockfileReader, BlockfileWriter, BlockfileWriterMutationOrdering,
+    BlockfileWriterOptions, Key, Value,
 };
+use arrow::buffer::Buffer;
 use async_trait::async_trait;
-use chroma_cache::{CacheError, PersistentCache};
+use chroma_cache::{CacheConfig, CacheError, PersistentCache};
 use chroma_config::{registry::Registry, Configurable};
 use chroma_error::{ChromaError, ErrorCodes};
 use chroma_storage::{
     admissioncontrolleds3::StorageRequestPriority, GetOptions, PutOptions, Storage,
 };
 use futures::{stream::FuturesUnordered, StreamExt};
+use opentelemetry::metrics::Meter;
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
 use tracing::{Instrument, Span};
@@ -36,6 +51,10 @@ pub enum ArrowBlockfileProviderPrefetchError {
     RootManager(#[from] Box<dyn ChromaError>),
     #[error("Error fetching block: {0}")]
     BlockManager(#[from] GetError),
+    #[error("Blockfile root not found")]
+    RootNotFound,
+    #[error("Prefetch was cancelled")]
+    Cancelled,
 }
 
 impl ChromaError for ArrowBlockfileProviderPrefetchError {
@@ -43,6 +62,8 @@ impl ChromaError for ArrowBlockfileProviderPrefetchError {
         match self {
             ArrowBlockfileProviderPrefetchError::RootManager(e) => e.code(),
             ArrowBlockfileProviderPrefetchError::BlockManager(e) => e.code(),
+            ArrowBlockfileProviderPrefetchError::RootNotFound => ErrorCodes::NotFound,
+            ArrowBlockfileProviderPrefetchError::Cancelled => ErrorCodes::Cancelled,
         }
     }
 }
@@ -85,6 +106,76 @@ impl ArrowBlockfileProvider {
         }
     }
 
+    /// Compress all blocks committed by writers created from this provider with the given codec.
+    /// Blocks are readable regardless of the codec they were written with.
+    pub fn with_block_compression(mut self, compression: BlockCompression) -> Self {
+        self.block_manager = self.block_manager.with_compression(compression);
+        self
+    }
+
+    /// Write blocks with the given concurrency and retry policy when blockfiles created by writers
+    /// from this provider are flushed.
+    pub fn with_flush_config(mut self, flush_config: BlockFlushConfig) -> Self {
+        self.block_manager = self.block_manager.with_flush_config(flush_config);
+        self
+    }
+
+    /// Store a bloom filter with `bits_per_key` bits per key for every block flushed by
+    /// writers created from this provider, so that readers can skip fetching blocks that
+    /// do not contain a key.
+    pub fn with_bloom_filters(mut self, bits_per_key: usize) -> Self {
+        self.block_manager = self.block_manager.with_bloom_filters(bits_per_key);
+        self
+    }
+
+    /// Merge adjacent blocks rewritten by writers created from this provider when they commit,
+    /// if one of them is filled below `min_fill_factor` of the max block size and the merged
+    /// block fits in the max block size. `min_fill_factor` must be between 0 and 1 exclusive.
+    /// Blocks are only merged with neighbors that the writer rewrote as well, so an underfilled
+    /// block whose neighbors are not written to stays as it is.
+    pub fn with_min_block_fill_factor(mut self, min_fill_factor: f64) -> Self {
+        self.block_manager = self.block_manager.with_min_fill_factor(min_fill_factor);
+        self
+    }
+
+    /// Encrypt the blocks and roots written by writers created from this provider with keys
+    /// from the key provider, both in storage and in the block cache. Blocks and roots that
+    /// were written in plain text are rejected on read unless `allow_plaintext` is set.
+    /// ### Notes
+    /// - Roots are cached decrypted, so the root cache should not be a disk cache
+    /// - Unordered writers cannot spill to disk, so writers with a memory budget are refused
+    pub fn with_encryption(
+        mut self,
+        key_provider: Arc<dyn KeyProvider>,
+        allow_plaintext: bool,
+    ) -> Self {
+        let encryption = BlockEncryption::new(key_provider).with_allow_plaintext(allow_plaintext);
+        self.block_manager = self.block_manager.with_encryption(encryption.clone());
+        self.root_manager = self.root_manager.with_encryption(encryption);
+        self
+    }
+
+    /// Load blocks that miss the block cache by memory mapping their files under `mmap_dir`
+    /// instead of copying them into memory. With a local storage, pass its root directory so
+    /// that blocks are mapped where they are stored. With any other storage, blocks are written
+    /// to `mmap_dir` the first time they are fetched, which makes it a disk cache of blocks.
+    /// ### Notes
+    /// - Nothing else may write to the files under `mmap_dir`, see `Block::map_file()`
+    /// - Encrypted blocks are decrypted into memory, so this has no effect with encryption
+    pub fn with_mmap_dir(mut self, mmap_dir: impl Into<PathBuf>) -> Self {
+        self.block_manager = self.block_manager.with_mmap_dir(mmap_dir.into());
+        self
+    }
+
+    /// Record the metrics of the blockfiles read and written through this provider with the
+    /// given meter instead of one from the global meter provider.
+    pub fn with_meter(mut self, meter: &Meter) -> Self {
+        let metrics = BlockstoreMetrics::new(meter);
+        self.block_manager = self.block_manager.with_metrics(metrics.clone());
+        self.root_manager = self.root_manager.with_metrics(metrics);
+        self
+    }
+
     pub async fn read<
         'new,
         K: Key + Into<KeyWrapper> + ArrowReadableKey<'new> + 'new,
@@ -106,6 +197,34 @@ impl ArrowBlockfileProvider {
         }
     }
 
+    /// Open two blockfiles to compare them, see `BlockfileDiff`.
+    /// `base_id` is usually the blockfile that `other_id` was forked from.
+    pub async fn diff<
+        'new,
+        K: Key + Into<KeyWrapper> + ArrowReadableKey<'new> + 'new,
+        V: ArrowReadableValue<'new> + PartialEq + 'new,
+    >(
+        &self,
+        base_id: &Uuid,
+        other_id: &Uuid,
+        prefix_path: &str,
+    ) -> Result<BlockfileDiff<'new, K, V>, Box<OpenError>> {
+        let mut roots = Vec::with_capacity(2);
+        for id in [base_id, other_id] {
+            match self.root_manager.get::<K>(id, prefix_path).await {
+                Ok(Some(root)) => roots.push(root),
+                Ok(None) => return Err(Box::new(OpenError::NotFound)),
+                Err(e) => return Err(Box::new(OpenError::Other(Box::new(e)))),
+            }
+        }
+        let other = roots.pop().expect("Both roots were loaded");
+        let base = roots.pop().expect("Both roots were loaded");
+        Ok(BlockfileDiff::new(
+            ArrowBlockfileReader::new(self.block_manager.clone(), base),
+            ArrowBlockfileReader::new(self.block_manager.clone(), other),
+        ))
+    }
+
     pub async fn prefetch(
         &self,
         id: &Uuid,
@@ -114,21 +233,28 @@ impl ArrowBlockfileProvider {
         if !self.root_manager.should_prefetch(id) {
             return Ok(0);
         }
-        // We call .get_all_block_ids() here instead of just reading the root because reading the root requires a concrete Key type.
-        let block_ids = self
+        // We call .get_all_blocks() here instead of just reading the root because reading the root requires a concrete Key type.
+        let blocks = self
             .root_manager
-            .get_all_block_ids(id, prefix_path)
+            .get_all_blocks_for_operation(
+                id,
+                prefix_path,
+                Operation::Prefetch,
+                StorageRequestPriority::P1,
+            )
             .await
             .map_err(|e| ArrowBlockfileProviderPrefetchError::RootManager(Box::new(e)))?;
 
         let mut futures = FuturesUnordered::new();
-        for block_id in block_ids.iter() {
+        for (block_id, checksum) in blocks.iter() {
             // Don't prefetch if already cached.
             if !self.block_manager.cached(block_id).await {
-                futures.push(self.block_manager.get(
+                futures.push(self.block_manager.get_for_operation(
                     prefix_path,
                     block_id,
+                    *checksum,
                     StorageRequestPriority::P1,
+                    Operation::Prefetch,
                 ));
             }
         }
@@ -144,6 +270,86 @@ impl ArrowBlockfileProvider {
         Ok(count)
     }
 
+    /// Fetch only the blocks of a blockfile that may contain keys in the scope into the block
+    /// cache. Unlike `.prefetch()`, this is not skipped if the blockfile was prefetched recently.
+    /// Returns the final progress, or `Cancelled` if the token of the options was cancelled
+    /// before all blocks were fetched.
+    pub async fn prefetch_scope<'new, K: Key + ArrowReadableKey<'new> + 'new>(
+        &self,
+        id: &Uuid,
+        prefix_path: &str,
+        scope: &PrefetchScope,
+        options: PrefetchOptions,
+    ) -> Result<PrefetchProgress, ArrowBlockfileProviderPrefetchError> {
+        let root = self
+            .root_manager
+            .get_for_operation::<K>(id, prefix_path, Operation::Prefetch, options.priority)
+            .await
+            .map_err(|e| ArrowBlockfileProviderPrefetchError::RootManager(Box::new(e)))?
+            .ok_or(ArrowBlockfileProviderPrefetchError::RootNotFound)?;
+
+        let block_ids = match scope.scope() {
+            Scope::Prefixes(prefixes) => root
+                .sparse_index
+                .get_block_ids_for_prefixes(prefixes.iter().map(String::as_str).collect()),
+            Scope::KeyRange(range) => root.sparse_index.get_block_ids_in_range(range),
+        };
+
+        let mut progress = PrefetchProgress {
+            total_blocks: block_ids.len(),
+            ..Default::default()
+        };
+        let mut futures = FuturesUnordered::new();
+        for block_id in block_ids.iter() {
+            // Don't prefetch if already cached.
+            if self.block_manager.cached(block_id).await {
+                progress.cached_blocks += 1;
+                progress.fetched_blocks += 1;
+            } else {
+                futures.push(self.block_manager.get_for_operation(
+                    prefix_path,
+                    block_id,
+                    root.sparse_index.get_checksum(block_id),
+                    options.priority,
+                    Operation::Prefetch,
+                ));
+            }
+        }
+        options.report(progress);
+
+        tracing::info!(
+            "Prefetching {} of {} blocks in scope for blockfile ID: {:?}",
+            futures.len(),
+            progress.total_blocks,
+            id
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = options.cancellation_token.cancelled() => {
+                    tracing::info!(
+                        "Prefetch of blockfile ID {:?} was cancelled after {} of {} blocks",
+                        id,
+                        progress.fetched_blocks,
+                        progress.total_blocks
+                    );
+                    return Err(ArrowBlockfileProviderPrefetchError::Cancelled);
+                }
+                result = futures.next() => match result {
+                    Some(result) => {
+                        result?;
+                        progress.fetched_blocks += 1;
+                        options.report(progress);
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        Ok(progress)
+    }
+
     pub async fn write<
         'new,
         K: Key + Into<KeyWrapper> + ArrowWriteableKey + 'new,
@@ -152,6 +358,15 @@ impl ArrowBlockfileProvider {
         &self,
         options: BlockfileWriterOptions,
     ) -> Result<crate::BlockfileWriter, Box<CreateError>> {
+        // Spilled deltas would be written to local disk in plain text
+        if self.block_manager.encryption.is_some()
+            && options.memory_budget_bytes.is_some()
+            && options.mutation_ordering == BlockfileWriterMutationOrdering::Unordered
+        {
+            return Err(Box::new(CreateError::Other(Box::new(
+                EncryptionConfigError::Spill,
+            ))));
+        }
         if let Some(fork_from) = options.fork_from {
             tracing::info!("Forking blockfile from {:?}", fork_from);
             let new_id = Uuid::new_v4();
@@ -176,12 +391,15 @@ impl ArrowBlockfileProvider {
                     Ok(BlockfileWriter::ArrowOrderedBlockfileWriter(file))
                 }
                 BlockfileWriterMutationOrdering::Unordered => {
-                    let file = ArrowUnorderedBlockfileWriter::from_root(
+                    let mut file = ArrowUnorderedBlockfileWriter::from_root(
                         new_id,
                         self.block_manager.clone(),
                         self.root_manager.clone(),
                         new_root,
                     );
+                    if let Some(memory_budget_bytes) = options.memory_budget_bytes {
+                        file = file.with_memory_budget(memory_budget_bytes);
+                    }
                     Ok(BlockfileWriter::ArrowUnorderedBlockfileWriter(file))
                 }
             }
@@ -200,18 +418,217 @@ impl ArrowBlockfileProvider {
                     Ok(BlockfileWriter::ArrowOrderedBlockfileWriter(file))
                 }
                 BlockfileWriterMutationOrdering::Unordered => {
-                    let file = ArrowUnorderedBlockfileWriter::new::<K, V>(
+                    let mut file = ArrowUnorderedBlockfileWriter::new::<K, V>(
                         new_id,
                         &options.prefix_path,
                         self.block_manager.clone(),
                         self.root_manager.clone(),
                     );
+                    if let Some(memory_budget_bytes) = options.memory_budget_bytes {
+                        file = file.with_memory_budget(memory_budget_bytes);
+                    }
                     Ok(BlockfileWriter::ArrowUnorderedBlockfileWriter(file))
                 }
             }
         }
     }
 
+    /// Merge the given blockfiles into a new blockfile under `prefix_path`, in key order.
+    /// Keys present in more than one blockfile are resolved with `duplicate_policy`.
+    /// The merged blockfile must be flushed with the returned flusher.
+    pub async fn merge<'readers, K, V>(
+        &self,
+        readers: &'readers [BlockfileReader<'readers, K, V::ReadableValue<'readers>>],
+        duplicate_policy: MergeDuplicatePolicy,
+        prefix_path: &str,
+    ) -> Result<BlockfileFlusher, MergeError>
+    where
+        K: Key
+            + Into<KeyWrapper>
+            + TryFrom<&'readers KeyWrapper, Error = InvalidKeyConversion>
+            + ArrowReadableKey<'readers>
+            + ArrowWriteableKey
+            + Sync
+            + Send
+            + 'readers,
+        V: Value + ArrowWriteableValue + From<V::ReadableValue<'readers>>,
+        V::ReadableValue<'readers>: Value + Readable<'readers> + Sync + Send + 'readers,
+    {
+        let writer = ArrowOrderedBlockfileWriter::new::<K, V>(
+            Uuid::new_v4(),
+            prefix_path,
+            self.block_manager.clone(),
+            self.root_manager.clone(),
+        );
+        merge_into::<K, V>(readers, duplicate_policy, &writer).await?;
+        let flusher = writer.commit::<K, V>().await.map_err(MergeError::Write)?;
+        Ok(BlockfileFlusher::ArrowBlockfileFlusher(flusher))
+    }
+
+    /// Write every (prefix, key, value) of the blockfile to a Parquet file at `path`.
+    /// Returns the number of exported records.
+    pub async fn export_parquet<'new, K: ArrowReadableKey<'new> + 'new>(
+        &self,
+        id: &Uuid,
+        prefix_path: &str,
+        path: &Path,
+    ) -> Result<usize, ParquetBlockfileError> {
+        let root = self
+            .root_manager
+            .get::<K>(id, prefix_path)
+            .await
+            .map_err(|e| ParquetBlockfileError::Read(Box::new(e)))?
+            .ok_or(ParquetBlockfileError::NotFound)?;
+        export_blocks(&self.block_manager, &root, path).await
+    }
+
+    /// Build a new blockfile under `prefix_path` from the records of a Parquet file,
+    /// see `ParquetBlockfileSource`. The new blockfile must be flushed with the returned flusher.
+    pub async fn import_parquet<K, V>(
+        &self,
+        source: &ParquetBlockfileSource,
+        prefix_path: &str,
+    ) -> Result<BlockfileFlusher, ParquetBlockfileError>
+    where
+        K: Key + Into<KeyWrapper> + ArrowWriteableKey,
+        V: Value + ArrowWriteableValue,
+        for<'data> K::ReadableKey<'data>: ArrowWriteableKey,
+        for<'data> V: From<V::ReadableValue<'data>>,
+    {
+        let writer = ArrowUnorderedBlockfileWriter::new::<K, V>(
+            Uuid::new_v4(),
+            prefix_path,
+            self.block_manager.clone(),
+            self.root_manager.clone(),
+        );
+        import_into::<K, V>(source, &writer).await?;
+        let flusher = writer
+            .commit::<K, V>()
+            .await
+            .map_err(ParquetBlockfileError::Write)?;
+        Ok(BlockfileFlusher::ArrowBlockfileFlusher(flusher))
+    }
+
+    /// List the blocks and roots under `prefix_path` that are not reachable from any of
+    /// `live_root_ids`. This is a dry run, nothing is deleted, see `delete_orphans`.
+    /// `prefix_path` must not be empty: blockfiles without one share the global block and root
+    /// namespaces, whose live roots cannot be known from a single caller.
+    pub async fn find_orphans(
+        &self,
+        prefix_path: &str,
+        live_root_ids: &HashSet<Uuid>,
+    ) -> Result<OrphanScan, OrphanError> {
+        if prefix_path.is_empty() {
+            return Err(OrphanError::EmptyPrefixPath);
+        }
+        let mut live_block_ids = HashSet::new();
+        for root_id in live_root_ids {
+            let block_ids = self
+                .root_manager
+                .get_all_block_ids(root_id, prefix_path)
+                .await
+                .map_err(|e| OrphanError::LiveRoot(*root_id, e))?;
+            live_block_ids.extend(block_ids);
+        }
+
+        let scanned_at = SystemTime::now();
+        let storage = &self.root_manager.storage;
+        let block_prefix = BlockManager::storage_prefix(prefix_path);
+        let block_keys = storage
+            .list_prefix(&block_prefix)
+            .await
+            .map_err(|e| OrphanError::List(block_prefix.clone(), e))?;
+        let root_prefix = RootManager::storage_prefix(prefix_path);
+        let root_keys = storage
+            .list_prefix(&root_prefix)
+            .await
+            .map_err(|e| OrphanError::List(root_prefix.clone(), e))?;
+
+        Ok(OrphanScan {
+            prefix_path: prefix_path.to_string(),
+            scanned_at,
+            orphaned_block_ids: ids_under_prefix(&block_prefix, block_keys)
+                .into_iter()
+                .filter(|id| !live_block_ids.contains(id))
+                .collect(),
+            orphaned_root_ids: ids_under_prefix(&root_prefix, root_keys)
+                .into_iter()
+                .filter(|id| !live_root_ids.contains(id))
+                .collect(),
+        })
+    }
+
+    /// Delete the orphans of a previous `find_orphans` scan that are still orphaned now.
+    /// The scan must be at least `grace_period` old, so that writers that were in flight
+    /// when it was taken have had time to flush their roots. `live_root_ids` must include
+    /// every root that should be kept, including roots flushed after the scan was taken.
+    /// Returns the objects that were deleted.
+    pub async fn delete_orphans(
+        &self,
+        scan: &OrphanScan,
+        live_root_ids: &HashSet<Uuid>,
+        grace_period: Duration,
+    ) -> Result<OrphanScan, OrphanError> {
+        if scan.prefix_path.is_empty() {
+            return Err(OrphanError::EmptyPrefixPath);
+        }
+        let age = scan.scanned_at.elapsed().unwrap_or_default();
+        if age < grace_period {
+            return Err(OrphanError::GracePeriodNotElapsed);
+        }
+
+        let current = self.find_orphans(&scan.prefix_path, live_root_ids).await?;
+        let still_orphaned_roots = current
+            .orphaned_root_ids
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let still_orphaned_blocks = current
+            .orphaned_block_ids
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let mut deleted = OrphanScan {
+            prefix_path: scan.prefix_path.clone(),
+            scanned_at: current.scanned_at,
+            orphaned_block_ids: Vec::new(),
+            orphaned_root_ids: Vec::new(),
+        };
+
+        let storage = &self.root_manager.storage;
+        // Roots are deleted first so that no root is left pointing at deleted blocks
+        for root_id in scan.orphaned_root_ids.iter() {
+            if !still_orphaned_roots.contains(root_id) {
+                continue;
+            }
+            let key = RootManager::get_storage_key(&scan.prefix_path, root_id);
+            storage
+                .delete(&key)
+                .await
+                .map_err(|e| OrphanError::Delete(key.clone(), e))?;
+            self.root_manager.cache.remove(root_id).await;
+            deleted.orphaned_root_ids.push(*root_id);
+        }
+        for block_id in scan.orphaned_block_ids.iter() {
+            if !still_orphaned_blocks.contains(block_id) {
+                continue;
+            }
+            let key = BlockManager::format_key(&scan.prefix_path, block_id);
+            storage
+                .delete(&key)
+                .await
+                .map_err(|e| OrphanError::Delete(key.clone(), e))?;
+            self.block_manager.block_cache.remove(block_id).await;
+            deleted.orphaned_block_ids.push(*block_id);
+        }
+        tracing::info!(
+            "Deleted {} orphaned roots and {} orphaned blocks under {:?}",
+            deleted.orphaned_root_ids.len(),
+            deleted.orphaned_block_ids.len(),
+            scan.prefix_path
+        );
+
+        Ok(deleted)
+    }
+
     pub async fn clear(&self) -> Result<(), CacheError> {
         self.block_manager.block_cache.clear().await?;
         self.root_manager.cache.clear().await?;
@@ -220,13 +637,57 @@ impl ArrowBlockfileProvider {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum BlockManagerConfigError {
+    #[error("min_block_fill_factor must be between 0 and 1 exclusive, got {0}")]
+    MinBlockFillFactor(f64),
+}
+
+impl ChromaError for BlockManagerConfigError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            BlockManagerConfigError::MinBlockFillFactor(_) => ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
 #[async_trait]
 impl Configurable<(ArrowBlockfileProviderConfig, Storage)> for ArrowBlockfileProvider {
     async fn try_from_config(
         config: &(ArrowBlockfileProviderConfig, Storage),
-        _registry: &Registry,
+        registry: &Registry,
     ) -> Result<Self, Box<dyn ChromaError>> {
         let (blockfile_config, storage) = config;
+        if let Some(min_fill_factor) = blockfile_config.block_manager_config.min_block_fill_factor {
+            // Also rejects NaN
+            if !(min_fill_factor > 0.0 && min_fill_factor < 1.0) {
+                return Err(Box::new(BlockManagerConfigError::MinBlockFillFactor(
+                    min_fill_factor,
+                )));
+            }
+        }
+        let key_provider: Option<Arc<dyn KeyProvider>> = match &blockfile_config.encryption {
+            Some(_)
+                if matches!(
+                    blockfile_config.root_manager_config.root_cache_config,
+                    CacheConfig::Disk(_)
+                ) =>
+            {
+                return Err(Box::new(EncryptionConfigError::DiskRootCache));
+            }
+            Some(encryption_config) => match &encryption_config.key_provider {
+                KeyProviderConfig::LocalFile(local_file_config) => Some(Arc::new(
+                    LocalFileKeyProvider::from_config(local_file_config),
+                )),
+                KeyProviderConfig::Registry => Some(
+                    registry
+                        .get::<BlockKeyProvider>()
+                        .map_err(|err| err.boxed())?
+                        .0,
+                ),
+            },
+            None => None,
+        };
         let block_cache = match chroma_cache::from_config_persistent(
             &blockfile_config.block_manager_config.block_cache_config,
         )
@@ -248,12 +709,32 @@ impl Configurable<(ArrowBlockfileProviderConfig, Storage)> for ArrowBlockfilePro
                     return Err(e);
                 }
             };
-        Ok(ArrowBlockfileProvider::new(
+        let mut provider = ArrowBlockfileProvider::new(
             storage.clone(),
             blockfile_config.block_manager_config.max_block_size_bytes,
             block_cache,
             sparse_index_cache,
-        ))
+        )
+        .with_block_compression(blockfile_config.block_manager_config.block_compression)
+        .with_flush_config(blockfile_config.block_manager_config.flush_config.clone());
+        if let Some(bits_per_key) = blockfile_config
+            .block_manager_config
+            .bloom_filter_bits_per_key
+        {
+            provider = provider.with_bloom_filters(bits_per_key);
+        }
+        if let Some(min_fill_factor) = blockfile_config.block_manager_config.min_block_fill_factor {
+            provider = provider.with_min_block_fill_factor(min_fill_factor);
+        }
+        if let Some(mmap_dir) = &blockfile_config.block_manager_config.mmap_dir {
+            provider = provider.with_mmap_dir(mmap_dir);
+        }
+        if let (Some(key_provider), Some(encryption_config)) =
+            (key_provider, &blockfile_config.encryption)
+        {
+            provider = provider.with_encryption(key_provider, encryption_config.allow_plaintext);
+        }
+        Ok(provider)
     }
 }
 
@@ -263,6 +744,8 @@ pub enum GetError {
     BlockLoadError(#[from] BlockLoadError),
     #[error(transparent)]
     StorageGetError(#[from] chroma_storage::StorageError),
+    #[error("Checksum mismatch for block {0}")]
+    ChecksumMismatch(Uuid),
 }
 
 impl ChromaError for GetError {
@@ -270,6 +753,7 @@ impl ChromaError for GetError {
         match self {
             GetError::BlockLoadError(e) => e.code(),
             GetError::StorageGetError(e) => e.code(),
+            GetError::ChecksumMismatch(_) => ErrorCodes::DataLoss,
         }
     }
 }
@@ -302,6 +786,13 @@ pub struct BlockManager {
     block_cache: Arc<dyn PersistentCache<Uuid, Block>>,
     storage: Storage,
     max_block_size_bytes: usize,
+    compression: BlockCompression,
+    bloom_filter_bits_per_key: Option<usize>,
+    flush_config: BlockFlushConfig,
+    min_fill_factor: Option<f64>,
+    encryption: Option<BlockEncryption>,
+    mmap_dir: Option<PathBuf>,
+    metrics: BlockstoreMetrics,
 }
 
 impl BlockManager {
@@ -315,9 +806,51 @@ impl BlockManager {
             block_cache,
             storage,
             max_block_size_bytes,
+            compression: BlockCompression::None,
+            bloom_filter_bits_per_key: None,
+            flush_config: BlockFlushConfig::default(),
+            min_fill_factor: None,
+            encryption: None,
+            mmap_dir: None,
+            metrics: BlockstoreMetrics::default(),
         }
     }
 
+    pub(super) fn with_compression(mut self, compression: BlockCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub(super) fn with_bloom_filters(mut self, bits_per_key: usize) -> Self {
+        self.bloom_filter_bits_per_key = Some(bits_per_key);
+        self
+    }
+
+    pub(super) fn with_flush_config(mut self, flush_config: BlockFlushConfig) -> Self {
+        self.flush_config = flush_config;
+        self
+    }
+
+    pub(super) fn with_min_fill_factor(mut self, min_fill_factor: f64) -> Self {
+        self.min_fill_factor = Some(min_fill_factor);
+        self
+    }
+
+    pub(super) fn with_encryption(mut self, encryption: BlockEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub(super) fn with_metrics(mut self, metrics: BlockstoreMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub(super) fn with_mmap_dir(mut self, mmap_dir: PathBuf) -> Self {
+        self.mmap_dir = Some(mmap_dir);
+        self
+    }
+
     pub(super) fn create<K: ArrowWriteableKey, V: ArrowWriteableValue, D: Delta>(&self) -> D {
         let new_block_id = Uuid::new_v4();
         D::new::<K, V>(new_block_id)
@@ -327,9 +860,16 @@ impl BlockManager {
         &self,
         block_id: &Uuid,
         prefix_path: &str,
+        expected_checksum: Option<u32>,
     ) -> Result<D, ForkError> {
         let block = self
-            .get(prefix_path, block_id, StorageRequestPriority::P0)
+            .get_for_operation(
+                prefix_path,
+                block_id,
+                expected_checksum,
+                StorageRequestPriority::P0,
+                Operation::Fork,
+            )
             .await;
         let block = match block {
             Ok(Some(block)) => block,
@@ -350,11 +890,43 @@ impl BlockManager {
     ) -> Block {
         let delta_id = delta.id();
         let record_batch = delta.finish::<K, V>(None);
-        let block = Block::from_record_batch(delta_id, record_batch);
-        self.block_cache.insert(delta_id, block.clone()).await;
+        self.commit_block(Block::from_record_batch(delta_id, record_batch))
+            .await
+    }
+
+    /// Commit a block that was already finished from a delta, such as a delta spilled to disk.
+    /// Blocks that are encrypted at rest are encrypted here, so that they are never written to
+    /// the disk cache in plain text and flushing does not encrypt them again.
+    pub(super) async fn commit_block(&self, block: Block) -> Block {
+        let block = block.with_compression(self.compression);
+        let block = match &self.encryption {
+            Some(encryption) => match Self::encrypt(encryption, &block).await {
+                Ok(bytes) => block.with_encrypted_bytes(Buffer::from_vec(bytes)),
+                Err(e) => {
+                    // Left out of the cache, flushing encrypts the block again or fails
+                    tracing::error!("Failed to encrypt block {}: {}", block.id, e);
+                    return block;
+                }
+            },
+            None => block,
+        };
+        self.block_cache.insert(block.id, block.clone()).await;
         block
     }
 
+    async fn encrypt(
+        encryption: &BlockEncryption,
+        block: &Block,
+    ) -> Result<Vec<u8>, Box<dyn ChromaError>> {
+        let bytes = block
+            .to_bytes()
+            .map_err(|e| Box::new(e) as Box<dyn ChromaError>)?;
+        encryption
+            .encrypt(&bytes, &block.id)
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn ChromaError>)
+    }
+
     pub(super) async fn cached(&self, id: &Uuid) -> bool {
         self.block_cache
             .get(id)
@@ -364,81 +936,255 @@ impl BlockManager {
     }
 
     pub fn format_key(prefix_path: &str, id: &Uuid) -> String {
+        format!("{}{}", Self::storage_prefix(prefix_path), id)
+    }
+
+    /// The prefix of the storage keys of all blocks under `prefix_path`
+    pub fn storage_prefix(prefix_path: &str) -> String {
         // For legacy collections, prefix_path is empty.
         if prefix_path.is_empty() {
-            return format!("block/{}", id);
+            return "block/".to_string();
         }
-        format!("{}/block/{}", prefix_path, id)
+        format!("{}/block/", prefix_path)
     }
 
+    /// Get a block from the cache, or from storage if it is not cached.
+    /// If `expected_checksum` is set, the bytes read from storage are verified against it.
+    /// Blocks read from the disk cache are verified against the checksum of their cache entry.
+    /// On a mismatch the block is not returned and any cached copy is evicted.
+    /// Blocks that are encrypted at rest are decrypted both when they are read from storage
+    /// and when they are read from the disk cache.
     pub(super) async fn get(
         &self,
         prefix_path: &str,
         id: &Uuid,
+        expected_checksum: Option<u32>,
+        priority: StorageRequestPriority,
+    ) -> Result<Option<Block>, GetError> {
+        self.get_for_operation(prefix_path, id, expected_checksum, priority, Operation::Get)
+            .await
+    }
+
+    /// Like `.get()`, with the operation the block is read for recorded in the metrics
+    pub(super) async fn get_for_operation(
+        &self,
+        prefix_path: &str,
+        id: &Uuid,
+        expected_checksum: Option<u32>,
         priority: StorageRequestPriority,
+        operation: Operation,
     ) -> Result<Option<Block>, GetError> {
         let block = self.block_cache.obtain(*id).await.ok().flatten();
+        self.metrics.block_cache_lookup(
+            block.as_ref().is_some_and(|block| !block.is_corrupted()),
+            operation,
+            priority,
+        );
         match block {
-            Some(block) => Ok(Some(block)),
-            None => async {
-                let key = Self::format_key(prefix_path, id);
-                let bytes_res = self
-                    .storage
-                    .get(&key, GetOptions::new(priority))
-                    .instrument(
-                        tracing::trace_span!(parent: Span::current(), "BlockManager storage get", id = id.to_string()),
-                    )
-                    .await;
-                match bytes_res {
-                    Ok(bytes) => {
-                        let deserialization_span = tracing::trace_span!(parent: Span::current(), "BlockManager deserialize block");
-                        let block =
-                            deserialization_span.in_scope(|| Block::from_bytes(&bytes, *id));
-                        match block {
-                            Ok(block) => {
-                                self.block_cache.insert(*id, block.clone()).await;
-                                Ok(Some(block))
-                            }
-                            Err(e) => {
-                                tracing::error!(
-                                    "Error converting bytes to Block {:?}/{:?}",
-                                    key,
-                                    e
-                                );
-                                Err(GetError::BlockLoadError(e))
-                            }
-                        }
+            Some(block) if block.is_corrupted() => {
+                tracing::error!("Block {} failed checksum verification in the block cache", id);
+                self.block_cache.remove(id).await;
+                Err(GetError::ChecksumMismatch(*id))
+            }
+            Some(block) if block.is_sealed() => {
+                let block = match &self.encryption {
+                    Some(encryption) => block.unseal(encryption).await,
+                    None => Err(BlockLoadError::Encrypted),
+                };
+                match block {
+                    Ok(block) => {
+                        // The block keeps its encrypted bytes, so caching it decrypted
+                        // does not encrypt it again
+                        self.block_cache.insert(*id, block.clone()).await;
+                        Ok(Some(block))
                     }
                     Err(e) => {
-                        tracing::error!("Error converting bytes to Block {:?}", e);
-                        Err(GetError::StorageGetError(e))
+                        tracing::error!("Could not decrypt block {} from the block cache: {}", id, e);
+                        self.block_cache.remove(id).await;
+                        Err(GetError::BlockLoadError(e))
                     }
                 }
+            }
+            Some(block) => Ok(Some(block)),
+            None => async {
+                let block = match (&self.mmap_dir, &self.encryption) {
+                    (Some(mmap_dir), None) => {
+                        self.fetch_mapped(mmap_dir, prefix_path, id, expected_checksum, priority, operation)
+                            .await?
+                    }
+                    _ => {
+                        self.fetch(prefix_path, id, expected_checksum, priority, operation)
+                            .await?
+                    }
+                };
+                self.block_cache.insert(*id, block.clone()).await;
+                Ok(Some(block))
             }.instrument(tracing::trace_span!(parent: Span::current(), "BlockManager get cold", block_id = id.to_string())).await
         }
     }
 
-    pub(super) async fn flush(
+    /// Read a block from storage without looking it up in or adding it to the block cache,
+    /// e.g. to verify a blockfile without evicting the blocks that are in use.
+    pub(super) async fn get_from_storage(
         &self,
-        block: &Block,
         prefix_path: &str,
-    ) -> Result<(), Box<dyn ChromaError>> {
-        let bytes = match block.to_bytes() {
-            Ok(bytes) => bytes,
+        id: &Uuid,
+        expected_checksum: Option<u32>,
+        priority: StorageRequestPriority,
+    ) -> Result<Block, GetError> {
+        self.fetch(prefix_path, id, expected_checksum, priority, Operation::Get)
+            .await
+    }
+
+    /// Load a block by memory mapping its file under `mmap_dir`, which is laid out like a local
+    /// storage. If the file does not exist, e.g. because the storage is not local, the block is
+    /// read from storage and written there first, so that later cache misses are mapped as well.
+    async fn fetch_mapped(
+        &self,
+        mmap_dir: &Path,
+        prefix_path: &str,
+        id: &Uuid,
+        expected_checksum: Option<u32>,
+        priority: StorageRequestPriority,
+        operation: Operation,
+    ) -> Result<Block, GetError> {
+        let key = Self::format_key(prefix_path, id);
+        let path = mmap_dir.join(&key);
+        if !path.exists() {
+            let bytes = self
+                .storage
+                .get(&key, GetOptions::new(priority))
+                .instrument(
+                    tracing::trace_span!(parent: Span::current(), "BlockManager storage get", id = id.to_string()),
+                )
+                .await?;
+            self.metrics.block_fetched(bytes.len(), operation, priority);
+            write_block_file(&path, &bytes).map_err(BlockLoadError::IOError)?;
+        }
+        let buffer = Block::map_file(&path)?;
+        if let Some(expected_checksum) = expected_checksum {
+            let checksum = Block::checksum_of(buffer.as_slice());
+            if checksum != expected_checksum {
+                tracing::error!(
+                    "Checksum mismatch for block {:?}: expected {}, got {}",
+                    path,
+                    expected_checksum,
+                    checksum
+                );
+                return Err(GetError::ChecksumMismatch(*id));
+            }
+        }
+        let deserialization_start = Instant::now();
+        let block = Block::from_buffer(buffer, *id);
+        self.metrics
+            .block_deserialized(deserialization_start.elapsed(), operation, priority);
+        Ok(block?)
+    }
+
+    async fn fetch(
+        &self,
+        prefix_path: &str,
+        id: &Uuid,
+        expected_checksum: Option<u32>,
+        priority: StorageRequestPriority,
+        operation: Operation,
+    ) -> Result<Block, GetError> {
+        let key = Self::format_key(prefix_path, id);
+        let bytes_res = self
+            .storage
+            .get(&key, GetOptions::new(priority))
+            .instrument(
+                tracing::trace_span!(parent: Span::current(), "BlockManager storage get", id = id.to_string()),
+            )
+            .await;
+        match bytes_res {
+            Ok(bytes) => {
+                if let Some(expected_checksum) = expected_checksum {
+                    let checksum = Block::checksum_of(&bytes);
+                    if checksum != expected_checksum {
+                        tracing::error!(
+                            "Checksum mismatch for block {:?}: expected {}, got {}",
+                            key,
+                            expected_checksum,
+                            checksum
+                        );
+                        return Err(GetError::ChecksumMismatch(*id));
+                    }
+                }
+                self.metrics.block_fetched(bytes.len(), operation, priority);
+                let deserialization_span =
+                    tracing::trace_span!(parent: Span::current(), "BlockManager deserialize block");
+                let block = async {
+                    let decryption_start = Instant::now();
+                    let plaintext =
+                        decrypt_if_encrypted(self.encryption.as_ref(), &bytes, id).await?;
+                    if let Cow::Owned(_) = plaintext {
+                        self.metrics.block_decrypted(
+                            decryption_start.elapsed(),
+                            operation,
+                            priority,
+                        );
+                    }
+                    let deserialization_start = Instant::now();
+                    let block =
+                        deserialization_span.in_scope(|| Block::from_bytes(&plaintext, *id));
+                    self.metrics.block_deserialized(
+                        deserialization_start.elapsed(),
+                        operation,
+                        priority,
+                    );
+                    let block = block?;
+                    // Keep the encrypted bytes to write the block to the disk cache
+                    Ok::<_, BlockLoadError>(match plaintext {
+                        Cow::Owned(_) => {
+                            block.with_encrypted_bytes(Buffer::from_slice_ref(&bytes[..]))
+                        }
+                        Cow::Borrowed(_) => block,
+                    })
+                }
+                .await;
+                block.map_err(|e| {
+                    tracing::error!("Error converting bytes to Block {:?}/{:?}", key, e);
+                    GetError::BlockLoadError(e)
+                })
+            }
             Err(e) => {
-                tracing::error!("Failed to convert block to bytes");
-                return Err(Box::new(e));
+                tracing::error!("Error converting bytes to Block {:?}", e);
+                Err(GetError::StorageGetError(e))
             }
+        }
+    }
+
+    /// Writes the block to storage and returns the checksum and length of the bytes that were written.
+    pub(super) async fn flush(
+        &self,
+        block: &Block,
+        prefix_path: &str,
+        priority: StorageRequestPriority,
+    ) -> Result<(u32, usize), BlockFlushError> {
+        let bytes = match (block.encrypted_bytes(), &self.encryption) {
+            (Some(encrypted), _) => encrypted.to_vec(),
+            (None, Some(encryption)) => match Self::encrypt(encryption, block).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::error!("Failed to encrypt block {}: {}", block.id, e);
+                    return Err(BlockFlushError::EncodeError(e));
+                }
+            },
+            (None, None) => match block.to_bytes() {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::error!("Failed to convert block to bytes");
+                    return Err(BlockFlushError::EncodeError(Box::new(e)));
+                }
+            },
         };
         let key = Self::format_key(prefix_path, &block.id);
         let block_bytes_len = bytes.len();
+        let checksum = Block::checksum_of(&bytes);
         let res = self
             .storage
-            .put_bytes(
-                &key,
-                bytes,
-                PutOptions::with_priority(StorageRequestPriority::P0),
-            )
+            .put_bytes(&key, bytes, PutOptions::with_priority(priority))
             .await;
         match res {
             Ok(_) => {
@@ -447,30 +1193,87 @@ impl BlockManager {
                     block.id,
                     block_bytes_len
                 );
+                self.metrics.block_written(block_bytes_len, priority);
             }
             Err(e) => {
                 tracing::info!("Error writing block to storage {}", e);
-                return Err(Box::new(e));
+                return Err(BlockFlushError::StorageWriteError(e));
             }
         }
-        Ok(())
+        Ok((checksum, block_bytes_len))
     }
 
     pub(super) fn max_block_size_bytes(&self) -> usize {
         self.max_block_size_bytes
     }
+
+    pub(super) fn bloom_filter_bits_per_key(&self) -> Option<usize> {
+        self.bloom_filter_bits_per_key
+    }
+
+    pub(super) fn flush_config(&self) -> &BlockFlushConfig {
+        &self.flush_config
+    }
+
+    pub(super) fn metrics(&self) -> &BlockstoreMetrics {
+        &self.metrics
+    }
+
+    /// The size below which a block is underfilled and merged with a neighbor at commit, if set
+    pub(super) fn min_block_size_bytes(&self) -> Option<usize> {
+        self.min_fill_factor
+            .map(|min_fill_factor| (min_fill_factor * self.max_block_size_bytes as f64) as usize)
+    }
+}
+
+/// Write a block file that may be memory mapped. The bytes are written to a temporary file in the
+/// same directory and renamed into place, so a mapped file is always complete and is never
+/// rewritten. A concurrent fetch of the same block replaces the file with identical bytes, which
+/// leaves existing mappings of the old file intact.
+fn write_block_file(path: &Path, bytes: &[u8]) -> Result<(), std::io::Error> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(bytes)?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
 }
 
 #[derive(Error, Debug)]
 pub enum BlockFlushError {
     #[error("Not found")]
     NotFound,
+    #[error("Error encoding for storage: {0}")]
+    EncodeError(Box<dyn ChromaError>),
+    #[error(transparent)]
+    StorageWriteError(#[from] chroma_storage::StorageError),
 }
 
 impl ChromaError for BlockFlushError {
     fn code(&self) -> ErrorCodes {
         match self {
             BlockFlushError::NotFound => ErrorCodes::NotFound,
+            BlockFlushError::EncodeError(e) => e.code(),
+            BlockFlushError::StorageWriteError(e) => e.code(),
+        }
+    }
+}
+
+impl BlockFlushError {
+    /// Whether the write may succeed if it is retried. Only storage errors that were not caused
+    /// by the request itself are, a block or root that failed to encode fails the same way again.
+    pub(super) fn is_transient(&self) -> bool {
+        match self {
+            BlockFlushError::StorageWriteError(e) => matches!(
+                e.code(),
+                ErrorCodes::Unknown
+                    | ErrorCodes::DeadlineExceeded
+                    | ErrorCodes::ResourceExhausted
+                    | ErrorCodes::Aborted
+                    | ErrorCodes::Internal
+                    | ErrorCodes::Unavailable
+            ),
+            BlockFlushError::NotFound | BlockFlushError::EncodeError(_) => false,
         }
     }
 }
@@ -511,6 +1314,8 @@ pub struct RootManager {
     storage: Storage,
     // Sparse indexes that have already been prefetched and don't need to be prefetched again.
     prefetched_roots: Arc<parking_lot::Mutex<HashMap<Uuid, Duration>>>,
+    encryption: Option<BlockEncryption>,
+    metrics: BlockstoreMetrics,
 }
 
 impl std::fmt::Debug for RootManager {
@@ -529,37 +1334,69 @@ impl RootManager {
             cache,
             storage,
             prefetched_roots: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            encryption: None,
+            metrics: BlockstoreMetrics::default(),
         }
     }
 
+    pub(super) fn with_encryption(mut self, encryption: BlockEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub(super) fn with_metrics(mut self, metrics: BlockstoreMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub async fn get<'new, K: ArrowReadableKey<'new> + 'new>(
         &self,
         id: &Uuid,
         prefix_path: &str,
+    ) -> Result<Option<RootReader>, RootManagerError> {
+        self.get_for_operation::<K>(id, prefix_path, Operation::Get, StorageRequestPriority::P0)
+            .await
+    }
+
+    /// Like `.get()`, with the operation the root is read for recorded in the metrics
+    /// and the given priority for the storage request
+    pub(super) async fn get_for_operation<'new, K: ArrowReadableKey<'new> + 'new>(
+        &self,
+        id: &Uuid,
+        prefix_path: &str,
+        operation: Operation,
+        priority: StorageRequestPriority,
     ) -> Result<Option<RootReader>, RootManagerError> {
         let index = self.cache.obtain(*id).await.ok().flatten();
+        self.metrics
+            .root_cache_lookup(index.is_some(), operation, priority);
         match index {
             Some(index) => Ok(Some(index)),
             None => {
                 tracing::info!("Cache miss - fetching root from storage");
                 let key = Self::get_storage_key(prefix_path, id);
                 tracing::debug!("Reading root from storage with key: {}", key);
-                match self
-                    .storage
-                    .get(&key, GetOptions::new(StorageRequestPriority::P0))
-                    .await
-                {
-                    Ok(bytes) => match RootReader::from_bytes::<K>(&bytes, prefix_path, *id) {
-                        Ok(root) => {
-                            self.cache.insert(*id, root.clone()).await;
-                            Ok(Some(root))
-                        }
-                        Err(e) => {
-                            tracing::error!("Error turning bytes into root: {}", e);
-                            Err(RootManagerError::FromBytesError(e))
-                        }
-                    },
-                    Err(e) => {
+                match self.storage.get(&key, GetOptions::new(priority)).await {
+                    Ok(bytes) => {
+                        self.metrics.root_fetched(bytes.len(), operation, priority);
+                        match decrypt_if_encrypted(self.encryption.as_ref(), &bytes, id)
+                            .await
+                            .map_err(RootManagerError::BlockLoadError)
+                            .and_then(|bytes| {
+                                RootReader::from_bytes::<K>(&bytes, prefix_path, *id)
+                                    .map_err(RootManagerError::FromBytesError)
+                            }) {
+                            Ok(root) => {
+                                self.cache.insert(*id, root.clone()).await;
+                                Ok(Some(root))
+                            }
+                            Err(e) => {
+                                tracing::error!("Error turning bytes into root: {}", e);
+                                Err(e)
+                            }
+                        }
+                    }
+                    Err(e) => {
                         tracing::error!("Error reading root from storage: {}", e);
                         Err(RootManagerError::StorageGetError(e))
                     }
@@ -573,15 +1410,47 @@ impl RootManager {
         id: &Uuid,
         prefix_path: &str,
     ) -> Result<Vec<Uuid>, RootManagerError> {
+        Ok(self
+            .get_all_blocks(id, prefix_path)
+            .await?
+            .into_iter()
+            .map(|(block_id, _)| block_id)
+            .collect())
+    }
+
+    /// Returns the ids of all blocks in the blockfile along with their recorded checksums
+    pub(super) async fn get_all_blocks(
+        &self,
+        id: &Uuid,
+        prefix_path: &str,
+    ) -> Result<Vec<(Uuid, Option<u32>)>, RootManagerError> {
+        self.get_all_blocks_for_operation(
+            id,
+            prefix_path,
+            Operation::Get,
+            StorageRequestPriority::P0,
+        )
+        .await
+    }
+
+    /// Like `.get_all_blocks()`, with the operation the root is read for recorded in the
+    /// metrics and the given priority for the storage request
+    pub(super) async fn get_all_blocks_for_operation(
+        &self,
+        id: &Uuid,
+        prefix_path: &str,
+        operation: Operation,
+        priority: StorageRequestPriority,
+    ) -> Result<Vec<(Uuid, Option<u32>)>, RootManagerError> {
         let key = Self::get_storage_key(prefix_path, id);
         tracing::debug!("Reading root from storage with key: {}", key);
-        match self
-            .storage
-            .get(&key, GetOptions::new(StorageRequestPriority::P0))
-            .await
-        {
-            Ok(bytes) => RootReader::get_all_block_ids_from_bytes(&bytes, *id)
-                .map_err(RootManagerError::FromBytesError),
+        match self.storage.get(&key, GetOptions::new(priority)).await {
+            Ok(bytes) => {
+                self.metrics.root_fetched(bytes.len(), operation, priority);
+                let bytes = decrypt_if_encrypted(self.encryption.as_ref(), &bytes, id).await?;
+                RootReader::get_all_blocks_from_bytes(&bytes, *id)
+                    .map_err(RootManagerError::FromBytesError)
+            }
             Err(e) => {
                 tracing::error!("Error reading root from storage: {}", e);
                 Err(RootManagerError::StorageGetError(e))
@@ -592,31 +1461,40 @@ impl RootManager {
     pub async fn flush<'read, K: ArrowWriteableKey + 'read>(
         &self,
         root: &RootWriter,
-    ) -> Result<(), Box<dyn ChromaError>> {
+        priority: StorageRequestPriority,
+    ) -> Result<(), BlockFlushError> {
         let bytes = match root.to_bytes::<K>() {
             Ok(bytes) => bytes,
             Err(e) => {
                 tracing::error!("Failed to convert root to bytes");
-                return Err(Box::new(e));
+                return Err(BlockFlushError::EncodeError(Box::new(e)));
             }
         };
+        let bytes = match &self.encryption {
+            Some(encryption) => match encryption.encrypt(&bytes, &root.id).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::error!("Failed to encrypt root {}: {}", root.id, e);
+                    return Err(BlockFlushError::EncodeError(Box::new(e)));
+                }
+            },
+            None => bytes,
+        };
         let key = Self::get_storage_key(&root.prefix_path, &root.id);
+        let root_bytes_len = bytes.len();
         let res = self
             .storage
-            .put_bytes(
-                &key,
-                bytes,
-                PutOptions::with_priority(StorageRequestPriority::P0),
-            )
+            .put_bytes(&key, bytes, PutOptions::with_priority(priority))
             .await;
         match res {
             Ok(_) => {
                 tracing::info!("Root written to storage");
+                self.metrics.root_written(root_bytes_len, priority);
                 Ok(())
             }
             Err(e) => {
                 tracing::error!("Error writing root to storage");
-                Err(Box::new(e))
+                Err(BlockFlushError::StorageWriteError(e))
             }
         }
     }
@@ -629,7 +1507,12 @@ impl RootManager {
     ) -> Result<RootWriter, RootManagerError> {
         tracing::info!("Forking root from {:?}", old_id);
         let original = self
-            .get::<K::ReadableKey<'key>>(old_id, prefix_path)
+            .get_for_operation::<K::ReadableKey<'key>>(
+                old_id,
+                prefix_path,
+                Operation::Fork,
+                StorageRequestPriority::P0,
+            )
             .await?;
         match original {
             Some(original) => {
@@ -641,11 +1524,16 @@ impl RootManager {
     }
 
     pub fn get_storage_key(prefix_path: &str, id: &Uuid) -> String {
+        format!("{}{}", Self::storage_prefix(prefix_path), id)
+    }
+
+    /// The prefix of the storage keys of all roots under `prefix_path`
+    pub fn storage_prefix(prefix_path: &str) -> String {
         // For legacy collections, prefix_path is empty.
         if prefix_path.is_empty() {
-            return format!("sparse_index/{}", id);
+            return "sparse_index/".to_string();
         }
-        format!("{}/root/{}", prefix_path, id)
+        format!("{}/root/", prefix_path)
     }
 
     fn should_prefetch(&self, id: &Uuid) -> bool {
@@ -688,8 +1576,20 @@ impl RootManager {
 mod tests {
     use super::*;
     use crate::arrow::block::delta::UnorderedBlockDelta;
+    use crate::arrow::config::TEST_MAX_BLOCK_SIZE_BYTES;
+    use crate::arrow::diff::DiffEntry;
+    use crate::arrow::encryption::EncryptionError;
     use chroma_cache::new_cache_for_test;
-    use chroma_storage::test_storage;
+    use chroma_storage::{local::LocalStorage, test_storage};
+    use opentelemetry::{metrics::MeterProvider, KeyValue};
+    use opentelemetry_sdk::{
+        metrics::{
+            data::{Histogram, Sum},
+            PeriodicReader, SdkMeterProvider,
+        },
+        runtime,
+        testing::metrics::InMemoryMetricExporter,
+    };
 
     #[tokio::test]
     async fn test_cached() {
@@ -701,4 +1601,1330 @@ mod tests {
         let block = manager.commit::<&str, String>(delta).await;
         assert!(manager.cached(&block.id).await, "should be write-through");
     }
+
+    #[tokio::test]
+    async fn test_compressed_blocks_round_trip() {
+        for compression in [
+            BlockCompression::None,
+            BlockCompression::Lz4,
+            BlockCompression::Zstd,
+        ] {
+            let (_temp_dir, storage) = test_storage();
+            let writer_manager =
+                BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test())
+                    .with_compression(compression);
+
+            let delta = writer_manager.create::<&str, String, UnorderedBlockDelta>();
+            for i in 0..100 {
+                delta.add("prefix", format!("{:04}", i).as_str(), "value".repeat(10));
+            }
+            let block = writer_manager.commit::<&str, String>(delta).await;
+            assert_eq!(block.compression(), compression);
+            writer_manager
+                .flush(&block, "", StorageRequestPriority::P0)
+                .await
+                .unwrap();
+
+            // A reader with a cold cache and a different codec must still load the block
+            let reader_manager = BlockManager::new(storage, 1024 * 1024, new_cache_for_test());
+            let loaded = reader_manager
+                .get("", &block.id, None, StorageRequestPriority::P0)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(loaded.compression(), compression);
+            assert_eq!(loaded.len(), 100);
+            assert_eq!(loaded.get_size(), block.get_size());
+            for i in 0..100 {
+                let key = format!("{:04}", i);
+                let value = loaded.get::<&str, &str>("prefix", key.as_str()).unwrap();
+                assert_eq!(value, "value".repeat(10));
+            }
+
+            let bytes = block.to_bytes().unwrap();
+            Block::from_bytes_with_validation(&bytes, block.id).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_from_storage() {
+        let (_temp_dir, storage) = test_storage();
+        let writer_manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test());
+        let delta = writer_manager.create::<&str, String, UnorderedBlockDelta>();
+        delta.add("prefix", "key", "value".to_string());
+        let block = writer_manager.commit::<&str, String>(delta).await;
+        let checksum = writer_manager
+            .flush(&block, "", StorageRequestPriority::P0)
+            .await
+            .unwrap();
+
+        // The correct checksum verifies
+        let reader_manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test());
+        reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Corrupt the stored bytes
+        let mut bytes = block.to_bytes().unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+        storage
+            .put_bytes(
+                &BlockManager::format_key("", &block.id),
+                bytes,
+                PutOptions::with_priority(StorageRequestPriority::P0),
+            )
+            .await
+            .unwrap();
+
+        let reader_manager = BlockManager::new(storage, 1024 * 1024, new_cache_for_test());
+        let result = reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await;
+        match result {
+            Err(e @ GetError::ChecksumMismatch(_)) => assert_eq!(e.code(), ErrorCodes::DataLoss),
+            _ => panic!("Expected a checksum mismatch"),
+        }
+        assert!(!reader_manager.cached(&block.id).await);
+    }
+
+    #[tokio::test]
+    async fn test_mmap_dir() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let storage = Storage::Local(LocalStorage::new(storage_dir.path().to_str().unwrap()));
+        let writer_manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test());
+        let delta = writer_manager.create::<&str, String, UnorderedBlockDelta>();
+        for i in 0..1000 {
+            delta.add(
+                "prefix",
+                format!("{:04}", i).as_str(),
+                format!("value{}", i),
+            );
+        }
+        let block = writer_manager.commit::<&str, String>(delta).await;
+        let checksum = writer_manager
+            .flush(&block, "", StorageRequestPriority::P0)
+            .await
+            .unwrap();
+        let assert_readable = |loaded: Block| {
+            assert_eq!(loaded.len(), 1000);
+            for i in 0..1000 {
+                let key = format!("{:04}", i);
+                let value = loaded.get::<&str, &str>("prefix", key.as_str()).unwrap();
+                assert_eq!(value, format!("value{}", i));
+            }
+        };
+
+        // Blocks of a local storage are mapped where they are stored
+        let reader_manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test())
+            .with_mmap_dir(storage_dir.path().to_path_buf());
+        let loaded = reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_readable(loaded);
+
+        // Otherwise blocks are written to the directory on the first fetch
+        let mmap_dir = tempfile::tempdir().unwrap();
+        let reader_manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test())
+            .with_mmap_dir(mmap_dir.path().to_path_buf());
+        let loaded = reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_readable(loaded);
+        let path = mmap_dir
+            .path()
+            .join(BlockManager::format_key("", &block.id));
+        assert!(path.exists());
+
+        // and later fetches are served from the directory
+        storage
+            .delete(&BlockManager::format_key("", &block.id))
+            .await
+            .unwrap();
+        let reader_manager = BlockManager::new(storage, 1024 * 1024, new_cache_for_test())
+            .with_mmap_dir(mmap_dir.path().to_path_buf());
+        let loaded = reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_readable(loaded);
+
+        // A corrupted file fails checksum verification
+        let mut bytes = std::fs::read(&path).unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 0xff;
+        write_block_file(&path, &bytes).unwrap();
+        let reader_manager = BlockManager::new(
+            Storage::Local(LocalStorage::new(storage_dir.path().to_str().unwrap())),
+            1024 * 1024,
+            new_cache_for_test(),
+        )
+        .with_mmap_dir(mmap_dir.path().to_path_buf());
+        let result = reader_manager
+            .get("", &block.id, Some(checksum), StorageRequestPriority::P0)
+            .await;
+        assert!(matches!(result, Err(GetError::ChecksumMismatch(_))));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_cache_entry_is_evicted() {
+        let (_temp_dir, storage) = test_storage();
+        let manager = BlockManager::new(storage, 1024 * 1024, new_cache_for_test());
+        let delta = manager.create::<&str, String, UnorderedBlockDelta>();
+        delta.add("prefix", "key", "value".to_string());
+        let block = manager.commit::<&str, String>(delta).await;
+
+        // An intact cache entry round trips
+        let serialized = bincode::serialize(&block).unwrap();
+        let deserialized: Block = bincode::deserialize(&serialized).unwrap();
+        assert!(!deserialized.is_corrupted());
+        assert_eq!(deserialized.len(), 1);
+
+        // Flip a byte in the serialized record batch, as bit-rot in a disk cache would
+        let mut serialized = serialized;
+        let mid = serialized.len() / 2;
+        serialized[mid] ^= 0xff;
+        let corrupted: Block = bincode::deserialize(&serialized).unwrap();
+        assert!(corrupted.is_corrupted());
+
+        manager.block_cache.insert(block.id, corrupted).await;
+        let result = manager
+            .get("", &block.id, None, StorageRequestPriority::P0)
+            .await;
+        match result {
+            Err(e @ GetError::ChecksumMismatch(_)) => assert_eq!(e.code(), ErrorCodes::DataLoss),
+            _ => panic!("Expected a checksum mismatch"),
+        }
+        assert!(!manager.cached(&block.id).await, "should be evicted");
+    }
+
+    #[tokio::test]
+    async fn test_flush_records_checksums_in_root() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let blocks = provider.root_manager.get_all_blocks(&id, "").await.unwrap();
+        assert!(blocks.len() > 1);
+        for (block_id, checksum) in blocks {
+            let block = provider
+                .block_manager
+                .get("", &block_id, checksum, StorageRequestPriority::P0)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                checksum,
+                Some(Block::checksum_of(&block.to_bytes().unwrap()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filters_skip_block_fetches() {
+        let (_temp_dir, storage) = test_storage();
+        let writer_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_bloom_filters(20);
+        let writer = writer_provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Read through a provider with cold caches so fetched blocks are observable
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let reader = match provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap()
+        {
+            BlockfileReader::ArrowBlockfileReader(reader) => reader,
+            _ => panic!("Expected an arrow reader"),
+        };
+        let blocks = provider.root_manager.get_all_blocks(&id, "").await.unwrap();
+        assert!(blocks.len() > 1);
+
+        for i in 0..10 {
+            let key = format!("{:04}a", i * 100);
+            assert_eq!(reader.get("prefix", key.as_str()).await.unwrap(), None);
+            assert!(!reader.contains("prefix", key.as_str()).await.unwrap());
+        }
+        for (block_id, _) in blocks.iter() {
+            assert!(!provider.block_manager.cached(block_id).await);
+        }
+
+        assert_eq!(reader.get("prefix", "0500").await.unwrap(), Some("value"));
+        assert!(reader.contains("prefix", "0999").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_merge() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        // Three blockfiles with interleaved keys, keys divisible by 10 are in every blockfile
+        let mut ids = Vec::new();
+        for shard in 0..3 {
+            let writer = provider
+                .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+                .await
+                .unwrap();
+            for i in (0..1000).filter(|i| i % 3 == shard || i % 10 == 0) {
+                let key = format!("{:04}", i);
+                writer
+                    .set("prefix", key.as_str(), format!("{}/{}", shard, i))
+                    .await
+                    .unwrap();
+            }
+            ids.push(writer.id());
+            let flusher = writer.commit::<&str, String>().await.unwrap();
+            flusher.flush::<&str, String>().await.unwrap();
+        }
+
+        let mut readers = Vec::new();
+        for id in ids.iter() {
+            readers.push(
+                provider
+                    .read::<&str, &str>(BlockfileReaderOptions::new(*id, "".to_string()))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        match provider
+            .merge::<&str, String>(&readers, MergeDuplicatePolicy::Error, "")
+            .await
+        {
+            Err(MergeError::DuplicateKey { prefix, key }) => {
+                assert_eq!(prefix, "prefix");
+                assert_eq!(key, "0000");
+            }
+            _ => panic!("Expected a duplicate key error"),
+        }
+
+        let flusher = provider
+            .merge::<&str, String>(&readers, MergeDuplicatePolicy::LastWins, "")
+            .await
+            .unwrap();
+        let merged_id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let merged = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(merged_id, "".to_string()))
+            .await
+            .unwrap();
+        let values = merged.get_range(.., ..).await.unwrap();
+        assert_eq!(values.len(), 1000);
+        for (i, (prefix, key, value)) in values.into_iter().enumerate() {
+            assert_eq!(prefix, "prefix");
+            assert_eq!(key, format!("{:04}", i));
+            let shard = if i % 10 == 0 { 2 } else { i % 3 };
+            assert_eq!(value, format!("{}/{}", shard, i));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_merge_duplicate_writes_nothing() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+
+        // Only the last key is in both blockfiles
+        let mut readers = Vec::new();
+        for keys in [0..1000, 999..1001] {
+            let writer = provider
+                .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+                .await
+                .unwrap();
+            for i in keys {
+                let key = format!("{:04}", i);
+                writer
+                    .set("prefix", key.as_str(), "value".to_string())
+                    .await
+                    .unwrap();
+            }
+            let id = writer.id();
+            let flusher = writer.commit::<&str, String>().await.unwrap();
+            flusher.flush::<&str, String>().await.unwrap();
+            readers.push(
+                provider
+                    .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let writer = ArrowOrderedBlockfileWriter::new::<&str, String>(
+            Uuid::new_v4(),
+            "",
+            provider.block_manager.clone(),
+            provider.root_manager.clone(),
+        );
+        match merge_into::<&str, String>(&readers, MergeDuplicatePolicy::Error, &writer).await {
+            Err(MergeError::DuplicateKey { key, .. }) => assert_eq!(key, "0999"),
+            _ => panic!("Expected a duplicate key error"),
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        assert_eq!(flusher.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_merge_empty_memory_blockfile() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        writer
+            .set("prefix", "key", "value".to_string())
+            .await
+            .unwrap();
+        let id = writer.id();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let memory_provider = crate::provider::BlockfileProvider::new_memory();
+        let memory_writer = memory_provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let memory_id = memory_writer.id();
+        let memory_flusher = memory_writer.commit::<&str, String>().await.unwrap();
+        memory_flusher.flush::<&str, String>().await.unwrap();
+
+        let readers = vec![
+            provider
+                .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+                .await
+                .unwrap(),
+            memory_provider
+                .read::<&str, &str>(BlockfileReaderOptions::new(memory_id, "".to_string()))
+                .await
+                .unwrap(),
+        ];
+        let flusher = provider
+            .merge::<&str, String>(&readers, MergeDuplicatePolicy::Error, "")
+            .await
+            .unwrap();
+        let merged_id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+        let merged = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(merged_id, "".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            merged.get_range(.., ..).await.unwrap(),
+            vec![("prefix", "key", "value")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_fork() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let base_id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Only touch the start of the key range so that later blocks stay shared
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()).fork(base_id))
+            .await
+            .unwrap();
+        let fork_id = writer.id();
+        writer
+            .set("prefix", "0001", "changed".to_string())
+            .await
+            .unwrap();
+        writer
+            .set("prefix", "0002a", "added".to_string())
+            .await
+            .unwrap();
+        writer
+            .delete::<&str, String>("prefix", "0003")
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        provider.clear().await.unwrap();
+        let diff = provider
+            .diff::<&str, &str>(&base_id, &fork_id, "")
+            .await
+            .unwrap();
+        let shared_block_ids = diff.shared_block_ids();
+        assert!(!shared_block_ids.is_empty());
+        assert_eq!(
+            diff.changes().await.unwrap(),
+            vec![
+                DiffEntry::Changed {
+                    prefix: "prefix",
+                    key: "0001",
+                    old_value: "value",
+                    new_value: "changed",
+                },
+                DiffEntry::Added {
+                    prefix: "prefix",
+                    key: "0002a",
+                    value: "added",
+                },
+                DiffEntry::Removed {
+                    prefix: "prefix",
+                    key: "0003",
+                    value: "value",
+                },
+            ]
+        );
+        for block_id in shared_block_ids {
+            assert!(!provider.block_manager.cached(&block_id).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parquet_export_import() {
+        let (temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, Vec<u32>>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000u32 {
+            let key = format!("{:04}", i);
+            writer
+                .set(&format!("prefix{}", i % 2), key.as_str(), vec![i, i + 1])
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, Vec<u32>>().await.unwrap();
+        flusher.flush::<&str, Vec<u32>>().await.unwrap();
+
+        let path = temp_dir.path().join("export.parquet");
+        let exported = provider
+            .export_parquet::<&str>(&id, "", &path)
+            .await
+            .unwrap();
+        assert_eq!(exported, 1000);
+
+        let source = ParquetBlockfileSource::open(&path).unwrap();
+        assert_eq!(source.len(), 1000);
+        assert!(matches!(
+            provider.import_parquet::<u32, Vec<u32>>(&source, "").await,
+            Err(ParquetBlockfileError::InvalidSchema(_))
+        ));
+        assert!(matches!(
+            provider.import_parquet::<&str, String>(&source, "").await,
+            Err(ParquetBlockfileError::InvalidSchema(_))
+        ));
+        let flusher = provider
+            .import_parquet::<&str, Vec<u32>>(&source, "")
+            .await
+            .unwrap();
+        let imported_id = flusher.id();
+        flusher.flush::<&str, Vec<u32>>().await.unwrap();
+
+        let original = provider
+            .read::<&str, &[u32]>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap();
+        let imported = provider
+            .read::<&str, &[u32]>(BlockfileReaderOptions::new(imported_id, "".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(
+            original.get_range(.., ..).await.unwrap(),
+            imported.get_range(.., ..).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parquet_import_rejects_nullable_values() {
+        use arrow::array::{StringArray, UInt32Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+        use arrow::record_batch::RecordBatch;
+
+        let (temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("prefix", DataType::Utf8, false),
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value", DataType::UInt32, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["prefix", "prefix"])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(UInt32Array::from(vec![Some(1), None])),
+            ],
+        )
+        .unwrap();
+        let path = temp_dir.path().join("nullable.parquet");
+        let mut writer = parquet::arrow::ArrowWriter::try_new(
+            std::fs::File::create(&path).unwrap(),
+            schema,
+            None,
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let source = ParquetBlockfileSource::open(&path).unwrap();
+        assert_eq!(source.len(), 2);
+        assert!(matches!(
+            provider.import_parquet::<&str, u32>(&source, "").await,
+            Err(ParquetBlockfileError::InvalidSchema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_find_and_delete_orphans() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let prefix_path = "tenant/database/collection";
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(prefix_path.to_string()))
+            .await
+            .unwrap();
+        let base_id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let writer = provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new(prefix_path.to_string()).fork(base_id),
+            )
+            .await
+            .unwrap();
+        let fork_id = writer.id();
+        writer
+            .set("prefix", "0001", "changed".to_string())
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Only the fork is live, so the base root and the blocks it no longer shares are orphans
+        let live_root_ids = HashSet::from([fork_id]);
+        let base_block_ids = provider
+            .root_manager
+            .get_all_block_ids(&base_id, prefix_path)
+            .await
+            .unwrap();
+        let fork_block_ids = provider
+            .root_manager
+            .get_all_block_ids(&fork_id, prefix_path)
+            .await
+            .unwrap();
+        let mut expected_orphans = base_block_ids
+            .into_iter()
+            .filter(|id| !fork_block_ids.contains(id))
+            .collect::<Vec<_>>();
+        expected_orphans.sort();
+        assert!(!expected_orphans.is_empty());
+
+        let scan = provider
+            .find_orphans(prefix_path, &live_root_ids)
+            .await
+            .unwrap();
+        assert_eq!(scan.orphaned_root_ids, vec![base_id]);
+        assert_eq!(scan.orphaned_block_ids, expected_orphans);
+
+        assert!(matches!(
+            provider
+                .delete_orphans(&scan, &live_root_ids, Duration::from_secs(3600))
+                .await,
+            Err(OrphanError::GracePeriodNotElapsed)
+        ));
+        let deleted = provider
+            .delete_orphans(&scan, &live_root_ids, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(deleted.orphaned_root_ids, scan.orphaned_root_ids);
+        assert_eq!(deleted.orphaned_block_ids, scan.orphaned_block_ids);
+        assert!(provider
+            .find_orphans(prefix_path, &live_root_ids)
+            .await
+            .unwrap()
+            .is_empty());
+
+        provider.clear().await.unwrap();
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(
+                fork_id,
+                prefix_path.to_string(),
+            ))
+            .await
+            .unwrap();
+        let values = reader.get_range(.., ..).await.unwrap();
+        assert_eq!(values.len(), 1000);
+        assert_eq!(values[1].2, "changed");
+    }
+
+    #[tokio::test]
+    async fn test_orphans_require_prefix_path() {
+        let (_temp_dir, storage) = test_storage();
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        writer
+            .set("prefix", "key", "value".to_string())
+            .await
+            .unwrap();
+        let id = writer.id();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let result = provider.find_orphans("", &HashSet::new()).await;
+        match result {
+            Err(e @ OrphanError::EmptyPrefixPath) => {
+                assert_eq!(e.code(), ErrorCodes::InvalidArgument)
+            }
+            _ => panic!("Expected an empty prefix path error"),
+        }
+
+        // A scan of the global namespace is rejected as well
+        let scan = OrphanScan {
+            prefix_path: "".to_string(),
+            scanned_at: UNIX_EPOCH,
+            orphaned_block_ids: vec![],
+            orphaned_root_ids: vec![id],
+        };
+        assert!(matches!(
+            provider
+                .delete_orphans(&scan, &HashSet::new(), Duration::ZERO)
+                .await,
+            Err(OrphanError::EmptyPrefixPath)
+        ));
+        provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_scope() {
+        let (_temp_dir, storage) = test_storage();
+        let writer_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = writer_provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for prefix in ["a", "b"] {
+            for i in 0..1000 {
+                let key = format!("{:04}", i);
+                writer
+                    .set(prefix, key.as_str(), "value".to_string())
+                    .await
+                    .unwrap();
+            }
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Prefetch through a provider with cold caches so fetched blocks are observable
+        let provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let blocks = provider.root_manager.get_all_blocks(&id, "").await.unwrap();
+        assert!(blocks.len() > 2);
+
+        let (sender, receiver) = tokio::sync::watch::channel(PrefetchProgress::default());
+        let progress = provider
+            .prefetch_scope::<&str>(
+                &id,
+                "",
+                &PrefetchScope::key_range("a", "0100".."0200"),
+                PrefetchOptions::new().with_progress(sender),
+            )
+            .await
+            .unwrap();
+        assert!(progress.total_blocks > 0);
+        assert!(progress.total_blocks < blocks.len());
+        assert_eq!(progress.fetched_blocks, progress.total_blocks);
+        assert_eq!(progress.cached_blocks, 0);
+        assert_eq!(*receiver.borrow(), progress);
+        let mut cached = 0;
+        for (block_id, _) in blocks.iter() {
+            if provider.block_manager.cached(block_id).await {
+                cached += 1;
+            }
+        }
+        assert_eq!(cached, progress.total_blocks);
+
+        // Blocks that are already cached are counted but not fetched again
+        let progress = provider
+            .prefetch_scope::<&str>(
+                &id,
+                "",
+                &PrefetchScope::prefixes(["a"]),
+                PrefetchOptions::new().with_priority(StorageRequestPriority::P0),
+            )
+            .await
+            .unwrap();
+        assert!(progress.total_blocks < blocks.len());
+        assert_eq!(progress.cached_blocks, cached);
+        assert_eq!(progress.fetched_blocks, progress.total_blocks);
+
+        // A cancelled prefetch stops before fetching anything
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        cancellation_token.cancel();
+        let result = provider
+            .prefetch_scope::<&str>(
+                &id,
+                "",
+                &PrefetchScope::prefixes(["b"]),
+                PrefetchOptions::new().with_cancellation_token(cancellation_token),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ArrowBlockfileProviderPrefetchError::Cancelled)
+        ));
+        for (block_id, _) in blocks.iter() {
+            assert!(!provider.block_manager.cached(block_id).await);
+        }
+    }
+    #[tokio::test]
+    async fn test_encryption_at_rest() {
+        let (_temp_dir, storage) = test_storage();
+        let key_dir = tempfile::tempdir().unwrap();
+        std::fs::write(key_dir.path().join("key1"), [7u8; 32]).unwrap();
+        let key_provider: Arc<dyn KeyProvider> =
+            Arc::new(LocalFileKeyProvider::new(key_dir.path(), "key1"));
+        let provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_encryption(key_provider.clone(), false);
+
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000 {
+            let key = format!("secret{:04}", i);
+            writer
+                .set("prefix", key.as_str(), format!("value{}", i))
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Only ciphertext is written to storage
+        let blocks = provider.root_manager.get_all_blocks(&id, "").await.unwrap();
+        assert!(blocks.len() > 1);
+        let mut keys = vec![RootManager::get_storage_key("", &id)];
+        keys.extend(
+            blocks
+                .iter()
+                .map(|(block_id, _)| BlockManager::format_key("", block_id)),
+        );
+        for key in keys.iter() {
+            let bytes = storage
+                .get(key, GetOptions::new(StorageRequestPriority::P0))
+                .await
+                .unwrap();
+            assert!(crate::arrow::encryption::is_encrypted(&bytes));
+            assert!(!bytes.windows(6).any(|window| window == b"secret"));
+        }
+
+        // Only ciphertext is written to the disk cache
+        let (block_id, checksum) = blocks[0];
+        let block = provider
+            .block_manager
+            .get("", &block_id, checksum, StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+        let serialized = bincode::serialize(&block).unwrap();
+        assert!(!serialized.windows(6).any(|window| window == b"secret"));
+        let sealed: Block = bincode::deserialize(&serialized).unwrap();
+        assert!(sealed.is_sealed());
+
+        // A sealed cache entry is decrypted on read
+        let manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test())
+            .with_encryption(BlockEncryption::new(key_provider.clone()));
+        manager.block_cache.insert(block_id, sealed.clone()).await;
+        let unsealed = manager
+            .get("", &block_id, None, StorageRequestPriority::P0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!unsealed.is_sealed());
+        assert_eq!(unsealed.len(), block.len());
+        // Caching the decrypted block again writes the same ciphertext to the disk cache
+        assert_eq!(bincode::serialize(&unsealed).unwrap(), serialized);
+
+        // A provider with the key and cold caches reads the blockfile
+        let reader_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_encryption(key_provider.clone(), false);
+        let reader = reader_provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap();
+        for i in 0..1000 {
+            let key = format!("secret{:04}", i);
+            let value = reader.get("prefix", key.as_str()).await.unwrap();
+            assert_eq!(value, Some(format!("value{}", i).as_str()));
+        }
+
+        // Without a key provider, encrypted blocks and roots fail to load
+        let manager = BlockManager::new(storage.clone(), 1024 * 1024, new_cache_for_test());
+        let result = manager
+            .get("", &block_id, None, StorageRequestPriority::P0)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GetError::BlockLoadError(BlockLoadError::Encrypted))
+        ));
+        manager.block_cache.insert(block_id, sealed).await;
+        let result = manager
+            .get("", &block_id, None, StorageRequestPriority::P0)
+            .await;
+        assert!(matches!(
+            result,
+            Err(GetError::BlockLoadError(BlockLoadError::Encrypted))
+        ));
+        assert!(!manager.cached(&block_id).await, "should be evicted");
+        let root_manager = RootManager::new(storage.clone(), new_cache_for_test());
+        assert!(matches!(
+            root_manager.get::<&str>(&id, "").await,
+            Err(RootManagerError::BlockLoadError(BlockLoadError::Encrypted))
+        ));
+
+        // A provider with a different key under the same id cannot decrypt
+        let other_key_dir = tempfile::tempdir().unwrap();
+        std::fs::write(other_key_dir.path().join("key1"), [8u8; 32]).unwrap();
+        let manager = BlockManager::new(storage, 1024 * 1024, new_cache_for_test())
+            .with_encryption(BlockEncryption::new(Arc::new(LocalFileKeyProvider::new(
+                other_key_dir.path(),
+                "key1",
+            ))));
+        let result = manager
+            .get("", &block_id, None, StorageRequestPriority::P0)
+            .await;
+        match result {
+            Err(e @ GetError::BlockLoadError(BlockLoadError::DecryptionError(_))) => {
+                assert_eq!(e.code(), ErrorCodes::DataLoss)
+            }
+            _ => panic!("Expected a decryption error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encryption_rejects_plaintext() {
+        let (_temp_dir, storage) = test_storage();
+        let key_dir = tempfile::tempdir().unwrap();
+        std::fs::write(key_dir.path().join("key1"), [7u8; 32]).unwrap();
+        let key_provider: Arc<dyn KeyProvider> =
+            Arc::new(LocalFileKeyProvider::new(key_dir.path(), "key1"));
+        let new_provider = |allow_plaintext| {
+            ArrowBlockfileProvider::new(
+                storage.clone(),
+                TEST_MAX_BLOCK_SIZE_BYTES,
+                new_cache_for_test(),
+                new_cache_for_test(),
+            )
+            .with_encryption(key_provider.clone(), allow_plaintext)
+        };
+
+        // A blockfile written before encryption was enabled
+        let plaintext_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        );
+        let writer = plaintext_provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        writer
+            .set("prefix", "key", "value".to_string())
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let provider = new_provider(false);
+        let result = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await;
+        assert!(result.is_err());
+        let result = provider.root_manager.get::<&str>(&id, "").await;
+        assert!(matches!(
+            result,
+            Err(RootManagerError::BlockLoadError(
+                BlockLoadError::DecryptionError(EncryptionError::Plaintext)
+            ))
+        ));
+
+        // Plain text is read while it is allowed for a migration
+        let migrating_provider = new_provider(true);
+        let reader = migrating_provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reader.get("prefix", "key").await.unwrap(), Some("value"));
+
+        // Writers cannot spill deltas to disk in plain text
+        let result = provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new("".to_string()).memory_budget_bytes(1024 * 1024),
+            )
+            .await;
+        assert!(matches!(result, Err(e) if e.code() == ErrorCodes::InvalidArgument));
+    }
+
+    fn test_meter_provider() -> (SdkMeterProvider, InMemoryMetricExporter) {
+        let exporter = InMemoryMetricExporter::default();
+        let reader = PeriodicReader::builder(exporter.clone(), runtime::TokioCurrentThread).build();
+        let meter_provider = SdkMeterProvider::builder().with_reader(reader).build();
+        (meter_provider, exporter)
+    }
+
+    /// The operation, priority and value of every data point of a metric after the meter
+    /// provider is flushed. The value of a histogram data point is its count.
+    fn data_points(
+        meter_provider: &SdkMeterProvider,
+        exporter: &InMemoryMetricExporter,
+        name: &str,
+    ) -> Vec<(String, String, u64)> {
+        meter_provider.force_flush().unwrap();
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metric = match exported
+            .last()
+            .into_iter()
+            .flat_map(|resource_metrics| resource_metrics.scope_metrics.iter())
+            .flat_map(|scope_metrics| scope_metrics.metrics.iter())
+            .find(|metric| metric.name == name)
+        {
+            Some(metric) => metric,
+            None => return Vec::new(),
+        };
+        let labeled = |attributes: &[KeyValue], value: u64| {
+            let label = |key: &str| {
+                attributes
+                    .iter()
+                    .find(|attribute| attribute.key.as_str() == key)
+                    .map(|attribute| attribute.value.as_str().to_string())
+                    .unwrap()
+            };
+            (label("operation"), label("priority"), value)
+        };
+        let data = metric.data.as_any();
+        if let Some(sum) = data.downcast_ref::<Sum<u64>>() {
+            sum.data_points
+                .iter()
+                .map(|point| labeled(&point.attributes, point.value))
+                .collect()
+        } else if let Some(histogram) = data.downcast_ref::<Histogram<u64>>() {
+            histogram
+                .data_points
+                .iter()
+                .map(|point| labeled(&point.attributes, point.count))
+                .collect()
+        } else {
+            panic!("Unexpected aggregation for {}", name);
+        }
+    }
+
+    /// The operation and priority of every data point, sorted
+    fn labels(points: &[(String, String, u64)]) -> Vec<(&str, &str)> {
+        let mut labels = points
+            .iter()
+            .map(|(operation, priority, _)| (operation.as_str(), priority.as_str()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
+
+    #[tokio::test]
+    async fn test_metrics_record_operation_and_priority() {
+        let (_temp_dir, storage) = test_storage();
+        let (meter_provider, exporter) = test_meter_provider();
+        let meter = meter_provider.meter("chroma");
+        let writer_provider = ArrowBlockfileProvider::new(
+            storage.clone(),
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_meter(&meter);
+        let writer = writer_provider
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        // Writes are recorded with the priority they were made at
+        let written = data_points(&meter_provider, &exporter, "blockstore_block_bytes_written");
+        assert_eq!(labels(&written), vec![("flush", "P0")]);
+        let written = data_points(&meter_provider, &exporter, "blockstore_root_bytes_written");
+        assert_eq!(labels(&written), vec![("flush", "P0")]);
+        let flushes = data_points(&meter_provider, &exporter, "blockstore_blocks_per_flush");
+        assert_eq!(flushes, vec![("flush".to_string(), "P0".to_string(), 1)]);
+
+        // Prefetch and read through a provider with cold caches
+        let provider = ArrowBlockfileProvider::new(
+            storage,
+            TEST_MAX_BLOCK_SIZE_BYTES,
+            new_cache_for_test(),
+            new_cache_for_test(),
+        )
+        .with_meter(&meter);
+        let num_blocks = provider.prefetch(&id, "").await.unwrap();
+        assert!(num_blocks > 1);
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, "".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(reader.get("prefix", "0000").await.unwrap(), Some("value"));
+
+        // The root read by the prefetch is not recorded as a get
+        let fetched = data_points(&meter_provider, &exporter, "blockstore_root_bytes_fetched");
+        assert_eq!(labels(&fetched), vec![("get", "P0"), ("prefetch", "P1")]);
+        let fetched = data_points(&meter_provider, &exporter, "blockstore_block_bytes_fetched");
+        assert_eq!(labels(&fetched), vec![("prefetch", "P1")]);
+        let deserializations = data_points(
+            &meter_provider,
+            &exporter,
+            "blockstore_block_deserialization_latency",
+        );
+        assert_eq!(
+            deserializations,
+            vec![("prefetch".to_string(), "P1".to_string(), num_blocks as u64)]
+        );
+        let hits = data_points(&meter_provider, &exporter, "blockstore_block_cache_hit");
+        assert_eq!(hits, vec![("get".to_string(), "P0".to_string(), 1)]);
+
+        // Blocks in plain text are not decrypted
+        assert!(data_points(
+            &meter_provider,
+            &exporter,
+            "blockstore_block_decryption_latency"
+        )
+        .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_time_decryption() {
+        let (_temp_dir, storage) = test_storage();
+        let key_dir = tempfile::tempdir().unwrap();
+        std::fs::write(key_dir.path().join("key1"), [7u8; 32]).unwrap();
+        let key_provider: Arc<dyn KeyProvider> =
+            Arc::new(LocalFileKeyProvider::new(key_dir.path(), "key1"));
+        let (meter_provider, exporter) = test_meter_provider();
+        let meter = meter_provider.meter("chroma");
+        let new_provider = || {
+            ArrowBlockfileProvider::new(
+                storage.clone(),
+                TEST_MAX_BLOCK_SIZE_BYTES,
+                new_cache_for_test(),
+                new_cache_for_test(),
+            )
+            .with_encryption(key_provider.clone(), false)
+            .with_meter(&meter)
+        };
+        let writer = new_provider()
+            .write::<&str, String>(BlockfileWriterOptions::new("".to_string()))
+            .await
+            .unwrap();
+        let id = writer.id();
+        for i in 0..1000 {
+            let key = format!("{:04}", i);
+            writer
+                .set("prefix", key.as_str(), "value".to_string())
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let num_blocks = new_provider().prefetch(&id, "").await.unwrap();
+        assert!(num_blocks > 1);
+        let expected = vec![("prefetch".to_string(), "P1".to_string(), num_blocks as u64)];
+        assert_eq!(
+            data_points(
+                &meter_provider,
+                &exporter,
+                "blockstore_block_decryption_latency"
+            ),
+            expected
+        );
+        assert_eq!(
+            data_points(
+                &meter_provider,
+                &exporter,
+                "blockstore_block_deserialization_latency"
+            ),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_min_block_fill_factor_is_validated() {
+        let registry = Registry::new();
+        for min_fill_factor in [0.0, 1.0, -0.5, 1.5, f64::NAN] {
+            let (_temp_dir, storage) = test_storage();
+            let mut config = ArrowBlockfileProviderConfig::default();
+            config.block_manager_config.min_block_fill_factor = Some(min_fill_factor);
+            let result =
+                ArrowBlockfileProvider::try_from_config(&(config, storage), &registry).await;
+            match result {
+                Err(e) => assert_eq!(e.code(), ErrorCodes::InvalidArgument),
+                Ok(_) => panic!("Expected {} to be rejected", min_fill_factor),
+            }
+        }
+
+        let (_temp_dir, storage) = test_storage();
+        let mut config = ArrowBlockfileProviderConfig::default();
+        config.block_manager_config.min_block_fill_factor = Some(0.5);
+        let provider = ArrowBlockfileProvider::try_from_config(&(config, storage), &registry)
+            .await
+            .unwrap();
+        assert_eq!(
+            provider.block_manager.min_block_size_bytes(),
+            Some(provider.block_manager.max_block_size_bytes() / 2)
+        );
+    }
 }
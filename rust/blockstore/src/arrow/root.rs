@@ -1,13 +1,16 @@
 use super::{
     block::{Block, BlockToBytesError},
-    sparse_index::{SparseIndexReader, SparseIndexValue, SparseIndexWriter, SparseIndexWriterData},
+    bloom_filter::BlockBloomFilter,
+    sparse_index::{
+        BlockStats, SparseIndexReader, SparseIndexValue, SparseIndexWriter, SparseIndexWriterData,
+    },
     types::{ArrowReadableKey, ArrowWriteableKey},
 };
 use crate::{arrow::sparse_index::SparseIndexDelimiter, key::CompositeKey};
 use arrow::{
     array::{
         Array, BinaryArray, BinaryBuilder, RecordBatch, StringArray, StringBuilder, UInt32Array,
-        UInt32Builder,
+        UInt32Builder, UInt64Array, UInt64Builder,
     },
     datatypes::{DataType, Field, Schema},
 };
@@ -16,12 +19,20 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashMap},
     fmt::Display,
+    ops::RangeBounds,
     sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
 use uuid::Uuid;
 
-pub(super) const CURRENT_VERSION: Version = Version::V1_1;
+pub(super) const CURRENT_VERSION: Version = Version::V2;
+const CHECKSUM_COLUMN_NAME: &str = "checksum";
+const BLOOM_FILTER_COLUMN_NAME: &str = "bloom_filter";
+const LAST_PREFIX_COLUMN_NAME: &str = "last_prefix";
+const LAST_KEY_COLUMN_NAME: &str = "last_key";
+const SIZE_BYTES_COLUMN_NAME: &str = "size_bytes";
+const CREATED_AT_COLUMN_NAME: &str = "created_at";
 
 // ================
 // Version
@@ -31,6 +42,7 @@ pub(super) const CURRENT_VERSION: Version = Version::V1_1;
 pub(crate) enum Version {
     V1 = 1,
     V1_1 = 2,
+    V2 = 3,
 }
 
 impl Display for Version {
@@ -38,6 +50,7 @@ impl Display for Version {
         match self {
             Version::V1 => write!(f, "v1"),
             Version::V1_1 => write!(f, "v1.1"),
+            Version::V2 => write!(f, "v2"),
         }
     }
 }
@@ -60,6 +73,7 @@ impl TryFrom<&str> for Version {
         match s {
             "v1" => Ok(Version::V1),
             "v1.1" => Ok(Version::V1_1),
+            "v2" => Ok(Version::V2),
             _ => Err(VersionError::UnknownVersion(s.to_string())),
         }
     }
@@ -131,6 +145,89 @@ impl RootWriter {
         )
     }
 
+    fn checksums_as_arrow(
+        &self,
+        sparse_index_data: &SparseIndexWriterData,
+    ) -> (Arc<dyn Array>, Field) {
+        let mut checksum_builder = UInt32Builder::new();
+        // Blocks that were never flushed with a checksum (e.g. inherited from a root written
+        // before checksums existed) are stored as null and are not verified on load
+        for (_, block_id) in sparse_index_data.forward.iter() {
+            checksum_builder.append_option(sparse_index_data.checksums.get(block_id).copied());
+        }
+        (
+            Arc::new(checksum_builder.finish()),
+            Field::new(CHECKSUM_COLUMN_NAME, DataType::UInt32, true),
+        )
+    }
+
+    fn bloom_filters_as_arrow(
+        &self,
+        sparse_index_data: &SparseIndexWriterData,
+    ) -> (Arc<dyn Array>, Field) {
+        let mut bloom_filter_builder = BinaryBuilder::new();
+        for (_, block_id) in sparse_index_data.forward.iter() {
+            match sparse_index_data.bloom_filters.get(block_id) {
+                Some(bloom_filter) => bloom_filter_builder.append_value(bloom_filter.to_bytes()),
+                None => bloom_filter_builder.append_null(),
+            }
+        }
+        (
+            Arc::new(bloom_filter_builder.finish()),
+            Field::new(BLOOM_FILTER_COLUMN_NAME, DataType::Binary, true),
+        )
+    }
+
+    fn stats_as_arrow<K: ArrowWriteableKey>(
+        &self,
+        sparse_index_data: &SparseIndexWriterData,
+    ) -> (Vec<Field>, Vec<Arc<dyn Array>>) {
+        let mut prefix_cap = 0;
+        let mut key_cap = 0;
+        for stats in sparse_index_data.stats.values() {
+            prefix_cap += stats.last_key.prefix.len();
+            key_cap += stats.last_key.key.get_size();
+        }
+        let mut last_key_builder =
+            K::get_arrow_builder(sparse_index_data.forward.len(), prefix_cap, key_cap);
+        let mut size_bytes_builder = UInt64Builder::new();
+        let mut created_at_builder = UInt64Builder::new();
+        for (_, block_id) in sparse_index_data.forward.iter() {
+            match sparse_index_data.stats.get(block_id) {
+                Some(stats) => {
+                    last_key_builder.add_key(stats.last_key.clone());
+                    size_bytes_builder.append_value(stats.size_bytes);
+                    created_at_builder.append_option(stats.created_at);
+                }
+                None => {
+                    // Blocks without statistics (e.g. empty blocks) store a placeholder last key
+                    // since the key columns are not nullable, readers check the size column
+                    last_key_builder.add_key(CompositeKey {
+                        prefix: String::new(),
+                        key: K::default().into(),
+                    });
+                    size_bytes_builder.append_null();
+                    created_at_builder.append_null();
+                }
+            }
+        }
+        let (_, last_prefix_arr, key_field, last_key_arr) = last_key_builder.as_arrow();
+        (
+            vec![
+                Field::new(LAST_PREFIX_COLUMN_NAME, DataType::Utf8, false),
+                Field::new(LAST_KEY_COLUMN_NAME, key_field.data_type().clone(), false),
+                Field::new(SIZE_BYTES_COLUMN_NAME, DataType::UInt64, true),
+                Field::new(CREATED_AT_COLUMN_NAME, DataType::UInt64, true),
+            ],
+            vec![
+                last_prefix_arr,
+                last_key_arr,
+                Arc::new(size_bytes_builder.finish()),
+                Arc::new(created_at_builder.finish()),
+            ],
+        )
+    }
+
     pub(super) fn to_bytes<K: ArrowWriteableKey>(&self) -> Result<Vec<u8>, Box<dyn ChromaError>> {
         // Serialize the sparse index as an arrow record batch
         // TODO(hammadb): Note that this should ideally use the Block API to serialize the sparse
@@ -183,6 +280,26 @@ impl RootWriter {
             let (built_counts, count_field) = self.counts_as_arrow(&sparse_index_data);
             schema_fields.push(count_field);
             data_arrays.push(built_counts);
+            // The checksum column is nullable and looked up by name, so it does not
+            // require a version bump: readers that predate it ignore the extra column.
+            let (built_checksums, checksum_field) = self.checksums_as_arrow(&sparse_index_data);
+            schema_fields.push(checksum_field);
+            data_arrays.push(built_checksums);
+            // Likewise for bloom filters, which are only written when enabled
+            let has_bloom_filters = !sparse_index_data.bloom_filters.is_empty();
+            if has_bloom_filters {
+                let (built_bloom_filters, bloom_filter_field) =
+                    self.bloom_filters_as_arrow(&sparse_index_data);
+                schema_fields.push(bloom_filter_field);
+                data_arrays.push(built_bloom_filters);
+            }
+        }
+
+        // Only RootWriter >= V2 will write block statistics
+        if self.version >= Version::V2 {
+            let (stats_fields, stats_arrays) = self.stats_as_arrow::<K>(&sparse_index_data);
+            schema_fields.extend(stats_fields);
+            data_arrays.extend(stats_arrays);
         }
 
         let metadata = HashMap::from_iter(vec![
@@ -260,6 +377,8 @@ pub enum FromBytesError {
     IdMismatch,
     #[error(transparent)]
     VersionError(#[from] VersionError),
+    #[error("Invalid bloom filter for block {0}")]
+    InvalidBloomFilter(Uuid),
 }
 
 impl ChromaError for FromBytesError {
@@ -272,35 +391,25 @@ impl ChromaError for FromBytesError {
             FromBytesError::NoDataError => chroma_error::ErrorCodes::Internal,
             FromBytesError::IdMismatch => chroma_error::ErrorCodes::InvalidArgument,
             FromBytesError::VersionError(e) => e.code(),
+            FromBytesError::InvalidBloomFilter(_) => chroma_error::ErrorCodes::DataLoss,
         }
     }
 }
 
 impl RootReader {
-    pub(super) fn get_all_block_ids_from_bytes(
+    /// Returns the ids of all blocks in the root along with their checksums, if recorded
+    pub(super) fn get_all_blocks_from_bytes(
         bytes: &[u8],
         id: Uuid,
-    ) -> Result<Vec<Uuid>, FromBytesError> {
-        let mut cursor = std::io::Cursor::new(bytes);
-        let arrow_reader = arrow::ipc::reader::FileReader::try_new(&mut cursor, None);
-
-        let record_batch = match arrow_reader {
-            Ok(mut reader) => match reader.next() {
-                Some(Ok(batch)) => batch,
-                Some(Err(e)) => return Err(FromBytesError::ArrowError(e)),
-                None => {
-                    return Err(FromBytesError::NoDataError);
-                }
-            },
-            Err(e) => return Err(FromBytesError::ArrowError(e)),
-        };
-
-        let (version, read_id) = Self::version_and_id_from_record_batch(&record_batch, id)?;
-        if read_id != id {
-            return Err(FromBytesError::IdMismatch);
-        }
-
-        Self::block_ids_from_record_batch(&record_batch, version)
+    ) -> Result<Vec<(Uuid, Option<u32>)>, FromBytesError> {
+        let (version, record_batch) = Self::record_batch_from_bytes(bytes, id)?;
+        let ids = Self::block_ids_from_record_batch(&record_batch, version)?;
+        let checksums = Self::block_checksums_from_record_batch(&record_batch);
+        Ok(ids
+            .into_iter()
+            .enumerate()
+            .map(|(i, block_id)| (block_id, Self::checksum_at(checksums, i)))
+            .collect())
     }
 
     pub(super) fn from_bytes<'data, K: ArrowReadableKey<'data>>(
@@ -308,25 +417,7 @@ impl RootReader {
         prefix_path: &str,
         id: Uuid,
     ) -> Result<Self, FromBytesError> {
-        let mut cursor = std::io::Cursor::new(bytes);
-        let arrow_reader = arrow::ipc::reader::FileReader::try_new(&mut cursor, None);
-
-        let record_batch = match arrow_reader {
-            Ok(mut reader) => match reader.next() {
-                Some(Ok(batch)) => batch,
-                Some(Err(e)) => return Err(FromBytesError::ArrowError(e)),
-                None => {
-                    return Err(FromBytesError::NoDataError);
-                }
-            },
-            Err(e) => return Err(FromBytesError::ArrowError(e)),
-        };
-
-        let (version, read_id) = Self::version_and_id_from_record_batch(&record_batch, id)?;
-
-        if read_id != id {
-            return Err(FromBytesError::IdMismatch);
-        }
+        let (version, record_batch) = Self::record_batch_from_bytes(bytes, id)?;
 
         let prefix_arr = record_batch
             .column(0)
@@ -351,6 +442,20 @@ impl RootReader {
         }
 
         let ids = Self::block_ids_from_record_batch(record_batch, version)?;
+        let checksums = Self::block_checksums_from_record_batch(record_batch);
+        let bloom_filters = record_batch
+            .column_by_name(BLOOM_FILTER_COLUMN_NAME)
+            .map(|column| {
+                column
+                    .as_any()
+                    .downcast_ref::<BinaryArray>()
+                    .expect("Bloom filter array to be a BinaryArray")
+            });
+        // Version 2 is the first version to have block statistics
+        let mut stats_columns = None;
+        if version >= Version::V2 {
+            stats_columns = Self::block_stats_columns_from_record_batch(record_batch);
+        }
 
         let mut forward = BTreeMap::new();
         for (i, block_id) in ids.iter().enumerate() {
@@ -361,18 +466,45 @@ impl RootReader {
                 Some(count_arr) => count_arr.value(i),
                 None => 0,
             };
+            let checksum = Self::checksum_at(checksums, i);
+            let bloom_filter = match bloom_filters {
+                Some(bloom_filter_arr) if bloom_filter_arr.is_valid(i) => Some(
+                    BlockBloomFilter::from_bytes(bloom_filter_arr.value(i))
+                        .ok_or(FromBytesError::InvalidBloomFilter(*block_id))?,
+                ),
+                _ => None,
+            };
+            let stats = match stats_columns {
+                Some((last_prefix_arr, last_key_arr, size_bytes_arr, created_at_arr))
+                    if size_bytes_arr.is_valid(i) =>
+                {
+                    let last_key: K = K::get(last_key_arr, i);
+                    Some(BlockStats {
+                        last_key: CompositeKey::new(last_prefix_arr.value(i).to_string(), last_key),
+                        size_bytes: size_bytes_arr.value(i),
+                        created_at: created_at_arr.is_valid(i).then(|| created_at_arr.value(i)),
+                    })
+                }
+                _ => None,
+            };
 
             match prefix {
                 "START" => {
                     forward.insert(
                         SparseIndexDelimiter::Start,
-                        SparseIndexValue::new(*block_id, count),
+                        SparseIndexValue::new(*block_id, count)
+                            .with_checksum(checksum)
+                            .with_bloom_filter(bloom_filter)
+                            .with_stats(stats),
                     );
                 }
                 _ => {
                     forward.insert(
                         SparseIndexDelimiter::Key(CompositeKey::new(prefix.to_string(), key)),
-                        SparseIndexValue::new(*block_id, count),
+                        SparseIndexValue::new(*block_id, count)
+                            .with_checksum(checksum)
+                            .with_bloom_filter(bloom_filter)
+                            .with_stats(stats),
                     );
                 }
             }
@@ -387,6 +519,35 @@ impl RootReader {
         })
     }
 
+    /// Returns the version of the root and the record batch it is stored in, after checking
+    /// that the stored id matches `id`
+    pub(super) fn record_batch_from_bytes(
+        bytes: &[u8],
+        id: Uuid,
+    ) -> Result<(Version, RecordBatch), FromBytesError> {
+        let mut cursor = std::io::Cursor::new(bytes);
+        let arrow_reader = arrow::ipc::reader::FileReader::try_new(&mut cursor, None);
+
+        let record_batch = match arrow_reader {
+            Ok(mut reader) => match reader.next() {
+                Some(Ok(batch)) => batch,
+                Some(Err(e)) => return Err(FromBytesError::ArrowError(e)),
+                None => {
+                    return Err(FromBytesError::NoDataError);
+                }
+            },
+            Err(e) => return Err(FromBytesError::ArrowError(e)),
+        };
+
+        let (version, read_id) = Self::version_and_id_from_record_batch(&record_batch, id)?;
+
+        if read_id != id {
+            return Err(FromBytesError::IdMismatch);
+        }
+
+        Ok((version, record_batch))
+    }
+
     pub(super) fn fork(&self, new_id: Uuid) -> RootWriter {
         let new_sparse_index = self.sparse_index.fork();
         RootWriter {
@@ -415,7 +576,7 @@ impl RootReader {
         }
     }
 
-    fn block_ids_from_record_batch(
+    pub(super) fn block_ids_from_record_batch(
         record_batch: &RecordBatch,
         version: Version,
     ) -> Result<Vec<Uuid>, FromBytesError> {
@@ -447,6 +608,108 @@ impl RootReader {
 
         Ok(ids)
     }
+
+    pub(super) fn block_checksums_from_record_batch(
+        record_batch: &RecordBatch,
+    ) -> Option<&UInt32Array> {
+        // Roots written before checksums were introduced do not have this column
+        record_batch
+            .column_by_name(CHECKSUM_COLUMN_NAME)
+            .map(|column| {
+                column
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .expect("Checksum array to be a UInt32Array")
+            })
+    }
+
+    pub(super) fn checksum_at(checksums: Option<&UInt32Array>, index: usize) -> Option<u32> {
+        checksums.and_then(|arr| arr.is_valid(index).then(|| arr.value(index)))
+    }
+
+    /// Returns the last prefix, last key, size and creation time columns of the root,
+    /// or None if any of them is missing
+    #[allow(clippy::type_complexity)]
+    fn block_stats_columns_from_record_batch(
+        record_batch: &RecordBatch,
+    ) -> Option<(&StringArray, &Arc<dyn Array>, &UInt64Array, &UInt64Array)> {
+        let last_prefix_arr = record_batch
+            .column_by_name(LAST_PREFIX_COLUMN_NAME)?
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .expect("Last prefix array to be a StringArray");
+        let last_key_arr = record_batch.column_by_name(LAST_KEY_COLUMN_NAME)?;
+        let size_bytes_arr = record_batch
+            .column_by_name(SIZE_BYTES_COLUMN_NAME)?
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .expect("Size array to be a UInt64Array");
+        let created_at_arr = record_batch
+            .column_by_name(CREATED_AT_COLUMN_NAME)?
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .expect("Creation time array to be a UInt64Array");
+        Some((
+            last_prefix_arr,
+            last_key_arr,
+            size_bytes_arr,
+            created_at_arr,
+        ))
+    }
+
+    /// Returns the storage statistics of the blocks that may hold keys with a prefix in
+    /// `prefix_range`, computed from the sparse index alone
+    pub(super) fn stats<'prefix, PrefixRange>(&self, prefix_range: PrefixRange) -> BlockfileStats
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+    {
+        let mut stats = BlockfileStats::default();
+        for value in self.sparse_index.get_blocks_range(prefix_range) {
+            stats.num_blocks += 1;
+            stats.num_keys += value.count as u64;
+            let block_stats = match &value.stats {
+                Some(block_stats) => block_stats,
+                None => {
+                    stats.blocks_without_stats += 1;
+                    continue;
+                }
+            };
+            stats.size_bytes += block_stats.size_bytes;
+            if let Some(created_at) = block_stats.created_at {
+                let created_at = UNIX_EPOCH + Duration::from_millis(created_at);
+                stats.oldest_block_created_at = Some(
+                    stats
+                        .oldest_block_created_at
+                        .map_or(created_at, |oldest| oldest.min(created_at)),
+                );
+                stats.newest_block_created_at = Some(
+                    stats
+                        .newest_block_created_at
+                        .map_or(created_at, |newest| newest.max(created_at)),
+                );
+            }
+        }
+        stats
+    }
+}
+
+/// Storage statistics of a blockfile, or of the blocks of a blockfile that may hold a
+/// range of prefixes, computed from its root without fetching any blocks.
+/// Since whole blocks are counted, the statistics of a range are an upper bound.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockfileStats {
+    /// The number of blocks
+    pub num_blocks: usize,
+    /// The number of keys in the blocks, zero for roots before V1.1 which do not record counts
+    pub num_keys: u64,
+    /// The serialized size of the blocks that have statistics
+    pub size_bytes: u64,
+    /// The number of blocks without statistics, whose size is not included in `size_bytes`
+    pub blocks_without_stats: usize,
+    /// The creation time of the oldest block that recorded one
+    pub oldest_block_created_at: Option<SystemTime>,
+    /// The creation time of the newest block that recorded one
+    pub newest_block_created_at: Option<SystemTime>,
 }
 
 #[cfg(test)]
@@ -502,6 +765,44 @@ mod test {
             .set_count(block_ids[3], 4)
             .expect("Set count should succeed");
 
+        // Leave the last block without a checksum, as if it was inherited from an old root
+        for (i, block_id) in block_ids.iter().take(3).enumerate() {
+            root_writer
+                .sparse_index
+                .set_checksum(*block_id, i as u32 + 100)
+                .expect("Set checksum should succeed");
+        }
+
+        // Only the second block has a bloom filter
+        let mut bloom_filter = BlockBloomFilter::new(2, 10);
+        bloom_filter.insert(&CompositeKey::new("prefix".to_string(), "a"));
+        root_writer
+            .sparse_index
+            .set_bloom_filter(block_ids[1], bloom_filter.clone())
+            .expect("Set bloom filter should succeed");
+
+        // The first and third blocks have statistics, the third without a creation time
+        let stats = [
+            BlockStats {
+                last_key: CompositeKey::new("prefix".to_string(), "0"),
+                size_bytes: 1000,
+                created_at: Some(1_700_000_000_000),
+            },
+            BlockStats {
+                last_key: CompositeKey::new("prefix".to_string(), "bz"),
+                size_bytes: 3000,
+                created_at: None,
+            },
+        ];
+        root_writer
+            .sparse_index
+            .set_stats(block_ids[0], stats[0].clone())
+            .expect("Set stats should succeed");
+        root_writer
+            .sparse_index
+            .set_stats(block_ids[2], stats[1].clone())
+            .expect("Set stats should succeed");
+
         let bytes = root_writer
             .to_bytes::<&str>()
             .expect("To be able to serialize");
@@ -537,6 +838,44 @@ mod test {
             );
         }
 
+        // Check that checksums are the same
+        for (i, block_id) in block_ids.iter().enumerate() {
+            let expected = if i < 3 { Some(i as u32 + 100) } else { None };
+            assert_eq!(root_reader.sparse_index.get_checksum(block_id), expected);
+        }
+        // Check that bloom filters are the same
+        for value in root_reader.sparse_index.data.forward.values() {
+            if value.id == block_ids[1] {
+                assert_eq!(value.bloom_filter.as_ref(), Some(&bloom_filter));
+            } else {
+                assert_eq!(value.bloom_filter, None);
+            }
+        }
+        // Check that block statistics are the same
+        for value in root_reader.sparse_index.data.forward.values() {
+            if value.id == block_ids[0] {
+                assert_eq!(value.stats.as_ref(), Some(&stats[0]));
+            } else if value.id == block_ids[2] {
+                assert_eq!(value.stats.as_ref(), Some(&stats[1]));
+            } else {
+                assert_eq!(value.stats, None);
+            }
+        }
+        let all_blocks = RootReader::get_all_blocks_from_bytes(&bytes, bf_id)
+            .expect("To be able to read block ids");
+        for (block_id, checksum) in all_blocks {
+            assert_eq!(checksum, root_reader.sparse_index.get_checksum(&block_id));
+        }
+
+        let blockfile_stats = root_reader.stats(..);
+        assert_eq!(blockfile_stats.num_blocks, 4);
+        assert_eq!(blockfile_stats.num_keys, 10);
+        assert_eq!(blockfile_stats.size_bytes, 4000);
+        assert_eq!(blockfile_stats.blocks_without_stats, 2);
+        let created_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        assert_eq!(blockfile_stats.oldest_block_created_at, Some(created_at));
+        assert_eq!(blockfile_stats.newest_block_created_at, Some(created_at));
+
         assert_eq!(root_writer.version, root_reader.version);
         assert_eq!(root_writer.id, root_reader.id);
     }
@@ -600,5 +939,9 @@ mod test {
                 0
             );
         }
+        // V1 roots do not store checksums
+        for block_id in block_ids.iter() {
+            assert_eq!(root_reader.sparse_index.get_checksum(block_id), None);
+        }
     }
 }
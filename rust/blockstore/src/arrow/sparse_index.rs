@@ -1,7 +1,12 @@
-use crate::key::CompositeKey;
+use super::bloom_filter::BlockBloomFilter;
+use crate::key::{CompositeKey, CompositeKeyRange};
 use chroma_error::ChromaError;
 use parking_lot::Mutex;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::ops::{Bound, RangeBounds};
@@ -71,6 +76,18 @@ pub(super) struct SparseIndexWriterData {
     // This is not intended updated incrementally, and is only populated
     // at commit time of the blockfile.
     pub(super) counts: BTreeMap<SparseIndexDelimiter, u32>,
+    // The checksum of the serialized bytes of each block, keyed by block id.
+    // Only populated when a block is flushed, or inherited from the root that
+    // was forked. Blocks written before checksums were introduced have no entry.
+    pub(super) checksums: HashMap<Uuid, u32>,
+    // The bloom filter of the keys in each block, keyed by block id.
+    // Only populated at flush time when bloom filters are enabled, or inherited
+    // from the root that was forked.
+    pub(super) bloom_filters: HashMap<Uuid, BlockBloomFilter>,
+    // The statistics of each block, keyed by block id.
+    // Only populated when a block is flushed, migrated from a root that
+    // predates them, or inherited from the root that was forked.
+    pub(super) stats: HashMap<Uuid, BlockStats>,
 }
 
 impl SparseIndexWriterData {
@@ -107,6 +124,48 @@ impl ChromaError for SetCountError {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum SetBloomFilterError {
+    #[error("Block id does not exist in the sparse index")]
+    BlockIdDoesNotExist,
+}
+
+impl ChromaError for SetBloomFilterError {
+    fn code(&self) -> chroma_error::ErrorCodes {
+        match self {
+            SetBloomFilterError::BlockIdDoesNotExist => chroma_error::ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SetChecksumError {
+    #[error("Block id does not exist in the sparse index")]
+    BlockIdDoesNotExist,
+}
+
+impl ChromaError for SetChecksumError {
+    fn code(&self) -> chroma_error::ErrorCodes {
+        match self {
+            SetChecksumError::BlockIdDoesNotExist => chroma_error::ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SetStatsError {
+    #[error("Block id does not exist in the sparse index")]
+    BlockIdDoesNotExist,
+}
+
+impl ChromaError for SetStatsError {
+    fn code(&self) -> chroma_error::ErrorCodes {
+        match self {
+            SetStatsError::BlockIdDoesNotExist => chroma_error::ErrorCodes::InvalidArgument,
+        }
+    }
+}
+
 impl SparseIndexWriter {
     pub(crate) fn new(initial_block_id: Uuid) -> Self {
         let mut forward = BTreeMap::new();
@@ -120,6 +179,9 @@ impl SparseIndexWriter {
             forward,
             reverse,
             counts,
+            checksums: HashMap::new(),
+            bloom_filters: HashMap::new(),
+            stats: HashMap::new(),
         };
 
         Self {
@@ -215,6 +277,64 @@ impl SparseIndexWriter {
         }
     }
 
+    /// Set the checksum of a block in the sparse index.
+    /// This is populated at flush time of the blockfile, once the
+    /// serialized bytes of the block are known.
+    /// # Arguments
+    /// * `block_id` - The block id to set the checksum for
+    /// * `checksum` - The CRC32 of the serialized block
+    pub(crate) fn set_checksum(
+        &self,
+        block_id: Uuid,
+        checksum: u32,
+    ) -> Result<(), SetChecksumError> {
+        let mut data = self.data.lock();
+        if !data.reverse.contains_key(&block_id) {
+            return Err(SetChecksumError::BlockIdDoesNotExist);
+        }
+        data.checksums.insert(block_id, checksum);
+        Ok(())
+    }
+
+    /// Set the bloom filter of a block in the sparse index.
+    /// This is populated at flush time of the blockfile when bloom filters are enabled.
+    /// # Arguments
+    /// * `block_id` - The block id to set the bloom filter for
+    /// * `bloom_filter` - The bloom filter of the keys in the block
+    pub(crate) fn set_bloom_filter(
+        &self,
+        block_id: Uuid,
+        bloom_filter: BlockBloomFilter,
+    ) -> Result<(), SetBloomFilterError> {
+        let mut data = self.data.lock();
+        if !data.reverse.contains_key(&block_id) {
+            return Err(SetBloomFilterError::BlockIdDoesNotExist);
+        }
+        data.bloom_filters.insert(block_id, bloom_filter);
+        Ok(())
+    }
+
+    /// Set the statistics of a block in the sparse index.
+    /// This is populated at flush time of the blockfile, or when migrating
+    /// a root that predates block statistics.
+    /// # Arguments
+    /// * `block_id` - The block id to set the statistics for
+    /// * `stats` - The statistics of the block
+    pub(crate) fn set_stats(&self, block_id: Uuid, stats: BlockStats) -> Result<(), SetStatsError> {
+        let mut data = self.data.lock();
+        if !data.reverse.contains_key(&block_id) {
+            return Err(SetStatsError::BlockIdDoesNotExist);
+        }
+        data.stats.insert(block_id, stats);
+        Ok(())
+    }
+
+    /// Get the checksum of a block in the sparse index, if one was recorded.
+    pub(super) fn get_checksum(&self, block_id: &Uuid) -> Option<u32> {
+        let data = self.data.lock();
+        data.checksums.get(block_id).copied()
+    }
+
     pub(super) fn get_target_block_id(&self, search_key: &CompositeKey) -> Uuid {
         let data = self.data.lock();
         let forward = &data.forward;
@@ -226,6 +346,80 @@ impl SparseIndexWriter {
         data.forward.len()
     }
 
+    pub(super) fn contains_block(&self, block_id: &Uuid) -> bool {
+        let data = self.data.lock();
+        data.reverse.contains_key(block_id)
+    }
+
+    /// Get the ids of all blocks that may contain keys in the given range, along with whether
+    /// every key of the block is known to be in the range. The first block is never known to be
+    /// covered, since its start is unbounded.
+    pub(super) fn get_blocks_in_range(&self, range: &CompositeKeyRange) -> Vec<(Uuid, bool)> {
+        let data = self.data.lock();
+        let next_start_keys = data
+            .forward
+            .keys()
+            .skip(1)
+            .map(|delimiter| match delimiter {
+                SparseIndexDelimiter::Start => {
+                    panic!("Invariant violation. Sparse index is not valid.");
+                }
+                SparseIndexDelimiter::Key(k) => Some(k),
+            })
+            .chain(std::iter::once(None));
+
+        data.forward
+            .iter()
+            .zip(next_start_keys)
+            .filter_map(|((start_delimiter, block_id), next_start_key)| {
+                let last_key = data.stats.get(block_id).map(|stats| &stats.last_key);
+                block_in_range(range, start_delimiter, last_key, next_start_key)
+                    .map(|covered| (*block_id, covered))
+            })
+            .collect()
+    }
+
+    /// Group adjacent blocks that should be merged because they are underfilled. Only the blocks
+    /// in `block_sizes` are considered. A block joins the group of the block before it if either
+    /// is smaller than `min_block_size_bytes` and the merged block is at most
+    /// `max_block_size_bytes`. Returns the groups of more than one block, in key order.
+    pub(super) fn get_underfilled_block_groups(
+        &self,
+        block_sizes: &HashMap<Uuid, usize>,
+        min_block_size_bytes: usize,
+        max_block_size_bytes: usize,
+    ) -> Vec<Vec<Uuid>> {
+        let data = self.data.lock();
+        let mut groups = Vec::new();
+        // The group being built along with its merged size
+        let mut current_group: Option<(Vec<Uuid>, usize)> = None;
+        for block_id in data.forward.values() {
+            let Some(size) = block_sizes.get(block_id).copied() else {
+                groups.extend(current_group.take());
+                continue;
+            };
+            current_group = match current_group.take() {
+                Some((mut group, group_size))
+                    if (group_size < min_block_size_bytes || size < min_block_size_bytes)
+                        && group_size + size <= max_block_size_bytes =>
+                {
+                    group.push(*block_id);
+                    Some((group, group_size + size))
+                }
+                previous_group => {
+                    groups.extend(previous_group);
+                    Some((vec![*block_id], size))
+                }
+            };
+        }
+        groups.extend(current_group);
+        groups
+            .into_iter()
+            .map(|(group, _)| group)
+            .filter(|group| group.len() > 1)
+            .collect()
+    }
+
     pub(super) fn remove_block(&self, block_id: &Uuid) -> bool {
         // We commit and flush an empty dummy block if the blockfile is empty.
         // It can happen that other indexes of the segment are not empty. In this case,
@@ -288,7 +482,13 @@ impl SparseIndexWriter {
 
         let zipped = data.forward.iter().zip(data.counts.iter());
         let new_forward = zipped.map(|((key, block_id), (_, count))| {
-            (key.clone(), SparseIndexValue::new(*block_id, *count))
+            (
+                key.clone(),
+                SparseIndexValue::new(*block_id, *count)
+                    .with_checksum(data.checksums.get(block_id).copied())
+                    .with_bloom_filter(data.bloom_filters.get(block_id).cloned())
+                    .with_stats(data.stats.get(block_id).cloned()),
+            )
         });
         let new_forward = BTreeMap::from_iter(new_forward);
         Ok(SparseIndexReader::new(new_forward))
@@ -318,31 +518,134 @@ pub struct SparseIndexReader {
     pub(super) data: Arc<SparseIndexReaderData>,
 }
 
-#[derive(Serialize, Deserialize)]
 pub(super) struct SparseIndexReaderData {
     pub(super) forward: BTreeMap<SparseIndexDelimiter, SparseIndexValue>,
+    /// The recorded checksums by block id, derived from `forward`
+    checksums: HashMap<Uuid, u32>,
+}
+
+impl SparseIndexReaderData {
+    fn new(forward: BTreeMap<SparseIndexDelimiter, SparseIndexValue>) -> Self {
+        let checksums = forward
+            .values()
+            .filter_map(|value| value.checksum.map(|checksum| (value.id, checksum)))
+            .collect();
+        Self { forward, checksums }
+    }
+}
+
+/// Tags the serialized sparse index of roots in the persistent root cache, which stores them
+/// with bincode, so fields cannot be added to `SparseIndexValue` without changing the format.
+/// Entries written before the tag existed start with the number of blocks in the sparse index
+/// instead, which is never this large. Entries of any other format fail to deserialize and are
+/// read from storage again like any other cache miss.
+/// Change the low bits whenever the serialized layout of the sparse index changes.
+const CACHE_FORMAT: u64 = 0x5350_4152_5345_0002;
+
+impl Serialize for SparseIndexReaderData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&CACHE_FORMAT)?;
+        tuple.serialize_element(&self.forward)?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SparseIndexReaderData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SparseIndexReaderDataVisitor;
+
+        impl<'de> Visitor<'de> for SparseIndexReaderDataVisitor {
+            type Value = SparseIndexReaderData;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a sparse index in format {:#x}", CACHE_FORMAT)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                // Checked before anything else is read, the rest of an entry of another
+                // format cannot be interpreted
+                let format: u64 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                if format != CACHE_FORMAT {
+                    return Err(de::Error::custom(format!(
+                        "Unsupported sparse index format {:#x}, expected {:#x}",
+                        format, CACHE_FORMAT
+                    )));
+                }
+                let forward = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(SparseIndexReaderData::new(forward))
+            }
+        }
+
+        deserializer.deserialize_tuple(2, SparseIndexReaderDataVisitor)
+    }
 }
 
 /// A value in the sparse index.
 /// # Fields
 /// * `id` - The block id that contains the keys in the range
 /// * `count` - The number of keys in the block
+/// * `checksum` - The CRC32 of the serialized block, if it was recorded when the block was flushed
+/// * `bloom_filter` - The bloom filter of the keys in the block, if bloom filters were enabled
+/// * `stats` - The statistics of the block, if the root it was read from records them
 #[derive(Serialize, Deserialize)]
 pub(super) struct SparseIndexValue {
     pub(super) id: Uuid,
     pub(super) count: u32,
+    pub(super) checksum: Option<u32>,
+    pub(super) bloom_filter: Option<BlockBloomFilter>,
+    pub(super) stats: Option<BlockStats>,
+}
+
+/// Statistics of a block that are recorded in roots from V2 onwards, so that
+/// readers can reason about a block without fetching it. Blocks of a root that was
+/// migrated from an earlier version have none until they are rewritten.
+/// # Fields
+/// * `last_key` - The largest prefix-key pair stored in the block
+/// * `size_bytes` - The size of the block as serialized to storage
+/// * `created_at` - When the block was flushed, in milliseconds since the unix epoch, if known
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(super) struct BlockStats {
+    pub(super) last_key: CompositeKey,
+    pub(super) size_bytes: u64,
+    pub(super) created_at: Option<u64>,
 }
 
 impl SparseIndexValue {
     pub(super) fn new(id: Uuid, count: u32) -> Self {
-        Self { id, count }
+        Self {
+            id,
+            count,
+            checksum: None,
+            bloom_filter: None,
+            stats: None,
+        }
+    }
+
+    pub(super) fn with_checksum(mut self, checksum: Option<u32>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub(super) fn with_bloom_filter(mut self, bloom_filter: Option<BlockBloomFilter>) -> Self {
+        self.bloom_filter = bloom_filter;
+        self
+    }
+
+    pub(super) fn with_stats(mut self, stats: Option<BlockStats>) -> Self {
+        self.stats = stats;
+        self
     }
 }
 
 impl SparseIndexReader {
     pub(super) fn new(data: BTreeMap<SparseIndexDelimiter, SparseIndexValue>) -> Self {
         Self {
-            data: Arc::new(SparseIndexReaderData { forward: data }),
+            data: Arc::new(SparseIndexReaderData::new(data)),
         }
     }
 
@@ -359,6 +662,82 @@ impl SparseIndexReader {
         get_target_block(search_key, forward).id
     }
 
+    /// Returns false if the key is definitely not in the block that would contain it,
+    /// according to the block's bloom filter. Blocks without a bloom filter may contain any key.
+    pub(super) fn may_contain(&self, search_key: &CompositeKey) -> bool {
+        let forward = &self.data.forward;
+        match &get_target_block(search_key, forward).bloom_filter {
+            Some(bloom_filter) => bloom_filter.may_contain(search_key),
+            None => true,
+        }
+    }
+
+    /// Get the checksum recorded for the given block id, if any
+    pub(super) fn get_checksum(&self, block_id: &Uuid) -> Option<u32> {
+        self.data.checksums.get(block_id).copied()
+    }
+
+    /// Get the ids of all blocks that may contain keys in the given range
+    pub(super) fn get_block_ids_in_range(&self, range: &CompositeKeyRange) -> Vec<Uuid> {
+        let forward = &self.data.forward;
+        let next_start_keys = forward
+            .keys()
+            .skip(1)
+            .map(|delimiter| match delimiter {
+                SparseIndexDelimiter::Start => {
+                    panic!("Invariant violation. Sparse index is not valid.");
+                }
+                SparseIndexDelimiter::Key(k) => Some(k),
+            })
+            .chain(std::iter::once(None));
+
+        forward
+            .iter()
+            .zip(next_start_keys)
+            .filter_map(|((start_delimiter, block_value), next_start_key)| {
+                let last_key = block_value.stats.as_ref().map(|stats| &stats.last_key);
+                block_in_range(range, start_delimiter, last_key, next_start_key)
+                    .map(|_| block_value.id)
+            })
+            .collect()
+    }
+
+    /// Get the value of every block along with the prefix shared by all of its keys, if the
+    /// sparse index is enough to tell. Otherwise the block has to be fetched to find its prefixes.
+    pub(super) fn get_blocks_with_prefix(&self) -> Vec<(&SparseIndexValue, Option<&str>)> {
+        let forward = &self.data.forward;
+        let next_start_keys = forward
+            .keys()
+            .skip(1)
+            .map(|delimiter| match delimiter {
+                SparseIndexDelimiter::Start => {
+                    panic!("Invariant violation. Sparse index is not valid.");
+                }
+                SparseIndexDelimiter::Key(k) => Some(k),
+            })
+            .chain(std::iter::once(None));
+
+        forward
+            .iter()
+            .zip(next_start_keys)
+            .map(|((start_delimiter, block_value), next_start_key)| {
+                let prefix = match start_delimiter {
+                    // The first key of the first block is unknown
+                    SparseIndexDelimiter::Start => None,
+                    SparseIndexDelimiter::Key(start_key) => {
+                        let end_prefix = match (&block_value.stats, next_start_key) {
+                            (Some(stats), _) => Some(&stats.last_key.prefix),
+                            (None, Some(next_start_key)) => Some(&next_start_key.prefix),
+                            (None, None) => None,
+                        };
+                        (end_prefix == Some(&start_key.prefix)).then_some(start_key.prefix.as_str())
+                    }
+                };
+                (block_value, prefix)
+            })
+            .collect()
+    }
+
     /// Get all the block ids that contain keys in the given input search keys
     pub(super) fn get_all_target_block_ids(&self, mut search_keys: Vec<CompositeKey>) -> Vec<Uuid> {
         // Sort so that we can search in one iteration.
@@ -443,12 +822,26 @@ impl SparseIndexReader {
         &self,
         prefix_range: PrefixRange,
     ) -> Vec<Uuid>
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+    {
+        self.get_blocks_range(prefix_range)
+            .into_iter()
+            .map(|value| value.id)
+            .collect()
+    }
+
+    /// Get the values of all blocks that may contain keys with a prefix in the given range
+    pub(super) fn get_blocks_range<'prefix, PrefixRange>(
+        &self,
+        prefix_range: PrefixRange,
+    ) -> Vec<&SparseIndexValue>
     where
         PrefixRange: RangeBounds<&'prefix str>,
     {
         let forward = &self.data.forward;
 
-        // We do not materialize the last key of each block, so we must check the next block's start key to determine if the current block's end key is within the query range.
+        // Roots before V2 do not materialize the last key of each block, so for blocks without statistics we must check the next block's start key to determine if the current block's end key is within the query range.
         let start_keys_offset_by_1_iter = forward
             .iter()
             .skip(1)
@@ -463,7 +856,13 @@ impl SparseIndexReader {
         forward
             .iter()
             .zip(start_keys_offset_by_1_iter)
-            .map(|((start_key, block_uuid), end_key)| (block_uuid, start_key, end_key))
+            .map(|((start_key, block_value), next_start_key)| {
+                let end_key = match &block_value.stats {
+                    Some(stats) => Some(&stats.last_key),
+                    None => next_start_key,
+                };
+                (block_value, start_key, end_key)
+            })
             .filter(|(_, block_start_delimiter, block_end_delimiter)| {
                 // The block should be retained if and only if its prefix range overlaps with the given prefix range
                 // The necessary and sufficient condition for range R1, R2 to overlap is MAX(R1.START, R2.START) <= MIN(R1.END, R2.END)
@@ -511,7 +910,7 @@ impl SparseIndexReader {
                     _ => true,
                 }
             })
-            .map(|(sparse_index_value, _, _)| sparse_index_value.id)
+            .map(|(sparse_index_value, _, _)| sparse_index_value)
             .collect()
     }
 
@@ -521,12 +920,24 @@ impl SparseIndexReader {
         let mut new_forward = BTreeMap::new();
         let mut new_reverse = HashMap::new();
         let mut new_counts = BTreeMap::new();
+        let mut new_checksums = HashMap::new();
+        let mut new_bloom_filters = HashMap::new();
+        let mut new_stats = HashMap::new();
         let old_data = &self.data;
         let old_forward = &old_data.forward;
         for (key, curr_block_value) in old_forward.iter() {
             new_forward.insert(key.clone(), curr_block_value.id);
             new_reverse.insert(curr_block_value.id, key.clone());
             new_counts.insert(key.clone(), curr_block_value.count);
+            if let Some(checksum) = curr_block_value.checksum {
+                new_checksums.insert(curr_block_value.id, checksum);
+            }
+            if let Some(bloom_filter) = &curr_block_value.bloom_filter {
+                new_bloom_filters.insert(curr_block_value.id, bloom_filter.clone());
+            }
+            if let Some(stats) = &curr_block_value.stats {
+                new_stats.insert(curr_block_value.id, stats.clone());
+            }
         }
 
         SparseIndexWriter {
@@ -534,6 +945,9 @@ impl SparseIndexReader {
                 forward: new_forward,
                 reverse: new_reverse,
                 counts: new_counts,
+                checksums: new_checksums,
+                bloom_filters: new_bloom_filters,
+                stats: new_stats,
             })),
         }
     }
@@ -568,6 +982,41 @@ impl Debug for SparseIndexReader {
     }
 }
 
+// Helper function to check whether a block may hold keys in the given range. Returns None if it
+// cannot, and otherwise whether every key of the block is known to be in the range.
+fn block_in_range(
+    range: &CompositeKeyRange,
+    start_delimiter: &SparseIndexDelimiter,
+    last_key: Option<&CompositeKey>,
+    next_start_key: Option<&CompositeKey>,
+) -> Option<bool> {
+    // Blocks of a root before V2 only carry the next block's start key as their end.
+    let starts_before_end = match start_delimiter {
+        SparseIndexDelimiter::Start => true,
+        SparseIndexDelimiter::Key(start_key) => range.is_before_end(start_key),
+    };
+    let ends_after_start = match (last_key, next_start_key) {
+        (Some(last_key), _) => range.is_after_start(last_key),
+        (None, Some(next_start_key)) => range.starts_before(next_start_key),
+        (None, None) => true,
+    };
+    if !starts_before_end || !ends_after_start {
+        return None;
+    }
+    let covered = match start_delimiter {
+        SparseIndexDelimiter::Start => false,
+        SparseIndexDelimiter::Key(start_key) => {
+            range.is_after_start(start_key)
+                && match (last_key, next_start_key) {
+                    (Some(last_key), _) => range.is_before_end(last_key),
+                    (None, Some(next_start_key)) => range.covers_up_to(next_start_key),
+                    (None, None) => false,
+                }
+        }
+    };
+    Some(covered)
+}
+
 // Helper function to get the target block id for a given key
 fn get_target_block<'data, T>(
     search_key: &CompositeKey,
@@ -877,6 +1326,124 @@ mod tests {
         assert_eq!(blocks, vec![block_id_4, block_id_5, block_id_6]);
     }
 
+    #[test]
+    fn test_get_block_ids_range_with_stats() {
+        let block_ids = [
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        ];
+        let writer = SparseIndexWriter::new(block_ids[0]);
+        writer
+            .add_block(CompositeKey::new("c".to_string(), "a"), block_ids[1])
+            .expect("No error");
+        writer
+            .add_block(CompositeKey::new("e".to_string(), "a"), block_ids[2])
+            .expect("No error");
+        for block_id in block_ids.iter() {
+            writer
+                .set_count(*block_id, 10)
+                .expect("Set count should succeed");
+        }
+        // The middle block has no statistics, as if it was inherited from an old root
+        for (block_id, last_prefix) in [(block_ids[0], "a"), (block_ids[2], "e")] {
+            writer
+                .set_stats(
+                    block_id,
+                    BlockStats {
+                        last_key: CompositeKey::new(last_prefix.to_string(), "z"),
+                        size_bytes: 100,
+                        created_at: None,
+                    },
+                )
+                .expect("Set stats should succeed");
+        }
+
+        let reader = writer.to_reader().expect("Conversion should succeed");
+        let blocks = reader.get_block_ids_range(..);
+        assert_eq!(blocks, block_ids.to_vec());
+
+        // The first block ends before "b", so it is pruned even though the next block starts after "b"
+        let blocks = reader.get_block_ids_range("b"..="b");
+        assert!(blocks.is_empty());
+
+        let blocks = reader.get_block_ids_range(..="a");
+        assert_eq!(blocks, vec![block_ids[0]]);
+
+        // Without statistics the middle block is bounded by the start of the next block
+        let blocks = reader.get_block_ids_range("d"..);
+        assert_eq!(blocks, vec![block_ids[1], block_ids[2]]);
+
+        let blocks = reader.get_block_ids_range("f"..);
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn test_get_blocks_in_range() {
+        let block_ids = [
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        ];
+        let writer = SparseIndexWriter::new(block_ids[0]);
+        writer
+            .add_block(CompositeKey::new("c".to_string(), "a"), block_ids[1])
+            .expect("No error");
+        writer
+            .add_block(CompositeKey::new("e".to_string(), "a"), block_ids[2])
+            .expect("No error");
+        // The middle block has no statistics, as if it was inherited from an old root
+        for (block_id, last_prefix) in [(block_ids[0], "a"), (block_ids[2], "e")] {
+            writer
+                .set_stats(
+                    block_id,
+                    BlockStats {
+                        last_key: CompositeKey::new(last_prefix.to_string(), "z"),
+                        size_bytes: 100,
+                        created_at: None,
+                    },
+                )
+                .expect("Set stats should succeed");
+        }
+
+        // The first block is never covered since its start is unbounded
+        let blocks = writer.get_blocks_in_range(&CompositeKeyRange::new::<&str>("a", .."m"));
+        assert_eq!(blocks, vec![(block_ids[0], false)]);
+
+        // The middle block may hold keys of prefix "d" as well
+        let blocks = writer.get_blocks_in_range(&CompositeKeyRange::new::<&str>("c", ..));
+        assert_eq!(blocks, vec![(block_ids[1], false)]);
+
+        let blocks = writer.get_blocks_in_range(&CompositeKeyRange::new::<&str>("e", ..));
+        assert_eq!(blocks, vec![(block_ids[1], false), (block_ids[2], true)]);
+
+        let blocks = writer.get_blocks_in_range(&CompositeKeyRange::new("e", "b".."z"));
+        assert_eq!(blocks, vec![(block_ids[2], false)]);
+    }
+
+    #[test]
+    fn test_get_underfilled_block_groups() {
+        let block_ids = (0..6).map(|_| uuid::Uuid::new_v4()).collect::<Vec<_>>();
+        let writer = SparseIndexWriter::new(block_ids[0]);
+        for (i, block_id) in block_ids.iter().enumerate().skip(1) {
+            writer
+                .add_block(CompositeKey::new(i.to_string(), "a"), *block_id)
+                .expect("No error");
+        }
+
+        // Block 3 was not rewritten, so it splits the blocks around it
+        let block_sizes = [(0, 10), (1, 80), (2, 30), (4, 60), (5, 70)]
+            .into_iter()
+            .map(|(i, size)| (block_ids[i], size))
+            .collect::<HashMap<_, _>>();
+        let groups = writer.get_underfilled_block_groups(&block_sizes, 50, 100);
+        // Block 2 does not fit in the first group, blocks 4 and 5 are both filled enough
+        assert_eq!(groups, vec![vec![block_ids[0], block_ids[1]]]);
+
+        let groups = writer.get_underfilled_block_groups(&block_sizes, 50, 200);
+        assert_eq!(groups, vec![vec![block_ids[0], block_ids[1], block_ids[2]]]);
+    }
+
     #[test]
     fn test_serde() {
         let ids = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()];
@@ -898,11 +1465,17 @@ mod tests {
         sparse_index
             .set_count(ids[1], counts[1])
             .expect("Set count should succeed");
+        sparse_index
+            .set_checksum(ids[1], 42)
+            .expect("Set checksum should succeed");
 
         let reader = sparse_index.to_reader().expect("Conversion should succeed");
 
         let serialized = bincode::serialize(&reader).unwrap();
         let deserialized: SparseIndexReader = bincode::deserialize(&serialized).unwrap();
+        // Checksums are looked up by block id after deserialization as well
+        assert_eq!(deserialized.get_checksum(&ids[0]), None);
+        assert_eq!(deserialized.get_checksum(&ids[1]), Some(42));
 
         let old_data = sparse_index.data.lock();
         let new_data = deserialized.data;
@@ -919,4 +1492,61 @@ mod tests {
             assert_eq!(new_data.forward.get(&target_key).unwrap().id, ids[i]);
         }
     }
+
+    #[test]
+    fn test_serde_rejects_old_layout() {
+        // The layout roots were cached in before sparse index values had optional fields
+        #[derive(Serialize)]
+        struct OldSparseIndexValue {
+            id: Uuid,
+            count: u32,
+        }
+        #[derive(Serialize)]
+        struct OldSparseIndexReaderData {
+            forward: BTreeMap<SparseIndexDelimiter, OldSparseIndexValue>,
+        }
+
+        let ids = [Uuid::new_v4(), Uuid::new_v4()];
+        let old = OldSparseIndexReaderData {
+            forward: BTreeMap::from([
+                (
+                    SparseIndexDelimiter::Start,
+                    OldSparseIndexValue {
+                        id: ids[0],
+                        count: 10,
+                    },
+                ),
+                (
+                    SparseIndexDelimiter::Key(CompositeKey::new("prefix".to_string(), "c")),
+                    OldSparseIndexValue {
+                        id: ids[1],
+                        count: 20,
+                    },
+                ),
+            ]),
+        };
+        let serialized = bincode::serialize(&old).unwrap();
+        assert!(bincode::deserialize::<SparseIndexReader>(&serialized).is_err());
+
+        // The current layout round trips
+        let sparse_index = SparseIndexWriter::new(ids[0]);
+        sparse_index.set_count(ids[0], 10).unwrap();
+        sparse_index
+            .add_block(CompositeKey::new("prefix".to_string(), "c"), ids[1])
+            .unwrap();
+        sparse_index.set_count(ids[1], 20).unwrap();
+        let reader = sparse_index.to_reader().unwrap();
+        let serialized = bincode::serialize(&reader).unwrap();
+        let deserialized: SparseIndexReader = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(deserialized.len(), 2);
+        assert_eq!(
+            deserialized
+                .data
+                .forward
+                .values()
+                .map(|value| (value.id, value.count))
+                .collect::<Vec<_>>(),
+            vec![(ids[0], 10), (ids[1], 20)]
+        );
+    }
 }
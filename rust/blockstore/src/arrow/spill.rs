@@ -0,0 +1,146 @@
+use super::{
+    block::{
+        delta::{types::Delta, UnorderedBlockDelta},
+        Block, BlockLoadError, BlockSaveError,
+    },
+    types::{ArrowWriteableKey, ArrowWriteableValue},
+};
+use chroma_error::{ChromaError, ErrorCodes};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use tempfile::TempDir;
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum SpillError {
+    #[error("Could not create spill directory: {0}")]
+    CreateDir(#[source] std::io::Error),
+    #[error("Could not spill delta: {0}")]
+    Save(#[from] BlockSaveError),
+    #[error("Could not load spilled delta: {0}")]
+    Load(#[from] BlockLoadError),
+}
+
+impl ChromaError for SpillError {
+    fn code(&self) -> ErrorCodes {
+        match self {
+            SpillError::CreateDir(_) => ErrorCodes::Internal,
+            SpillError::Save(e) => e.code(),
+            SpillError::Load(e) => e.code(),
+        }
+    }
+}
+
+struct SpilledDelta {
+    path: String,
+    len: usize,
+    size: usize,
+}
+
+/// The deltas of an unordered writer that were spilled to local temp files to keep the writer
+/// under its memory budget. A spilled delta is finished into a block, so its keys are sorted,
+/// and it is loaded back into memory when it is mutated again or when the writer commits.
+/// The files are removed once they are loaded back, or when the spill is dropped.
+pub(super) struct DeltaSpill {
+    memory_budget_bytes: usize,
+    // Created on the first spill, so writers that stay under budget never touch the disk
+    dir: Mutex<Option<TempDir>>,
+    deltas: Mutex<HashMap<Uuid, SpilledDelta>>,
+}
+
+impl DeltaSpill {
+    pub(super) fn new(memory_budget_bytes: usize) -> Self {
+        Self {
+            memory_budget_bytes,
+            dir: Mutex::new(None),
+            deltas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn memory_budget_bytes(&self) -> usize {
+        self.memory_budget_bytes
+    }
+
+    /// Returns the number of deltas that are spilled to disk
+    pub(super) fn len(&self) -> usize {
+        self.deltas.lock().len()
+    }
+
+    /// Returns the id of every spilled delta with its size as it was in memory
+    pub(super) fn sizes(&self) -> Vec<(Uuid, usize)> {
+        self.deltas
+            .lock()
+            .iter()
+            .map(|(id, spilled)| (*id, spilled.size))
+            .collect()
+    }
+
+    /// Write the delta to a temp file and release its memory.
+    pub(super) fn spill<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        delta: UnorderedBlockDelta,
+    ) -> Result<(), SpillError> {
+        let id = delta.id();
+        let len = delta.len();
+        let size = delta.get_size::<K, V>();
+        let path = {
+            let mut dir = self.dir.lock();
+            if dir.is_none() {
+                *dir = Some(tempfile::tempdir().map_err(SpillError::CreateDir)?);
+            }
+            dir.as_ref()
+                .expect("Spill directory was created")
+                .path()
+                .join(id.to_string())
+                .to_string_lossy()
+                .into_owned()
+        };
+        let block = Block::from_record_batch(id, delta.finish::<K, V>(None));
+        block.save(&path)?;
+        tracing::debug!("Spilled delta {:?} with {} keys to {}", id, len, path);
+        self.deltas
+            .lock()
+            .insert(id, SpilledDelta { path, len, size });
+        Ok(())
+    }
+
+    /// Load a spilled delta back into memory and remove its file.
+    /// Returns None if the delta was not spilled.
+    pub(super) fn load<K: ArrowWriteableKey, V: ArrowWriteableValue>(
+        &self,
+        id: &Uuid,
+    ) -> Result<Option<UnorderedBlockDelta>, SpillError> {
+        let Some(spilled) = self.deltas.lock().remove(id) else {
+            return Ok(None);
+        };
+        let block = Block::load(&spilled.path, *id)?;
+        let delta = UnorderedBlockDelta::fork_block::<K, V>(*id, &block);
+        Self::remove_file(&spilled.path);
+        Ok(Some(delta))
+    }
+
+    /// Drop a spilled delta without loading it. Does nothing if the delta was not spilled.
+    pub(super) fn remove(&self, id: &Uuid) {
+        if let Some(spilled) = self.deltas.lock().remove(id) {
+            Self::remove_file(&spilled.path);
+        }
+    }
+
+    /// Take every spilled delta as a finished block along with its number of keys.
+    /// The blocks are memory mapped from their files, so committing them does not load the
+    /// spilled deltas back into memory. The files are removed along with the spill directory.
+    pub(super) fn drain_blocks(&self) -> Result<Vec<(Block, usize)>, SpillError> {
+        let deltas = self.deltas.lock().drain().collect::<Vec<_>>();
+        deltas
+            .into_iter()
+            .map(|(id, spilled)| Ok((Block::load(&spilled.path, id)?, spilled.len)))
+            .collect()
+    }
+
+    fn remove_file(path: &str) {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Could not remove spilled delta {}: {}", path, e);
+        }
+    }
+}
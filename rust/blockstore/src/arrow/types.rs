@@ -1,7 +1,11 @@
 use super::block::delta::{BlockKeyArrowBuilder, BlockStorage, UnorderedBlockDelta};
+use super::block::Block;
 use crate::{key::KeyWrapper, BlockfileWriterMutationOrdering, Key, Value};
-use arrow::{array::Array, datatypes::Field};
+use arrow::{array::Array, datatypes::Field, error::ArrowError};
+use chroma_error::{ChromaError, ErrorCodes};
+use parking_lot::Mutex;
 use std::sync::Arc;
+use thiserror::Error;
 
 pub trait ArrowWriteableKey: Key + Default {
     type ReadableKey<'referred_data>: ArrowReadableKey<'referred_data>;
@@ -30,6 +34,14 @@ pub trait ArrowWriteableValue: Value {
     fn validity_size(item_count: usize) -> usize;
     /// Add a K/V pair to a delta. This is called when a new K/V pair is added to a blockfile.
     fn add(prefix: &str, key: KeyWrapper, value: Self, delta: &BlockStorage);
+    /// The dimension of the value, for values that must have the same dimension across a blockfile (e.g. vectors stored in a fixed size list), see `ValueDimension`. `None` for any other value.
+    fn dimension(_value: &Self) -> Option<usize> {
+        None
+    }
+    /// The dimension of the values in a delta, or in the block an ordered delta was forked from (`old_block`), whose rows may not be copied into the delta yet. `None` if it holds no values or the values have no dimension.
+    fn delta_dimension(_delta: &BlockStorage, _old_block: Option<&Block>) -> Option<usize> {
+        None
+    }
     /// Delete a K/V pair from a delta. This is called when a K/V pair is deleted from a blockfile.
     fn delete(prefix: &str, key: KeyWrapper, delta: &UnorderedBlockDelta);
     /// Returns an appropriate `BlockStorage` instance for the value type. This is called when creating a new delta.
@@ -39,19 +51,78 @@ pub trait ArrowWriteableValue: Value {
     /// Prepare a value for storage in delta or Arrow array.
     fn prepare(value: Self) -> Self::PreparedValue;
     /// Given only a prepared value (not a K/V pair), append it to an Arrow builder. This is called during delta serialization when it's being turned into an Arrow array.
-    fn append(value: Self::PreparedValue, builder: &mut Self::ArrowBuilder);
+    fn append(
+        value: Self::PreparedValue,
+        builder: &mut Self::ArrowBuilder,
+    ) -> Result<(), ArrowError>;
     /// Finish an Arrow builder and return the Arrow array and its corresponding field.
     fn finish(
         builder: Self::ArrowBuilder,
         size_tracker: &Self::SizeTracker,
     ) -> (Field, Arc<dyn Array>);
+    /// Get the prepared value for a K/V pair from the storage of a delta, if present.
     fn get_owned_value_from_delta(
         prefix: &str,
         key: KeyWrapper,
-        delta: &UnorderedBlockDelta,
+        delta: &BlockStorage,
     ) -> Option<Self::PreparedValue>;
 }
 
+#[derive(Error, Debug)]
+#[error("All values of a blockfile must have the same dimension, expected {expected} got {actual}")]
+pub struct VectorDimensionError {
+    expected: usize,
+    actual: usize,
+}
+
+impl ChromaError for VectorDimensionError {
+    fn code(&self) -> ErrorCodes {
+        ErrorCodes::InvalidArgument
+    }
+}
+
+/// The dimension shared by every value of a blockfile, for values that have one. Blocks are
+/// merged and split without checking their values, so the dimension is fixed for the whole
+/// blockfile rather than per block: by the blocks of the blockfile it was forked from, or by the
+/// first value written to a new blockfile.
+#[derive(Clone, Default)]
+pub(crate) struct ValueDimension(Arc<Mutex<Option<usize>>>);
+
+impl ValueDimension {
+    /// Records the dimension of the values of a delta if it is not known yet. Must be called
+    /// before values are deleted from a delta, as their dimension is lost with them.
+    pub(crate) fn observe<V: ArrowWriteableValue>(
+        &self,
+        delta: &BlockStorage,
+        old_block: Option<&Block>,
+    ) {
+        let mut dimension = self.0.lock();
+        if dimension.is_none() {
+            *dimension = V::delta_dimension(delta, old_block);
+        }
+    }
+
+    /// Checks that a value that is about to be added to a delta has the dimension of the
+    /// blockfile. If the dimension is not known yet, the delta or else the value fixes it.
+    pub(crate) fn check<V: ArrowWriteableValue>(
+        &self,
+        value: &V,
+        delta: &BlockStorage,
+        old_block: Option<&Block>,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let Some(actual) = V::dimension(value) else {
+            return Ok(());
+        };
+        let mut dimension = self.0.lock();
+        let expected = *dimension
+            .get_or_insert_with(|| V::delta_dimension(delta, old_block).unwrap_or(actual));
+        if expected != actual {
+            return Err(Box::new(VectorDimensionError { expected, actual }));
+        }
+        Ok(())
+    }
+}
+
 pub trait ArrowReadableKey<'referred_data>: Key + PartialOrd {
     fn get(array: &'referred_data Arc<dyn Array>, index: usize) -> Self;
     fn add_to_delta<'external, V: ArrowReadableValue<'external>>(
@@ -0,0 +1,125 @@
+//! Prints the root and blocks of an Arrow blockfile in local filesystem storage.
+
+use chroma_blockstore::arrow::config::BlockManagerConfig;
+use chroma_blockstore::arrow::encryption::{KeyProvider, LocalFileKeyProvider};
+use chroma_blockstore::arrow::inspect::inspect_blockfile;
+use chroma_storage::{local::LocalStorage, Storage};
+use clap::Parser;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Root directory of the local storage
+    #[arg(long)]
+    storage_path: String,
+    /// Prefix path of the blockfile, empty for legacy blockfiles
+    #[arg(long, default_value = "")]
+    prefix_path: String,
+    /// Id of the blockfile root
+    #[arg(long)]
+    root_id: Uuid,
+    /// Max block size the blockfile was written with
+    #[arg(long, default_value_t = BlockManagerConfig::default().max_block_size_bytes)]
+    max_block_size_bytes: usize,
+    /// Also print every (prefix, key, value) of every block
+    #[arg(long)]
+    dump: bool,
+    /// Directory of the keys an encrypted blockfile was written with, one file per key id
+    #[arg(long)]
+    key_dir: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let storage = Storage::Local(LocalStorage::new(&args.storage_path));
+    // Keys are looked up by the id recorded in each object, so no current key is needed
+    let key_provider = args.key_dir.as_ref().map(|key_dir| {
+        Arc::new(LocalFileKeyProvider::new(key_dir, String::new())) as Arc<dyn KeyProvider>
+    });
+    let inspection =
+        match inspect_blockfile(&storage, &args.prefix_path, args.root_id, key_provider).await {
+            Ok(inspection) => inspection,
+            Err(e) => {
+                eprintln!("Error inspecting blockfile {}: {}", args.root_id, e);
+                std::process::exit(1);
+            }
+        };
+
+    println!("root: {}", inspection.id);
+    println!("version: {}", inspection.version);
+    println!("blocks: {}", inspection.blocks.len());
+    for block in inspection.blocks.iter() {
+        println!();
+        println!("block: {}", block.id);
+        match &block.delimiter {
+            Some((prefix, key)) => println!("  delimiter: ({}, {})", prefix, key),
+            None => println!("  delimiter: START"),
+        }
+        let contents = match &block.contents {
+            Ok(contents) => contents,
+            Err(e) => {
+                println!("  ERROR: {}", e);
+                continue;
+            }
+        };
+        match block.recorded_count {
+            Some(count) => println!("  keys: {} (root records {})", contents.num_keys, count),
+            None => println!("  keys: {}", contents.num_keys),
+        }
+        println!(
+            "  size: {} / {} bytes ({:.1}%), {} bytes in storage",
+            contents.size_bytes,
+            args.max_block_size_bytes,
+            100.0 * contents.size_bytes as f64 / args.max_block_size_bytes as f64,
+            contents.stored_size_bytes
+        );
+        match block.checksum_matches() {
+            Some(true) => println!("  checksum: {:#010x} (ok)", contents.checksum),
+            Some(false) => println!(
+                "  checksum: {:#010x} (MISMATCH, root records {:#010x})",
+                contents.checksum,
+                block.recorded_checksum.unwrap_or_default()
+            ),
+            None => println!("  checksum: {:#010x} (not recorded)", contents.checksum),
+        }
+        println!("  schema:");
+        for field in contents.schema.fields() {
+            println!(
+                "    {}: {}{}",
+                field.name(),
+                field.data_type(),
+                if field.is_nullable() {
+                    " (nullable)"
+                } else {
+                    ""
+                }
+            );
+        }
+        if args.dump {
+            match contents.entries() {
+                Ok(entries) => {
+                    println!("  entries:");
+                    for (prefix, key, value) in entries {
+                        println!("    ({}, {}) => {}", prefix, key, value);
+                    }
+                }
+                Err(e) => println!("  entries: error formatting entries: {}", e),
+            }
+        }
+    }
+
+    let bad_blocks = inspection.bad_blocks().collect::<Vec<_>>();
+    if !bad_blocks.is_empty() {
+        println!();
+        println!("bad blocks: {}", bad_blocks.len());
+        for block in bad_blocks {
+            match &block.contents {
+                Ok(_) => println!("  {}: checksum mismatch", block.id),
+                Err(e) => println!("  {}: {}", block.id, e),
+            }
+        }
+        std::process::exit(1);
+    }
+}
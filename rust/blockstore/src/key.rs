@@ -1,7 +1,8 @@
-use super::Key;
+use super::{BytesKey, Key};
 use chroma_error::{ChromaError, ErrorCodes};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
+use std::ops::{Bound, RangeBounds};
 
 // TODO(rescrv):  This used to be a panic/unwrap, but could be a nicer type.
 #[derive(thiserror::Error, Debug)]
@@ -20,6 +21,9 @@ pub enum KeyWrapper {
     Float32(f32),
     Bool(bool),
     Uint32(u32),
+    Uint64(u64),
+    Int64(i64),
+    Bytes(Vec<u8>),
 }
 
 impl KeyWrapper {
@@ -30,6 +34,9 @@ impl KeyWrapper {
             KeyWrapper::Float32(_) => 4,
             KeyWrapper::Bool(_) => 1,
             KeyWrapper::Uint32(_) => 4,
+            KeyWrapper::Uint64(_) => 8,
+            KeyWrapper::Int64(_) => 8,
+            KeyWrapper::Bytes(b) => b.len(),
         }
     }
 }
@@ -102,6 +109,57 @@ impl TryFrom<&KeyWrapper> for u32 {
     }
 }
 
+impl From<u64> for KeyWrapper {
+    fn from(u: u64) -> KeyWrapper {
+        KeyWrapper::Uint64(u)
+    }
+}
+
+impl TryFrom<&KeyWrapper> for u64 {
+    type Error = InvalidKeyConversion;
+
+    fn try_from(key: &KeyWrapper) -> Result<Self, InvalidKeyConversion> {
+        match key {
+            KeyWrapper::Uint64(u) => Ok(*u),
+            _ => Err(InvalidKeyConversion),
+        }
+    }
+}
+
+impl From<i64> for KeyWrapper {
+    fn from(i: i64) -> KeyWrapper {
+        KeyWrapper::Int64(i)
+    }
+}
+
+impl TryFrom<&KeyWrapper> for i64 {
+    type Error = InvalidKeyConversion;
+
+    fn try_from(key: &KeyWrapper) -> Result<Self, InvalidKeyConversion> {
+        match key {
+            KeyWrapper::Int64(i) => Ok(*i),
+            _ => Err(InvalidKeyConversion),
+        }
+    }
+}
+
+impl From<BytesKey<'_>> for KeyWrapper {
+    fn from(b: BytesKey<'_>) -> KeyWrapper {
+        KeyWrapper::Bytes(b.0.to_vec())
+    }
+}
+
+impl<'referred_data> TryFrom<&'referred_data KeyWrapper> for BytesKey<'referred_data> {
+    type Error = InvalidKeyConversion;
+
+    fn try_from(key: &'referred_data KeyWrapper) -> Result<Self, InvalidKeyConversion> {
+        match key {
+            KeyWrapper::Bytes(b) => Ok(BytesKey(b)),
+            _ => Err(InvalidKeyConversion),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CompositeKey {
     pub(super) prefix: String,
@@ -160,9 +218,106 @@ impl Ord for CompositeKey {
                     KeyWrapper::Uint32(u2) => u1.cmp(u2),
                     _ => panic!("Invalid comparison"),
                 },
+                KeyWrapper::Uint64(u1) => match &other.key {
+                    KeyWrapper::Uint64(u2) => u1.cmp(u2),
+                    _ => panic!("Invalid comparison"),
+                },
+                KeyWrapper::Int64(i1) => match &other.key {
+                    KeyWrapper::Int64(i2) => i1.cmp(i2),
+                    _ => panic!("Invalid comparison"),
+                },
+                KeyWrapper::Bytes(ref b1) => match &other.key {
+                    KeyWrapper::Bytes(b2) => b1.cmp(b2),
+                    _ => panic!("Invalid comparison"),
+                },
             }
         } else {
             self.prefix.cmp(&other.prefix)
         }
     }
 }
+
+/// A range of keys under a single prefix, e.g. the keys removed by `BlockfileWriter::delete_range()`.
+/// An unbounded end of the range extends to the first or the last key of the prefix.
+#[derive(Clone, Debug)]
+pub(crate) struct CompositeKeyRange {
+    pub(crate) prefix: String,
+    start: Bound<CompositeKey>,
+    end: Bound<CompositeKey>,
+}
+
+impl CompositeKeyRange {
+    pub(crate) fn new<K: Into<KeyWrapper> + Clone>(
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Self {
+        let to_composite_key = |key: &K| CompositeKey {
+            prefix: prefix.to_string(),
+            key: key.clone().into(),
+        };
+        Self {
+            prefix: prefix.to_string(),
+            start: key_range.start_bound().map(to_composite_key),
+            end: key_range.end_bound().map(to_composite_key),
+        }
+    }
+
+    pub(crate) fn start_bound(&self) -> Bound<&CompositeKey> {
+        self.start.as_ref()
+    }
+
+    pub(crate) fn end_bound(&self) -> Bound<&CompositeKey> {
+        self.end.as_ref()
+    }
+
+    /// Returns true if no key can be in the range, following the semantics of `BTreeMap::range()`
+    /// for bounds that are out of order.
+    pub(crate) fn is_empty(&self) -> bool {
+        match (&self.start, &self.end) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start), Bound::Excluded(end))
+            | (Bound::Excluded(start), Bound::Included(end))
+            | (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the key is not before the start of the range.
+    pub(crate) fn is_after_start(&self, key: &CompositeKey) -> bool {
+        match &self.start {
+            Bound::Included(start) => key >= start,
+            Bound::Excluded(start) => key > start,
+            Bound::Unbounded => key.prefix >= self.prefix,
+        }
+    }
+
+    /// Returns true if the key is not past the end of the range.
+    pub(crate) fn is_before_end(&self, key: &CompositeKey) -> bool {
+        match &self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => key.prefix <= self.prefix,
+        }
+    }
+
+    pub(crate) fn contains(&self, key: &CompositeKey) -> bool {
+        self.is_after_start(key) && self.is_before_end(key)
+    }
+
+    /// Returns true if the range may contain keys that are less than the given key.
+    pub(crate) fn starts_before(&self, key: &CompositeKey) -> bool {
+        match &self.start {
+            Bound::Included(start) | Bound::Excluded(start) => start < key,
+            Bound::Unbounded => self.prefix <= key.prefix,
+        }
+    }
+
+    /// Returns true if every key that is less than the given key and not before the start of the
+    /// range is in the range.
+    pub(crate) fn covers_up_to(&self, key: &CompositeKey) -> bool {
+        match &self.end {
+            Bound::Included(end) | Bound::Excluded(end) => key <= end,
+            Bound::Unbounded => key.prefix <= self.prefix,
+        }
+    }
+}
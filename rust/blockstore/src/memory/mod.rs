@@ -1,4 +1,4 @@
 pub(crate) mod provider;
 pub(super) mod reader_writer;
 pub(crate) mod storage;
-pub use storage::Writeable;
+pub use storage::{PreparedReadable, Writeable};
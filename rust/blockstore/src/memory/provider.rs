@@ -38,24 +38,26 @@ impl MemoryBlockfileProvider {
         &self,
         id: &uuid::Uuid,
     ) -> Result<BlockfileReader<'new, K, V>, Box<OpenError>> {
-        let reader = MemoryBlockfileReader::open(*id, self.storage_manager.clone());
-        Ok(BlockfileReader::<K, V>::MemoryBlockfileReader(reader))
+        match MemoryBlockfileReader::open(*id, self.storage_manager.clone()) {
+            Some(reader) => Ok(BlockfileReader::<K, V>::MemoryBlockfileReader(reader)),
+            None => Err(Box::new(OpenError::NotFound)),
+        }
     }
 
     pub(crate) fn write(
         &self,
         options: BlockfileWriterOptions,
     ) -> Result<BlockfileWriter, Box<CreateError>> {
-        if options.mutation_ordering != BlockfileWriterMutationOrdering::Unordered {
-            unimplemented!();
-        }
+        let writer = match options.fork_from {
+            Some(fork_from) => MemoryBlockfileWriter::fork(self.storage_manager.clone(), fork_from)
+                .ok_or_else(|| Box::new(CreateError::Other(Box::new(OpenError::NotFound))))?,
+            None => MemoryBlockfileWriter::new(self.storage_manager.clone()),
+        };
 
-        if options.fork_from.is_some() {
-            unimplemented!();
-        }
-
-        let writer: MemoryBlockfileWriter =
-            MemoryBlockfileWriter::new(self.storage_manager.clone());
+        let writer = match options.mutation_ordering {
+            BlockfileWriterMutationOrdering::Ordered => writer.with_ordered_mutations(),
+            BlockfileWriterMutationOrdering::Unordered => writer,
+        };
         Ok(BlockfileWriter::MemoryBlockfileWriter(writer))
     }
 
@@ -66,9 +68,14 @@ impl MemoryBlockfileProvider {
 
 #[cfg(test)]
 mod tests {
-    use chroma_types::{Chunk, DataRecord, LogRecord, Operation, OperationRecord};
+    use chroma_error::{ChromaError, ErrorCodes};
+    use chroma_types::{
+        Chunk, DataRecord, LogRecord, Operation, OperationRecord, SpannPostingList,
+    };
+    use uuid::Uuid;
 
     use super::*;
+    use crate::{arrow::provider::BlockfileReaderOptions, provider::BlockfileProvider};
 
     #[test]
     fn test_data_record() {
@@ -131,4 +138,282 @@ mod tests {
         // assert_eq!(record.id, "embedding_id_1");
         // assert_eq!(record.embedding, &[7.0, 8.0, 9.0]);
     }
+
+    #[tokio::test]
+    async fn test_ordered_writer_and_fork() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).ordered_mutations())
+            .await
+            .unwrap();
+        for key in ["a", "b", "c"] {
+            writer
+                .set("prefix", key, format!("value_{}", key))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            writer
+                .get_owned::<&str, String>("prefix", "c")
+                .await
+                .unwrap(),
+            Some("value_c".to_string())
+        );
+        assert!(writer
+            .set("prefix", "a", "value".to_string())
+            .await
+            .is_err());
+        assert!(writer.delete::<&str, String>("prefix", "c").await.is_err());
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        assert_eq!(flusher.count(), 3);
+        assert_eq!(flusher.num_entries(), 3);
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+        assert_eq!(provider.prefetch(&id, "").await.unwrap(), 0);
+
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).fork(id))
+            .await
+            .unwrap();
+        assert_eq!(
+            writer
+                .get_owned::<&str, String>("prefix", "b")
+                .await
+                .unwrap(),
+            Some("value_b".to_string())
+        );
+        writer.delete::<&str, String>("prefix", "b").await.unwrap();
+        writer
+            .set("prefix", "d", "value_d".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            writer
+                .get_owned::<&str, String>("prefix", "b")
+                .await
+                .unwrap(),
+            None
+        );
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        assert_eq!(flusher.count(), 3);
+        let forked_id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(forked_id, String::new()))
+            .await
+            .unwrap();
+        reader
+            .load_blocks_for_keys([("prefix".to_string(), "a")])
+            .await;
+        reader.load_blocks_for_prefixes(["prefix"]).await;
+        let values = reader.get_range("prefix"..="prefix", ..).await.unwrap();
+        assert_eq!(
+            values,
+            vec![
+                ("prefix", "a", "value_a"),
+                ("prefix", "c", "value_c"),
+                ("prefix", "d", "value_d")
+            ]
+        );
+
+        // The blockfile that was forked from is unchanged
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        assert_eq!(reader.get("prefix", "b").await.unwrap(), Some("value_b"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_range() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()))
+            .await
+            .unwrap();
+        for prefix in ["a", "b", "c"] {
+            for key in ["1", "2", "3"] {
+                writer
+                    .set(prefix, key, format!("{}{}", prefix, key))
+                    .await
+                    .unwrap();
+            }
+        }
+        writer.delete_prefix::<&str, String>("a").await.unwrap();
+        writer
+            .delete_range::<&str, String>("c", "2"..)
+            .await
+            .unwrap();
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        assert_eq!(flusher.count(), 4);
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        let values = reader.get_range(.., ..).await.unwrap();
+        assert_eq!(
+            values,
+            vec![
+                ("b", "1", "b1"),
+                ("b", "2", "b2"),
+                ("b", "3", "b3"),
+                ("c", "1", "c1")
+            ]
+        );
+
+        // Ordered writers only accept keys past the end of a deleted range
+        let writer = provider
+            .write::<&str, String>(
+                BlockfileWriterOptions::new(String::new())
+                    .ordered_mutations()
+                    .fork(id),
+            )
+            .await
+            .unwrap();
+        writer
+            .delete_range::<&str, String>("b", .."2")
+            .await
+            .unwrap();
+        assert!(writer.set("b", "1", "value".to_string()).await.is_err());
+        assert!(writer.delete_prefix::<&str, String>("b").await.is_err());
+        writer
+            .delete_range::<&str, String>("b", "2"..)
+            .await
+            .unwrap();
+        assert!(writer.set("b", "3", "value".to_string()).await.is_err());
+        writer.set("c", "2", "c2".to_string()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_many() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()))
+            .await
+            .unwrap();
+        for key in ["a", "b", "c"] {
+            writer
+                .set("prefix", key, format!("value_{}", key))
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        let values = reader
+            .get_many([
+                ("prefix".to_string(), "c"),
+                ("prefix".to_string(), "d"),
+                ("prefix".to_string(), "a"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(values, vec![Some("value_c"), None, Some("value_a")]);
+    }
+
+    #[tokio::test]
+    async fn test_get_by_rank_and_sample() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()))
+            .await
+            .unwrap();
+        for (prefix, key) in [("a", "1"), ("b", "1"), ("b", "2"), ("b", "3"), ("c", "1")] {
+            writer
+                .set(prefix, key, format!("{}{}", prefix, key))
+                .await
+                .unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        assert_eq!(reader.get_by_rank("b", 0).await.unwrap(), Some(("1", "b1")));
+        assert_eq!(reader.get_by_rank("b", 2).await.unwrap(), Some(("3", "b3")));
+        assert_eq!(reader.get_by_rank("b", 3).await.unwrap(), None);
+        assert_eq!(reader.get_by_rank("d", 0).await.unwrap(), None);
+
+        let sample = reader.sample(3).await.unwrap();
+        assert_eq!(sample.len(), 3);
+        for window in sample.windows(2) {
+            assert!((window[0].0, window[0].1) < (window[1].0, window[1].1));
+        }
+        for (prefix, key, value) in sample {
+            assert_eq!(value, format!("{}{}", prefix, key));
+        }
+        assert_eq!(reader.sample(10).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_get_prefixes_with_counts() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()))
+            .await
+            .unwrap();
+        for (prefix, key) in [("a", "1"), ("b", "1"), ("b", "2"), ("c", "1")] {
+            writer.set(prefix, key, "value".to_string()).await.unwrap();
+        }
+        let flusher = writer.commit::<&str, String>().await.unwrap();
+        let id = flusher.id();
+        flusher.flush::<&str, String>().await.unwrap();
+
+        let reader = provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+            .unwrap();
+        assert_eq!(
+            reader.get_prefixes_with_counts().await.unwrap(),
+            vec![
+                ("a".to_string(), 1),
+                ("b".to_string(), 2),
+                ("c".to_string(), 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_owned_unsupported_value() {
+        let provider = BlockfileProvider::new_memory();
+        let writer = provider
+            .write::<u32, &SpannPostingList<'_>>(BlockfileWriterOptions::new(String::new()))
+            .await
+            .unwrap();
+        let result = writer
+            .get_owned::<u32, &SpannPostingList<'_>>("prefix", 1)
+            .await;
+        assert!(matches!(result, Err(e) if e.code() == ErrorCodes::Unimplemented));
+    }
+
+    #[tokio::test]
+    async fn test_missing_blockfile() {
+        let provider = BlockfileProvider::new_memory();
+        let id = Uuid::new_v4();
+        match provider
+            .read::<&str, &str>(BlockfileReaderOptions::new(id, String::new()))
+            .await
+        {
+            Err(e) => assert!(matches!(*e, OpenError::NotFound)),
+            Ok(_) => panic!("Expected the blockfile to be missing"),
+        }
+        let err = provider
+            .write::<&str, String>(BlockfileWriterOptions::new(String::new()).fork(id))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), ErrorCodes::NotFound);
+    }
 }
@@ -1,21 +1,28 @@
-use std::ops::RangeBounds;
+use std::{ops::RangeBounds, sync::Arc};
 
 use super::{
     super::{BlockfileError, Key, Value},
-    storage::{Readable, Storage, StorageBuilder, StorageManager, Writeable},
+    storage::{PreparedReadable, Readable, Storage, StorageBuilder, StorageManager, Writeable},
+};
+use crate::{
+    key::{CompositeKeyRange, InvalidKeyConversion, KeyWrapper},
+    types::MutationOrderChecker,
 };
-use crate::key::{InvalidKeyConversion, KeyWrapper};
 use chroma_error::ChromaError;
+use parking_lot::Mutex;
 
 #[derive(Clone)]
 pub struct MemoryBlockfileWriter {
     builder: StorageBuilder,
     storage_manager: StorageManager,
     id: uuid::Uuid,
+    // Only set for writers with ordered mutations
+    order_checker: Option<Arc<Mutex<MutationOrderChecker>>>,
 }
 
 pub struct MemoryBlockfileFlusher {
     id: uuid::Uuid,
+    count: u64,
 }
 
 impl MemoryBlockfileFlusher {
@@ -23,6 +30,15 @@ impl MemoryBlockfileFlusher {
         self.id
     }
 
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// There are no blocks, so every entry of the blockfile counts as written.
+    pub(crate) fn num_entries(&self) -> usize {
+        self.count as usize
+    }
+
     pub(crate) fn prefix_path(&self) -> &str {
         ""
     }
@@ -31,17 +47,37 @@ impl MemoryBlockfileFlusher {
 impl MemoryBlockfileWriter {
     pub(super) fn new(storage_manager: StorageManager) -> Self {
         let builder = storage_manager.create();
+        Self::from_builder(builder, storage_manager)
+    }
+
+    /// Returns `None` if there is no committed blockfile `fork_from`.
+    pub(super) fn fork(storage_manager: StorageManager, fork_from: uuid::Uuid) -> Option<Self> {
+        let builder = storage_manager.fork(fork_from)?;
+        Some(Self::from_builder(builder, storage_manager))
+    }
+
+    fn from_builder(builder: StorageBuilder, storage_manager: StorageManager) -> Self {
         let id = builder.id;
         Self {
             builder,
             storage_manager,
             id,
+            order_checker: None,
         }
     }
 
+    /// Reject mutations that are not in ascending key order, like the Arrow ordered writer does.
+    pub(super) fn with_ordered_mutations(mut self) -> Self {
+        self.order_checker = Some(Arc::new(Mutex::new(MutationOrderChecker::default())));
+        self
+    }
+
     pub(crate) fn commit(&self) -> Result<MemoryBlockfileFlusher, Box<dyn ChromaError>> {
-        self.storage_manager.commit(self.builder.id);
-        Ok(MemoryBlockfileFlusher { id: self.id })
+        let storage = self.storage_manager.commit(self.builder.id);
+        Ok(MemoryBlockfileFlusher {
+            id: self.id,
+            count: storage.len() as u64,
+        })
     }
 
     pub(crate) fn set<K: Key + Into<KeyWrapper>, V: Value + Writeable>(
@@ -51,6 +87,12 @@ impl MemoryBlockfileWriter {
         value: V,
     ) -> Result<(), Box<dyn ChromaError>> {
         let key = key.clone().into();
+        if let Some(order_checker) = &self.order_checker {
+            order_checker
+                .lock()
+                .check_mutation(prefix, &key)
+                .map_err(|e| e.boxed())?;
+        }
         V::write_to_storage(prefix, key, value, &self.builder);
         Ok(())
     }
@@ -61,10 +103,50 @@ impl MemoryBlockfileWriter {
         key: K,
     ) -> Result<(), Box<dyn ChromaError>> {
         let key = key.into();
+        if let Some(order_checker) = &self.order_checker {
+            order_checker
+                .lock()
+                .check_mutation(prefix, &key)
+                .map_err(|e| e.boxed())?;
+        }
         V::remove_from_storage(prefix, key, &self.builder);
         Ok(())
     }
 
+    pub(crate) fn delete_range<K: Key + Into<KeyWrapper>>(
+        &self,
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        let range = CompositeKeyRange::new(prefix, key_range);
+        if range.is_empty() {
+            return Ok(());
+        }
+        if let Some(order_checker) = &self.order_checker {
+            order_checker
+                .lock()
+                .check_range_mutation(&range)
+                .map_err(|e| e.boxed())?;
+        }
+        self.builder.remove_range(&range);
+        Ok(())
+    }
+
+    pub(crate) fn get_owned<K: Key + Into<KeyWrapper>, V: Value + PreparedReadable>(
+        &self,
+        prefix: &str,
+        key: K,
+    ) -> Result<Option<V::PreparedValue>, Box<dyn ChromaError>> {
+        let key = key.into();
+        if let Some(order_checker) = &self.order_checker {
+            order_checker
+                .lock()
+                .check_read(prefix, &key)
+                .map_err(|e| e.boxed())?;
+        }
+        V::read_prepared_from_storage(prefix, key, &self.builder)
+    }
+
     pub(crate) fn id(&self) -> uuid::Uuid {
         self.id
     }
@@ -83,13 +165,14 @@ impl<
         V: Value + Readable<'storage>,
     > MemoryBlockfileReader<K, V>
 {
-    pub(crate) fn open(id: uuid::Uuid, storage_manager: StorageManager) -> Self {
-        let storage = storage_manager.get(id).unwrap();
-        Self {
+    /// Returns `None` if there is no committed blockfile `id`.
+    pub(crate) fn open(id: uuid::Uuid, storage_manager: StorageManager) -> Option<Self> {
+        let storage = storage_manager.get(id)?;
+        Some(Self {
             _storage_manager: storage_manager,
             storage,
             marker: std::marker::PhantomData,
-        }
+        })
     }
 
     pub(crate) fn get(
@@ -101,11 +184,23 @@ impl<
         Ok(V::read_from_storage(prefix, key, &self.storage))
     }
 
+    pub(crate) fn get_many(
+        &'storage self,
+        keys: impl IntoIterator<Item = (String, K)>,
+    ) -> Vec<Option<V>> {
+        keys.into_iter()
+            .map(|(prefix, key)| V::read_from_storage(&prefix, key.into(), &self.storage))
+            .collect()
+    }
+
     pub(crate) fn get_range_iter<'prefix, PrefixRange, KeyRange>(
         &'storage self,
         prefix_range: PrefixRange,
         key_range: KeyRange,
-    ) -> Result<impl Iterator<Item = (&'storage str, K, V)> + 'storage, Box<dyn ChromaError>>
+    ) -> Result<
+        impl DoubleEndedIterator<Item = (&'storage str, K, V)> + 'storage,
+        Box<dyn ChromaError>,
+    >
     where
         PrefixRange: RangeBounds<&'prefix str>,
         KeyRange: RangeBounds<K>,
@@ -127,10 +222,26 @@ impl<
             .map(|(key, value)| (key.prefix.as_str(), K::try_from(&key.key).unwrap(), value)))
     }
 
+    pub(crate) fn get_range_iter_rev<'prefix, PrefixRange, KeyRange>(
+        &'storage self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> Result<impl Iterator<Item = (&'storage str, K, V)> + 'storage, Box<dyn ChromaError>>
+    where
+        PrefixRange: RangeBounds<&'prefix str>,
+        KeyRange: RangeBounds<K>,
+    {
+        Ok(self.get_range_iter(prefix_range, key_range)?.rev())
+    }
+
     pub(crate) fn count(&self) -> Result<usize, Box<dyn ChromaError>> {
         V::count(&self.storage)
     }
 
+    pub(crate) fn get_prefixes_with_counts(&self) -> Vec<(String, usize)> {
+        self.storage.prefix_counts()
+    }
+
     pub(crate) fn contains(&'storage self, prefix: &str, key: K) -> bool {
         V::contains(prefix, key.into(), &self.storage)
     }
@@ -142,6 +253,27 @@ impl<
     pub(crate) fn rank(&'storage self, prefix: &'storage str, key: K) -> usize {
         V::rank(prefix, key.into(), &self.storage)
     }
+
+    pub(crate) fn get_by_rank(&'storage self, prefix: &str, rank: usize) -> Option<(K, V)> {
+        V::read_range_from_storage(prefix..=prefix, .., &self.storage)
+            .into_iter()
+            .nth(rank)
+            .map(|(key, value)| (K::try_from(&key.key).unwrap(), value))
+    }
+
+    pub(crate) fn sample(&'storage self, n: usize) -> Vec<(&'storage str, K, V)> {
+        let values = V::read_range_from_storage(.., .., &self.storage);
+        let mut positions =
+            rand::seq::index::sample(&mut rand::thread_rng(), values.len(), n.min(values.len()))
+                .into_vec();
+        positions.sort_unstable();
+        values
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| positions.binary_search(index).is_ok())
+            .map(|(_, (key, value))| (key.prefix.as_str(), K::try_from(&key.key).unwrap(), value))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -149,6 +281,7 @@ mod tests {
     use std::ops::Bound;
 
     use super::*;
+    use crate::BytesKey;
     use chroma_types::{Chunk, DataRecord, LogRecord, Operation, OperationRecord};
 
     #[test]
@@ -159,7 +292,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<&str, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let value = reader.get("prefix", "key1").unwrap().unwrap();
         assert_eq!(value, "value1");
     }
@@ -176,7 +309,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<&str, roaring::RoaringBitmap> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let value = reader.get("prefix", "bitmap1").unwrap().unwrap();
         assert!(value.contains(1));
         assert!(value.contains(2));
@@ -251,7 +384,7 @@ mod tests {
         writer.commit().unwrap();
 
         let reader: MemoryBlockfileReader<&str, DataRecord> =
-            MemoryBlockfileReader::open(id, storage_manager);
+            MemoryBlockfileReader::open(id, storage_manager).unwrap();
         let record = reader.get("prefix", "embedding_id_1").unwrap().unwrap();
         assert_eq!(record.id, "embedding_id_1");
         assert_eq!(record.embedding, vec![1.0, 2.0, 3.0]);
@@ -265,7 +398,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<bool, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let value = reader.get("prefix", true).unwrap();
         assert_eq!(value, Some("value1"));
     }
@@ -278,11 +411,93 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let value = reader.get("prefix", 1).unwrap();
         assert_eq!(value, Some("value1"));
     }
 
+    #[test]
+    fn test_u64_key() {
+        let storage_manager = StorageManager::new();
+        let writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        let _ = writer.set("prefix", u64::MAX, "value1".to_string());
+        let _ = writer.set("prefix", 1u64, "value2".to_string());
+        let _ = writer.commit();
+
+        let reader: MemoryBlockfileReader<u64, &str> =
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
+        assert_eq!(reader.get("prefix", u64::MAX).unwrap(), Some("value1"));
+        let values = reader
+            .get_range_iter("prefix"..="prefix", ..)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![("prefix", 1, "value2"), ("prefix", u64::MAX, "value1")]
+        );
+    }
+
+    #[test]
+    fn test_i64_key() {
+        let storage_manager = StorageManager::new();
+        let writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        let _ = writer.set("prefix", 5i64, "value1".to_string());
+        let _ = writer.set("prefix", -5i64, "value2".to_string());
+        let _ = writer.commit();
+
+        let reader: MemoryBlockfileReader<i64, &str> =
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
+        assert_eq!(reader.get("prefix", -5).unwrap(), Some("value2"));
+        let values = reader
+            .get_range_iter("prefix"..="prefix", ..0)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![("prefix", -5, "value2")]);
+    }
+
+    #[test]
+    fn test_bytes_key() {
+        let storage_manager = StorageManager::new();
+        let writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        let _ = writer.set("prefix", BytesKey(&[0xff, 0x00]), "value1".to_string());
+        let _ = writer.set("prefix", BytesKey(&[0x01]), "value2".to_string());
+        let _ = writer.commit();
+
+        let reader: MemoryBlockfileReader<BytesKey, &str> =
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
+        assert_eq!(
+            reader.get("prefix", BytesKey(&[0xff, 0x00])).unwrap(),
+            Some("value1")
+        );
+        assert_eq!(reader.get("prefix", BytesKey(&[0xff])).unwrap(), None);
+        assert_eq!(BytesKey(&[0xff, 0x00, 0x0a]).to_string(), "ff000a");
+    }
+
+    #[test]
+    fn test_f32_vector_and_bytes_values() {
+        let storage_manager = StorageManager::new();
+        let vector_writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        let _ = vector_writer.set("prefix", "key1", vec![1.0f32, 2.0, 3.0]);
+        let _ = vector_writer.commit();
+        let bytes_writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        let _ = bytes_writer.set("prefix", "key1", vec![0xde_u8, 0xad]);
+        let _ = bytes_writer.commit();
+
+        let vector_reader: MemoryBlockfileReader<&str, &[f32]> =
+            MemoryBlockfileReader::open(vector_writer.id, storage_manager.clone()).unwrap();
+        assert_eq!(
+            vector_reader.get("prefix", "key1").unwrap(),
+            Some(&[1.0f32, 2.0, 3.0][..])
+        );
+        let bytes_reader: MemoryBlockfileReader<&str, &[u8]> =
+            MemoryBlockfileReader::open(bytes_writer.id, storage_manager).unwrap();
+        assert_eq!(
+            bytes_reader.get("prefix", "key1").unwrap(),
+            Some(&[0xde_u8, 0xad][..])
+        );
+        assert_eq!(bytes_reader.get("prefix", "key2").unwrap(), None);
+    }
+
     #[test]
     fn test_float32_key() {
         let storage_manager = StorageManager::new();
@@ -291,7 +506,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let value = reader.get("prefix", 1.0).unwrap();
         assert_eq!(value, Some("value1"));
     }
@@ -306,7 +521,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<&str, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -328,7 +543,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values =
             reader.get_range_iter("prefix"..="prefix", (Bound::Excluded(3), Bound::Unbounded));
         assert!(values.is_err());
@@ -344,7 +559,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader
             .get_range_iter("prefix"..="prefix", (Bound::Excluded(0), Bound::Unbounded))
             .unwrap();
@@ -371,7 +586,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader
             .get_range_iter("prefix"..="prefix", (Bound::Excluded(1), Bound::Unbounded))
             .unwrap();
@@ -395,7 +610,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter(
             "prefix"..="prefix",
             (Bound::Excluded(3.0), Bound::Unbounded),
@@ -413,7 +628,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader
             .get_range_iter(
                 "prefix"..="prefix",
@@ -443,7 +658,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader
             .get_range_iter(
                 "prefix"..="prefix",
@@ -470,7 +685,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", 4..);
         assert!(values.is_err());
     }
@@ -485,7 +700,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", 1..).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -510,7 +725,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", 2..).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -532,7 +747,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", 3.5..);
         assert!(values.is_err());
     }
@@ -547,7 +762,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", 0.5..).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -572,7 +787,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", 1.5..).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -594,7 +809,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", ..1);
         assert!(values.is_err());
     }
@@ -609,7 +824,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..4).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -634,7 +849,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..3).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -656,7 +871,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", ..0.5);
         assert!(values.is_err());
     }
@@ -671,7 +886,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..3.5).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -696,7 +911,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..2.5).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -718,7 +933,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", ..=0);
         assert!(values.is_err());
     }
@@ -733,7 +948,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..=3).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -758,7 +973,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<u32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..=2).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -780,7 +995,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let values = reader.get_range_iter("prefix"..="prefix", ..=0.5);
         assert!(values.is_err());
     }
@@ -795,7 +1010,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..=3.0).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 3);
@@ -820,7 +1035,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<f32, &str> =
-            MemoryBlockfileReader::open(writer.id, storage_manager);
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
         let range_iter = reader.get_range_iter("prefix"..="prefix", ..=2.0).unwrap();
         let values = range_iter.collect::<Vec<_>>();
         assert_eq!(values.len(), 2);
@@ -845,7 +1060,7 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<&str, &str> =
-            MemoryBlockfileReader::open(id, storage_manager.clone());
+            MemoryBlockfileReader::open(id, storage_manager.clone()).unwrap();
         let key_2 = reader.get("prefix", "key2").unwrap();
         assert_eq!(key_2, Some("value2"));
         let key_3 = reader.get("different_prefix", "key3").unwrap();
@@ -870,11 +1085,41 @@ mod tests {
         let _ = writer.commit();
 
         let reader: MemoryBlockfileReader<&str, &str> =
-            MemoryBlockfileReader::open(id, storage_manager.clone());
+            MemoryBlockfileReader::open(id, storage_manager.clone()).unwrap();
         for i in 0..n {
             let rank_key = format!("key{:04}", i);
             let rank = MemoryBlockfileReader::<&str, &str>::rank(&reader, "prefix", &rank_key);
             assert_eq!(rank, i);
         }
     }
+
+    #[test]
+    fn test_get_range_iter_rev() {
+        let storage_manager = StorageManager::new();
+        let writer = MemoryBlockfileWriter::new(storage_manager.clone());
+        for prefix in ["a", "b"] {
+            for i in 0..10u32 {
+                writer.set(prefix, i, format!("{}/{}", prefix, i)).unwrap();
+            }
+        }
+        let _ = writer.commit();
+
+        let reader: MemoryBlockfileReader<u32, &str> =
+            MemoryBlockfileReader::open(writer.id, storage_manager).unwrap();
+        let values = reader
+            .get_range_iter_rev("a"..="b", 3..6)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ("b", 5, "b/5"),
+                ("b", 4, "b/4"),
+                ("b", 3, "b/3"),
+                ("a", 5, "a/5"),
+                ("a", 4, "a/4"),
+                ("a", 3, "a/3"),
+            ]
+        );
+    }
 }
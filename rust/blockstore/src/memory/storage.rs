@@ -1,5 +1,6 @@
-use crate::key::{CompositeKey, KeyWrapper};
-use chroma_error::ChromaError;
+use crate::arrow::types::ArrowWriteableValue;
+use crate::key::{CompositeKey, CompositeKeyRange, KeyWrapper};
+use chroma_error::{ChromaError, ErrorCodes};
 use chroma_types::{DataRecord, SpannPostingList};
 use parking_lot::RwLock;
 use roaring::RoaringBitmap;
@@ -7,12 +8,34 @@ use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
 };
+use thiserror::Error;
 
 pub trait Writeable {
     fn write_to_storage(prefix: &str, key: KeyWrapper, value: Self, storage: &StorageBuilder);
     fn remove_from_storage(prefix: &str, key: KeyWrapper, storage: &StorageBuilder);
 }
 
+/// Reads a value back from a `StorageBuilder` that has not been committed yet, in the form
+/// returned by `BlockfileWriter::get_owned()`.
+pub trait PreparedReadable: ArrowWriteableValue {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>>;
+}
+
+/// Returned when a value type cannot be read back from the memory blockfile
+#[derive(Error, Debug)]
+#[error("{0} values are not supported by the memory blockfile")]
+pub struct UnsupportedValueError(&'static str);
+
+impl ChromaError for UnsupportedValueError {
+    fn code(&self) -> ErrorCodes {
+        ErrorCodes::Unimplemented
+    }
+}
+
 pub trait Readable<'referred_data>: Sized {
     fn read_from_storage(
         prefix: &str,
@@ -65,6 +88,25 @@ impl Writeable for String {
     }
 }
 
+impl PreparedReadable for String {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .string_value_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(value.clone())))
+    }
+}
+
 impl<'referred_data> Readable<'referred_data> for &'referred_data str {
     fn read_from_storage(
         prefix: &str,
@@ -156,6 +198,25 @@ impl Writeable for Vec<u32> {
     }
 }
 
+impl PreparedReadable for Vec<u32> {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .uint32_array_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(value.clone())))
+    }
+}
+
 impl<'referred_data> Readable<'referred_data> for &'referred_data [u32] {
     fn read_from_storage(
         prefix: &str,
@@ -217,6 +278,219 @@ impl<'referred_data> Readable<'referred_data> for &'referred_data [u32] {
     }
 }
 
+impl Writeable for Vec<f32> {
+    fn write_to_storage(prefix: &str, key: KeyWrapper, value: Self, storage: &StorageBuilder) {
+        storage
+            .float32_array_storage
+            .write()
+            .as_mut()
+            .unwrap()
+            .insert(
+                CompositeKey {
+                    prefix: prefix.to_string(),
+                    key: key.clone(),
+                },
+                value.clone(),
+            );
+    }
+
+    fn remove_from_storage(prefix: &str, key: KeyWrapper, storage: &StorageBuilder) {
+        storage
+            .float32_array_storage
+            .write()
+            .as_mut()
+            .unwrap()
+            .remove(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            });
+    }
+}
+
+impl PreparedReadable for Vec<f32> {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .float32_array_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(value.clone())))
+    }
+}
+
+impl<'referred_data> Readable<'referred_data> for &'referred_data [f32] {
+    fn read_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &'referred_data Storage,
+    ) -> Option<Self> {
+        storage
+            .float32_array_storage
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|a| a.as_slice())
+    }
+
+    fn read_range_from_storage<'prefix, PrefixRange, KeyRange>(
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+        storage: &'referred_data Storage,
+    ) -> Vec<(&'referred_data CompositeKey, Self)>
+    where
+        PrefixRange: std::ops::RangeBounds<&'prefix str>,
+        KeyRange: std::ops::RangeBounds<KeyWrapper>,
+    {
+        storage
+            .float32_array_storage
+            .iter()
+            .filter(|(k, _)| {
+                prefix_range.contains(&k.prefix.as_str()) && key_range.contains(&k.key)
+            })
+            .map(|(k, v)| (k, v.as_slice()))
+            .collect()
+    }
+
+    fn count(storage: &Storage) -> Result<usize, Box<dyn ChromaError>> {
+        Ok(storage.float32_array_storage.iter().len())
+    }
+
+    fn contains(prefix: &str, key: KeyWrapper, storage: &'referred_data Storage) -> bool {
+        storage
+            .float32_array_storage
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .is_some()
+    }
+
+    fn rank(prefix: &str, key: KeyWrapper, storage: &'referred_data Storage) -> usize {
+        storage
+            .float32_array_storage
+            .range(
+                ..CompositeKey {
+                    prefix: prefix.to_string(),
+                    key,
+                },
+            )
+            .count()
+    }
+}
+
+impl Writeable for Vec<u8> {
+    fn write_to_storage(prefix: &str, key: KeyWrapper, value: Self, storage: &StorageBuilder) {
+        storage.bytes_storage.write().as_mut().unwrap().insert(
+            CompositeKey {
+                prefix: prefix.to_string(),
+                key: key.clone(),
+            },
+            value.clone(),
+        );
+    }
+
+    fn remove_from_storage(prefix: &str, key: KeyWrapper, storage: &StorageBuilder) {
+        storage
+            .bytes_storage
+            .write()
+            .as_mut()
+            .unwrap()
+            .remove(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            });
+    }
+}
+
+impl PreparedReadable for Vec<u8> {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .bytes_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(value.clone())))
+    }
+}
+
+impl<'referred_data> Readable<'referred_data> for &'referred_data [u8] {
+    fn read_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &'referred_data Storage,
+    ) -> Option<Self> {
+        storage
+            .bytes_storage
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|a| a.as_slice())
+    }
+
+    fn read_range_from_storage<'prefix, PrefixRange, KeyRange>(
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+        storage: &'referred_data Storage,
+    ) -> Vec<(&'referred_data CompositeKey, Self)>
+    where
+        PrefixRange: std::ops::RangeBounds<&'prefix str>,
+        KeyRange: std::ops::RangeBounds<KeyWrapper>,
+    {
+        storage
+            .bytes_storage
+            .iter()
+            .filter(|(k, _)| {
+                prefix_range.contains(&k.prefix.as_str()) && key_range.contains(&k.key)
+            })
+            .map(|(k, v)| (k, v.as_slice()))
+            .collect()
+    }
+
+    fn count(storage: &Storage) -> Result<usize, Box<dyn ChromaError>> {
+        Ok(storage.bytes_storage.iter().len())
+    }
+
+    fn contains(prefix: &str, key: KeyWrapper, storage: &'referred_data Storage) -> bool {
+        storage
+            .bytes_storage
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .is_some()
+    }
+
+    fn rank(prefix: &str, key: KeyWrapper, storage: &'referred_data Storage) -> usize {
+        storage
+            .bytes_storage
+            .range(
+                ..CompositeKey {
+                    prefix: prefix.to_string(),
+                    key,
+                },
+            )
+            .count()
+    }
+}
+
 impl Writeable for RoaringBitmap {
     fn write_to_storage(prefix: &str, key: KeyWrapper, value: Self, storage: &StorageBuilder) {
         storage
@@ -246,6 +520,25 @@ impl Writeable for RoaringBitmap {
     }
 }
 
+impl PreparedReadable for RoaringBitmap {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .roaring_bitmap_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(value.clone())))
+    }
+}
+
 impl<'referred_data> Readable<'referred_data> for RoaringBitmap {
     fn read_from_storage(prefix: &str, key: KeyWrapper, storage: &Storage) -> Option<Self> {
         storage
@@ -408,6 +701,25 @@ impl Writeable for u32 {
     }
 }
 
+impl PreparedReadable for u32 {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        Ok(storage
+            .u32_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .map(|value| Self::prepare(*value)))
+    }
+}
+
 impl<'referred_data> Readable<'referred_data> for u32 {
     fn read_from_storage(prefix: &str, key: KeyWrapper, storage: &Storage) -> Option<Self> {
         storage
@@ -610,6 +922,49 @@ impl Writeable for &SpannPostingList<'_> {
     }
 }
 
+impl PreparedReadable for &DataRecord<'_> {
+    fn read_prepared_from_storage(
+        prefix: &str,
+        key: KeyWrapper,
+        storage: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        let id = storage
+            .data_record_id_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key: key.clone(),
+            })
+            .cloned();
+        let embedding = storage
+            .data_record_embedding_storage
+            .read()
+            .as_ref()
+            .unwrap()
+            .get(&CompositeKey {
+                prefix: prefix.to_string(),
+                key,
+            })
+            .cloned();
+        Ok(id
+            .zip(embedding)
+            .map(|(id, embedding)| (id, embedding, None, None)))
+    }
+}
+
+impl PreparedReadable for &SpannPostingList<'_> {
+    fn read_prepared_from_storage(
+        _: &str,
+        _: KeyWrapper,
+        _: &StorageBuilder,
+    ) -> Result<Option<Self::PreparedValue>, Box<dyn ChromaError>> {
+        // The memory blockfile does not store spann posting lists
+        Err(Box::new(UnsupportedValueError("Spann posting list")))
+    }
+}
+
 impl<'referred_data> Readable<'referred_data> for DataRecord<'referred_data> {
     fn read_from_storage(
         prefix: &str,
@@ -733,6 +1088,12 @@ pub struct StorageBuilder {
     // UInt32 Array Value
     #[allow(clippy::type_complexity)]
     uint32_array_storage: Arc<RwLock<Option<BTreeMap<CompositeKey, Vec<u32>>>>>,
+    // Float32 Array Value
+    #[allow(clippy::type_complexity)]
+    float32_array_storage: Arc<RwLock<Option<BTreeMap<CompositeKey, Vec<f32>>>>>,
+    // Bytes Value
+    #[allow(clippy::type_complexity)]
+    bytes_storage: Arc<RwLock<Option<BTreeMap<CompositeKey, Vec<u8>>>>>,
     // Data Record Fields
     #[allow(clippy::type_complexity)]
     data_record_id_storage: Arc<RwLock<Option<BTreeMap<CompositeKey, String>>>>,
@@ -741,6 +1102,31 @@ pub struct StorageBuilder {
     pub(super) id: uuid::Uuid,
 }
 
+impl StorageBuilder {
+    /// Remove every key in the range. A blockfile only uses the maps of its value type.
+    pub(super) fn remove_range(&self, range: &CompositeKeyRange) {
+        fn retain_outside<V>(
+            map: &RwLock<Option<BTreeMap<CompositeKey, V>>>,
+            range: &CompositeKeyRange,
+        ) {
+            map.write()
+                .as_mut()
+                .unwrap()
+                .retain(|key, _| !range.contains(key));
+        }
+        retain_outside(&self.bool_storage, range);
+        retain_outside(&self.string_value_storage, range);
+        retain_outside(&self.u32_storage, range);
+        retain_outside(&self.f32_storage, range);
+        retain_outside(&self.roaring_bitmap_storage, range);
+        retain_outside(&self.uint32_array_storage, range);
+        retain_outside(&self.float32_array_storage, range);
+        retain_outside(&self.bytes_storage, range);
+        retain_outside(&self.data_record_id_storage, range);
+        retain_outside(&self.data_record_embedding_storage, range);
+    }
+}
+
 #[derive(Clone)]
 pub struct Storage {
     bool_storage: Arc<BTreeMap<CompositeKey, bool>>,
@@ -754,12 +1140,54 @@ pub struct Storage {
     roaring_bitmap_storage: Arc<BTreeMap<CompositeKey, RoaringBitmap>>,
     // UInt32 Array Value
     uint32_array_storage: Arc<BTreeMap<CompositeKey, Vec<u32>>>,
+    // Float32 Array Value
+    float32_array_storage: Arc<BTreeMap<CompositeKey, Vec<f32>>>,
+    // Bytes Value
+    bytes_storage: Arc<BTreeMap<CompositeKey, Vec<u8>>>,
     // Data Record Fields
     data_record_id_storage: Arc<BTreeMap<CompositeKey, String>>,
     data_record_embedding_storage: Arc<BTreeMap<CompositeKey, Vec<f32>>>,
     pub(super) id: uuid::Uuid,
 }
 
+impl Storage {
+    /// The number of keys in the storage. A blockfile only uses the maps of its value type.
+    pub(super) fn len(&self) -> usize {
+        self.bool_storage.len()
+            + self.string_value_storage.len()
+            + self.u32_storage.len()
+            + self.f32_storage.len()
+            + self.roaring_bitmap_storage.len()
+            + self.uint32_array_storage.len()
+            + self.float32_array_storage.len()
+            + self.bytes_storage.len()
+            + self.data_record_id_storage.len()
+    }
+
+    /// Every distinct prefix in the storage with its number of keys, in prefix order.
+    pub(super) fn prefix_counts(&self) -> Vec<(String, usize)> {
+        fn count_prefixes<V>(
+            map: &BTreeMap<CompositeKey, V>,
+            prefix_counts: &mut BTreeMap<String, usize>,
+        ) {
+            for key in map.keys() {
+                *prefix_counts.entry(key.prefix.clone()).or_default() += 1;
+            }
+        }
+        let mut prefix_counts = BTreeMap::new();
+        count_prefixes(&self.bool_storage, &mut prefix_counts);
+        count_prefixes(&self.string_value_storage, &mut prefix_counts);
+        count_prefixes(&self.u32_storage, &mut prefix_counts);
+        count_prefixes(&self.f32_storage, &mut prefix_counts);
+        count_prefixes(&self.roaring_bitmap_storage, &mut prefix_counts);
+        count_prefixes(&self.uint32_array_storage, &mut prefix_counts);
+        count_prefixes(&self.float32_array_storage, &mut prefix_counts);
+        count_prefixes(&self.bytes_storage, &mut prefix_counts);
+        count_prefixes(&self.data_record_id_storage, &mut prefix_counts);
+        prefix_counts.into_iter().collect()
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct StorageManager {
     read_cache: Arc<RwLock<HashMap<uuid::Uuid, Storage>>>,
@@ -789,6 +1217,8 @@ impl StorageManager {
             f32_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
             roaring_bitmap_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
             uint32_array_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
+            float32_array_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
+            bytes_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
             data_record_id_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
             data_record_embedding_storage: Arc::new(RwLock::new(Some(BTreeMap::new()))),
             id,
@@ -798,6 +1228,39 @@ impl StorageManager {
         builder
     }
 
+    /// Create a builder that starts with the data of the committed storage `id`.
+    pub(super) fn fork(&self, id: uuid::Uuid) -> Option<StorageBuilder> {
+        let storage = self.get(id)?;
+        let builder = StorageBuilder {
+            bool_storage: Arc::new(RwLock::new(Some(storage.bool_storage.as_ref().clone()))),
+            string_value_storage: Arc::new(RwLock::new(Some(
+                storage.string_value_storage.as_ref().clone(),
+            ))),
+            u32_storage: Arc::new(RwLock::new(Some(storage.u32_storage.as_ref().clone()))),
+            f32_storage: Arc::new(RwLock::new(Some(storage.f32_storage.as_ref().clone()))),
+            roaring_bitmap_storage: Arc::new(RwLock::new(Some(
+                storage.roaring_bitmap_storage.as_ref().clone(),
+            ))),
+            uint32_array_storage: Arc::new(RwLock::new(Some(
+                storage.uint32_array_storage.as_ref().clone(),
+            ))),
+            float32_array_storage: Arc::new(RwLock::new(Some(
+                storage.float32_array_storage.as_ref().clone(),
+            ))),
+            bytes_storage: Arc::new(RwLock::new(Some(storage.bytes_storage.as_ref().clone()))),
+            data_record_id_storage: Arc::new(RwLock::new(Some(
+                storage.data_record_id_storage.as_ref().clone(),
+            ))),
+            data_record_embedding_storage: Arc::new(RwLock::new(Some(
+                storage.data_record_embedding_storage.as_ref().clone(),
+            ))),
+            id: uuid::Uuid::new_v4(),
+        };
+        let mut cache_guard = self.write_cache.write();
+        cache_guard.insert(builder.id, builder.clone());
+        Some(builder)
+    }
+
     pub(super) fn commit(&self, id: uuid::Uuid) -> Storage {
         let mut write_cache_guard = self.write_cache.write();
         let builder = write_cache_guard.remove(&id).unwrap();
@@ -805,6 +1268,8 @@ impl StorageManager {
             bool_storage: builder.bool_storage.write().take().unwrap().into(),
             string_value_storage: builder.string_value_storage.write().take().unwrap().into(),
             uint32_array_storage: builder.uint32_array_storage.write().take().unwrap().into(),
+            float32_array_storage: builder.float32_array_storage.write().take().unwrap().into(),
+            bytes_storage: builder.bytes_storage.write().take().unwrap().into(),
             roaring_bitmap_storage: builder
                 .roaring_bitmap_storage
                 .write()
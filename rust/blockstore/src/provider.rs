@@ -1,3 +1,4 @@
+use crate::arrow::prefetch::{PrefetchOptions, PrefetchProgress, PrefetchScope};
 use crate::arrow::provider::BlockfileReaderOptions;
 use crate::arrow::root::RootReader;
 use crate::BlockfileWriterOptions;
@@ -113,13 +114,33 @@ impl BlockfileProvider {
         prefix_path: &str,
     ) -> Result<usize, Box<dyn ChromaError>> {
         match self {
-            BlockfileProvider::HashMapBlockfileProvider(_) => unimplemented!(),
+            // Memory blockfiles have no blocks to fetch
+            BlockfileProvider::HashMapBlockfileProvider(_) => Ok(0),
             BlockfileProvider::ArrowBlockfileProvider(provider) => provider
                 .prefetch(id, prefix_path)
                 .await
                 .map_err(|e| Box::new(e) as _),
         }
     }
+
+    /// Prefetch only the blocks of a blockfile that cover the scope, see
+    /// `ArrowBlockfileProvider::prefetch_scope()`.
+    pub async fn prefetch_scope<'new, K: Key + ArrowReadableKey<'new> + 'new>(
+        &self,
+        id: &uuid::Uuid,
+        prefix_path: &str,
+        scope: &PrefetchScope,
+        options: PrefetchOptions,
+    ) -> Result<PrefetchProgress, Box<dyn ChromaError>> {
+        match self {
+            // Memory blockfiles have no blocks to fetch
+            BlockfileProvider::HashMapBlockfileProvider(_) => Ok(PrefetchProgress::default()),
+            BlockfileProvider::ArrowBlockfileProvider(provider) => provider
+                .prefetch_scope::<K>(id, prefix_path, scope, options)
+                .await
+                .map_err(|e| Box::new(e) as _),
+        }
+    }
 }
 
 // =================== Configurable ===================
@@ -1,3 +1,4 @@
+use crate::arrow::provider::BlockFlushError;
 use crate::arrow::root::{RootWriter, Version};
 use crate::arrow::sparse_index::SparseIndexWriter;
 use crate::arrow::sparse_index::{AddError, SetCountError};
@@ -5,6 +6,7 @@ use crate::key::CompositeKey;
 use crate::RootManager;
 use chroma_cache::nop::NopCache;
 use chroma_error::ChromaError;
+use chroma_storage::admissioncontrolleds3::StorageRequestPriority;
 use chroma_storage::Storage;
 use uuid::Uuid;
 
@@ -47,7 +49,9 @@ pub async fn create_test_sparse_index(
     // Create and save the sparse index file
     let root_writer = RootWriter::new(Version::V1_1, root_id, sparse_index, prefix_path);
     let root_manager = RootManager::new(storage.clone(), Box::new(NopCache));
-    root_manager.flush::<&str>(&root_writer).await?;
+    root_manager
+        .flush::<&str>(&root_writer, StorageRequestPriority::P0)
+        .await?;
 
     Ok(root_id)
 }
@@ -76,6 +80,12 @@ impl From<SetCountError> for Box<dyn ChromaError> {
     }
 }
 
+impl From<BlockFlushError> for Box<dyn ChromaError> {
+    fn from(error: BlockFlushError) -> Self {
+        Box::new(error)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
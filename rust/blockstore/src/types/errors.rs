@@ -8,6 +8,8 @@ pub enum BlockfileError {
     NotFoundError,
     #[error("Block not found")]
     BlockNotFound,
+    #[error("Key was provided out of order to a writer with ordered mutations")]
+    KeyOutOfOrder,
 }
 
 impl ChromaError for BlockfileError {
@@ -15,6 +17,7 @@ impl ChromaError for BlockfileError {
         match self {
             BlockfileError::NotFoundError => ErrorCodes::InvalidArgument,
             BlockfileError::BlockNotFound => ErrorCodes::Internal,
+            BlockfileError::KeyOutOfOrder => ErrorCodes::InvalidArgument,
         }
     }
 }
@@ -13,11 +13,13 @@ pub enum BlockfileFlusher {
 }
 
 impl BlockfileFlusher {
+    /// Write the blockfile to storage. If this fails, calling it again completes the flush
+    /// without rewriting what was already written. Flushing again after a success is a no-op.
     pub async fn flush<
         K: Key + Into<KeyWrapper> + ArrowWriteableKey,
         V: Value + Writeable + ArrowWriteableValue,
     >(
-        self,
+        &self,
     ) -> Result<(), Box<dyn ChromaError>> {
         match self {
             BlockfileFlusher::MemoryBlockfileFlusher(_) => Ok(()),
@@ -34,14 +36,14 @@ impl BlockfileFlusher {
 
     pub fn count(&self) -> u64 {
         match self {
-            BlockfileFlusher::MemoryBlockfileFlusher(_) => unimplemented!(), // no op
+            BlockfileFlusher::MemoryBlockfileFlusher(flusher) => flusher.count(),
             BlockfileFlusher::ArrowBlockfileFlusher(flusher) => flusher.count(),
         }
     }
 
     pub fn num_entries(&self) -> usize {
         match self {
-            BlockfileFlusher::MemoryBlockfileFlusher(_) => unimplemented!(),
+            BlockfileFlusher::MemoryBlockfileFlusher(flusher) => flusher.num_entries(),
             BlockfileFlusher::ArrowBlockfileFlusher(flusher) => flusher.num_entries(),
         }
     }
@@ -29,3 +29,34 @@ impl Key for u32 {
         4
     }
 }
+
+impl Key for u64 {
+    fn get_size(&self) -> usize {
+        8
+    }
+}
+
+impl Key for i64 {
+    fn get_size(&self) -> usize {
+        8
+    }
+}
+
+/// A key of arbitrary bytes, ordered lexicographically. Displayed as lowercase hex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytesKey<'referred_data>(pub &'referred_data [u8]);
+
+impl Display for BytesKey<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Key for BytesKey<'_> {
+    fn get_size(&self) -> usize {
+        self.0.len()
+    }
+}
@@ -39,6 +39,17 @@ impl<
         }
     }
 
+    /// Gets the values of many keys at once, in the order of the input.
+    pub async fn get_many(
+        &'referred_data self,
+        keys: impl IntoIterator<Item = (String, K)>,
+    ) -> Result<Vec<Option<V>>, Box<dyn ChromaError>> {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => Ok(reader.get_many(keys)),
+            BlockfileReader::ArrowBlockfileReader(reader) => reader.get_many(keys).await,
+        }
+    }
+
     pub async fn contains(
         &'referred_data self,
         prefix: &str,
@@ -63,6 +74,18 @@ impl<
         }
     }
 
+    /// Returns every distinct prefix in the blockfile with its number of keys, in prefix order.
+    pub async fn get_prefixes_with_counts(
+        &'referred_data self,
+    ) -> Result<Vec<(String, usize)>, Box<dyn ChromaError>> {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => Ok(reader.get_prefixes_with_counts()),
+            BlockfileReader::ArrowBlockfileReader(reader) => {
+                reader.get_prefixes_with_counts().await
+            }
+        }
+    }
+
     pub fn get_range_stream<'prefix, PrefixRange, KeyRange>(
         &'referred_data self,
         prefix_range: PrefixRange,
@@ -109,6 +132,54 @@ impl<
         }
     }
 
+    /// Like `get_range_stream`, but yields records in descending (prefix, key) order.
+    pub fn get_range_stream_rev<'prefix, PrefixRange, KeyRange>(
+        &'referred_data self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> impl Stream<Item = Result<(&'referred_data str, K, V), Box<dyn ChromaError>>>
+           + 'referred_data
+           + Send
+    where
+        PrefixRange: RangeBounds<&'prefix str> + Clone + Send + 'referred_data,
+        KeyRange: RangeBounds<K> + Clone + Send + 'referred_data,
+        K: Sync + Send,
+        V: Sync + Send,
+    {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => {
+                match reader.get_range_iter_rev(prefix_range, key_range) {
+                    Ok(r) => futures::stream::iter(r.map(Ok)).boxed(),
+                    Err(e) => futures::stream::iter(vec![Err(e)]).boxed(),
+                }
+            }
+
+            BlockfileReader::ArrowBlockfileReader(reader) => {
+                reader.get_range_stream_rev(prefix_range, key_range).boxed()
+            }
+        }
+    }
+
+    /// Like `get_range`, but returns records in descending (prefix, key) order.
+    pub async fn get_range_rev<'prefix, PrefixRange, KeyRange>(
+        &'referred_data self,
+        prefix_range: PrefixRange,
+        key_range: KeyRange,
+    ) -> Result<Vec<(&'referred_data str, K, V)>, Box<dyn ChromaError>>
+    where
+        PrefixRange: RangeBounds<&'prefix str> + Clone,
+        KeyRange: RangeBounds<K> + Clone,
+    {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => reader
+                .get_range_iter_rev(prefix_range, key_range)
+                .map(|i| i.collect()),
+            BlockfileReader::ArrowBlockfileReader(reader) => {
+                reader.get_range_rev(prefix_range, key_range).await
+            }
+        }
+    }
+
     pub fn id(&self) -> uuid::Uuid {
         match self {
             BlockfileReader::MemoryBlockfileReader(reader) => reader.id(),
@@ -118,7 +189,8 @@ impl<
 
     pub async fn load_blocks_for_keys(&self, keys: impl IntoIterator<Item = (String, K)>) {
         match self {
-            BlockfileReader::MemoryBlockfileReader(_reader) => unimplemented!(),
+            // Memory blockfiles have no blocks to load
+            BlockfileReader::MemoryBlockfileReader(_reader) => {}
             BlockfileReader::ArrowBlockfileReader(reader) => {
                 reader.load_blocks_for_keys(keys).await
             }
@@ -130,7 +202,7 @@ impl<
         prefixes: impl IntoIterator<Item = &'prefix str>,
     ) {
         match self {
-            BlockfileReader::MemoryBlockfileReader(_reader) => unimplemented!(),
+            BlockfileReader::MemoryBlockfileReader(_reader) => {}
             BlockfileReader::ArrowBlockfileReader(reader) => {
                 reader.load_blocks_for_prefixes(prefixes).await
             }
@@ -147,6 +219,32 @@ impl<
             BlockfileReader::ArrowBlockfileReader(reader) => reader.rank(prefix, key).await,
         }
     }
+
+    /// Returns the key and value at the given position among the keys of the prefix, sorted in
+    /// ascending order, or None if the prefix has fewer keys. This is the inverse of `.rank()`
+    /// within a prefix.
+    pub async fn get_by_rank(
+        &'referred_data self,
+        prefix: &str,
+        rank: usize,
+    ) -> Result<Option<(K, V)>, Box<dyn ChromaError>> {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => Ok(reader.get_by_rank(prefix, rank)),
+            BlockfileReader::ArrowBlockfileReader(reader) => reader.get_by_rank(prefix, rank).await,
+        }
+    }
+
+    /// Returns `n` distinct records picked uniformly at random from the whole blockfile, in
+    /// ascending order, or every record if the blockfile holds fewer.
+    pub async fn sample(
+        &'referred_data self,
+        n: usize,
+    ) -> Result<Vec<(&'referred_data str, K, V)>, Box<dyn ChromaError>> {
+        match self {
+            BlockfileReader::MemoryBlockfileReader(reader) => Ok(reader.sample(n)),
+            BlockfileReader::ArrowBlockfileReader(reader) => reader.sample(n).await,
+        }
+    }
 }
 
 impl<
@@ -19,6 +19,30 @@ impl Value for &[u32] {
     }
 }
 
+impl Value for Vec<f32> {
+    fn get_size(&self) -> usize {
+        self.len() * size_of::<f32>()
+    }
+}
+
+impl Value for &[f32] {
+    fn get_size(&self) -> usize {
+        std::mem::size_of_val(*self)
+    }
+}
+
+impl Value for Vec<u8> {
+    fn get_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Value for &[u8] {
+    fn get_size(&self) -> usize {
+        self.len()
+    }
+}
+
 impl Value for &str {
     fn get_size(&self) -> usize {
         self.len()
@@ -4,8 +4,9 @@ use crate::arrow::ordered_blockfile_writer::ArrowOrderedBlockfileWriter;
 use crate::arrow::types::{ArrowWriteableKey, ArrowWriteableValue};
 use crate::key::KeyWrapper;
 use crate::memory::reader_writer::MemoryBlockfileWriter;
-use crate::memory::storage::Writeable;
+use crate::memory::storage::{PreparedReadable, Writeable};
 use chroma_error::ChromaError;
+use std::ops::RangeBounds;
 
 #[derive(Clone)]
 pub enum BlockfileWriter {
@@ -80,20 +81,57 @@ impl BlockfileWriter {
         }
     }
 
-    pub async fn get_owned<
+    /// Delete every key of the prefix in the key range. With ordered mutations, the range must not
+    /// start before the last key provided to the writer, and afterwards only keys past the end of
+    /// the range are accepted.
+    pub async fn delete_range<
         K: Key + Into<KeyWrapper> + ArrowWriteableKey,
         V: Value + Writeable + ArrowWriteableValue,
+    >(
+        &self,
+        prefix: &str,
+        key_range: impl RangeBounds<K>,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        match self {
+            BlockfileWriter::MemoryBlockfileWriter(writer) => {
+                writer.delete_range::<K>(prefix, key_range)
+            }
+            BlockfileWriter::ArrowUnorderedBlockfileWriter(writer) => {
+                writer.delete_range::<K, V>(prefix, key_range).await
+            }
+            BlockfileWriter::ArrowOrderedBlockfileWriter(writer) => {
+                writer.delete_range::<K, V>(prefix, key_range).await
+            }
+        }
+    }
+
+    /// Delete every key of the prefix.
+    pub async fn delete_prefix<
+        K: Key + Into<KeyWrapper> + ArrowWriteableKey,
+        V: Value + Writeable + ArrowWriteableValue,
+    >(
+        &self,
+        prefix: &str,
+    ) -> Result<(), Box<dyn ChromaError>> {
+        self.delete_range::<K, V>(prefix, ..).await
+    }
+
+    pub async fn get_owned<
+        K: Key + Into<KeyWrapper> + ArrowWriteableKey,
+        V: Value + Writeable + ArrowWriteableValue + PreparedReadable,
     >(
         &self,
         prefix: &str,
         key: K,
     ) -> Result<Option<V::PreparedValue>, Box<dyn ChromaError>> {
         match self {
-            BlockfileWriter::MemoryBlockfileWriter(_) => todo!(),
+            BlockfileWriter::MemoryBlockfileWriter(writer) => writer.get_owned::<K, V>(prefix, key),
             BlockfileWriter::ArrowUnorderedBlockfileWriter(writer) => {
                 writer.get_owned::<K, V>(prefix, key).await
             }
-            BlockfileWriter::ArrowOrderedBlockfileWriter(_) => todo!(),
+            BlockfileWriter::ArrowOrderedBlockfileWriter(writer) => {
+                writer.get_owned::<K, V>(prefix, key).await
+            }
         }
     }
 
@@ -1,3 +1,6 @@
+use super::BlockfileError;
+use crate::key::{CompositeKey, CompositeKeyRange, KeyWrapper};
+use std::ops::Bound;
 use uuid::Uuid;
 
 #[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
@@ -13,6 +16,7 @@ pub struct BlockfileWriterOptions {
     pub(crate) fork_from: Option<Uuid>,
     #[allow(dead_code)]
     pub(crate) prefix_path: String,
+    pub(crate) memory_budget_bytes: Option<usize>,
 }
 
 impl BlockfileWriterOptions {
@@ -21,6 +25,7 @@ impl BlockfileWriterOptions {
             prefix_path,
             fork_from: None,
             mutation_ordering: BlockfileWriterMutationOrdering::default(),
+            memory_budget_bytes: None,
         }
     }
 
@@ -48,4 +53,114 @@ impl BlockfileWriterOptions {
         self.fork_from = Some(fork);
         self
     }
+
+    /// Bound the memory held by the pending changes of the writer. Once the budget is exceeded,
+    /// unordered Arrow writers spill changes to local temp files and read them back at commit.
+    /// Other writers ignore the budget. Providers that encrypt blocks at rest refuse to create
+    /// unordered Arrow writers with a budget, as spilled changes are not encrypted.
+    pub fn memory_budget_bytes(mut self, memory_budget_bytes: usize) -> Self {
+        self.memory_budget_bytes = Some(memory_budget_bytes);
+        self
+    }
+}
+
+/// Enforces the contract of `BlockfileWriterMutationOrdering::Ordered` for a writer: keys are
+/// provided in ascending order and every key is mutated at most once. Reads (`.get_owned()`)
+/// may repeat the last key, but cannot go back to an earlier one either.
+#[derive(Debug, Default)]
+pub(crate) struct MutationOrderChecker {
+    last_key: Option<CompositeKey>,
+    last_key_mutated: bool,
+    // Set when a range mutation covered the rest of this prefix, so only later prefixes are accepted.
+    exhausted_prefix: Option<String>,
+}
+
+impl MutationOrderChecker {
+    pub(crate) fn check_mutation(
+        &mut self,
+        prefix: &str,
+        key: &KeyWrapper,
+    ) -> Result<(), BlockfileError> {
+        self.check(prefix, key, true)
+    }
+
+    pub(crate) fn check_read(
+        &mut self,
+        prefix: &str,
+        key: &KeyWrapper,
+    ) -> Result<(), BlockfileError> {
+        self.check(prefix, key, false)
+    }
+
+    /// Checks a mutation of every key in the range. Afterwards, only keys past the end of the
+    /// range are accepted.
+    pub(crate) fn check_range_mutation(
+        &mut self,
+        range: &CompositeKeyRange,
+    ) -> Result<(), BlockfileError> {
+        if let Some(exhausted_prefix) = self.exhausted_prefix.as_deref() {
+            if range.prefix.as_str() <= exhausted_prefix {
+                return Err(BlockfileError::KeyOutOfOrder);
+            }
+        }
+        if let Some(last_key) = self.last_key.as_ref() {
+            let in_order = match range.start_bound() {
+                Bound::Included(start) => {
+                    start > last_key || (start == last_key && !self.last_key_mutated)
+                }
+                Bound::Excluded(start) => start >= last_key,
+                Bound::Unbounded => range.prefix > last_key.prefix,
+            };
+            if !in_order {
+                return Err(BlockfileError::KeyOutOfOrder);
+            }
+        }
+        match range.end_bound() {
+            Bound::Included(end) => {
+                self.last_key = Some(end.clone());
+                self.last_key_mutated = true;
+            }
+            Bound::Excluded(end) => {
+                self.last_key = Some(end.clone());
+                self.last_key_mutated = false;
+            }
+            Bound::Unbounded => {
+                self.last_key = None;
+                self.exhausted_prefix = Some(range.prefix.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn check(
+        &mut self,
+        prefix: &str,
+        key: &KeyWrapper,
+        mutation: bool,
+    ) -> Result<(), BlockfileError> {
+        if let Some(exhausted_prefix) = self.exhausted_prefix.as_deref() {
+            if prefix <= exhausted_prefix {
+                return Err(BlockfileError::KeyOutOfOrder);
+            }
+        }
+        let key = CompositeKey {
+            prefix: prefix.to_string(),
+            key: key.clone(),
+        };
+        match self.last_key.as_ref().map(|last_key| key.cmp(last_key)) {
+            Some(std::cmp::Ordering::Less) => Err(BlockfileError::KeyOutOfOrder),
+            Some(std::cmp::Ordering::Equal) => {
+                if mutation && self.last_key_mutated {
+                    return Err(BlockfileError::KeyOutOfOrder);
+                }
+                self.last_key_mutated |= mutation;
+                Ok(())
+            }
+            Some(std::cmp::Ordering::Greater) | None => {
+                self.last_key = Some(key);
+                self.last_key_mutated = mutation;
+                Ok(())
+            }
+        }
+    }
 }